@@ -0,0 +1,157 @@
+//! Throughput of building, updating and sampling from urns, compared with
+//! rebuilding `rand`'s `WeightedIndex` (which doesn't support insertions or
+//! removals), and with `ArenaUrn` for removals. Run with `cargo bench`.
+//!
+//! Every benchmark uses a seeded `StdRng`, so the inputs (and the sequence
+//! of sampled indexes) are the same across runs.
+
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use urn::{from_list, prelude::WeightedSampler, ArenaUrn, Urn, Weight};
+
+/// The sizes of the urns, from `10^2` to `10^7` elements
+const SIZES: [u32; 6] = [100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+/// A deterministic RNG for the benchmarks
+fn rng() -> StdRng {
+    StdRng::seed_from_u64(0x5eed)
+}
+
+/// `n` random weights in `[1, 255]` (for every width of `Weight`, so that
+/// the total weight never overflows)
+fn weights(n: u32) -> Vec<Weight> {
+    let mut rng = rng();
+    (0..n).map(|_| rng.gen_range(1..=255)).collect()
+}
+
+/// An urn of the no.s `0..n` with random positive weights
+fn urn(n: u32) -> Urn<u32> {
+    from_list(weights(n).into_iter().zip(0..).collect()).unwrap()
+}
+
+/// Fewer samples for the largest urns, whose setup takes a while
+fn sample_size(n: u32) -> usize {
+    if n >= 1_000_000 {
+        10
+    } else {
+        100
+    }
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for n in SIZES {
+        group.sample_size(sample_size(n));
+        let ws = weights(n);
+        group.bench_with_input(BenchmarkId::new("urn", n), &ws, |b, ws| {
+            b.iter_batched(
+                || ws.iter().copied().zip(0..).collect::<Vec<_>>(),
+                |elems: Vec<(Weight, u32)>| from_list(elems),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(
+            BenchmarkId::new("weighted_index", n),
+            &ws,
+            |b, ws| b.iter(|| WeightedIndex::new(ws).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn sample(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sample");
+    for n in SIZES {
+        group.sample_size(sample_size(n));
+        let urn = urn(n);
+        let mut r = rng();
+        group.bench_function(BenchmarkId::new("urn", n), |b| {
+            b.iter(|| *urn.sample_ref(&mut r))
+        });
+        let index = WeightedIndex::new(weights(n)).unwrap();
+        group.bench_function(BenchmarkId::new("weighted_index", n), |b| {
+            b.iter(|| index.sample(&mut r))
+        });
+    }
+    group.finish();
+}
+
+/// Inserting an element (followed by `uninsert`, to keep the size fixed),
+/// compared with rebuilding a `WeightedIndex` with one more weight
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for n in SIZES {
+        group.sample_size(sample_size(n));
+        let mut slot = Some(urn(n));
+        group.bench_function(BenchmarkId::new("urn", n), |b| {
+            b.iter(|| {
+                let urn = slot.take().unwrap().insert(1, n);
+                let (_, _, urn) = urn.uninsert();
+                slot = urn;
+            })
+        });
+        let mut ws = weights(n);
+        group.bench_function(
+            BenchmarkId::new("weighted_index_rebuild", n),
+            |b| {
+                b.iter(|| {
+                    ws.push(1);
+                    let index = WeightedIndex::new(&ws).unwrap();
+                    ws.pop();
+                    index
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Removing a random element (followed by re-inserting it, to keep the
+/// size fixed), compared with rebuilding a `WeightedIndex` without it
+fn remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    for n in SIZES {
+        group.sample_size(sample_size(n));
+        let mut slot = Some(urn(n));
+        let mut r = rng();
+        group.bench_function(BenchmarkId::new("urn", n), |b| {
+            b.iter(|| {
+                let urn = slot.take().unwrap();
+                let ((w, a), urn) = WeightedSampler::remove(urn, &mut r);
+                slot = urn.map(|urn| urn.insert(w, a));
+            })
+        });
+        let mut arena =
+            ArenaUrn::from_list(weights(n).into_iter().zip(0..).collect());
+        group.bench_function(BenchmarkId::new("arena", n), |b| {
+            b.iter(|| {
+                let i = r.gen_range(0..arena.weight());
+                let (w, a) = arena.remove_index(i);
+                arena.push(w, a);
+            })
+        });
+        let mut ws = weights(n);
+        group.bench_function(
+            BenchmarkId::new("weighted_index_rebuild", n),
+            |b| {
+                b.iter(|| {
+                    let k = r.gen_range(0..ws.len());
+                    let w = ws.swap_remove(k);
+                    let index = WeightedIndex::new(&ws).unwrap();
+                    ws.push(w);
+                    index
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, sample, insert, remove);
+criterion_main!(benches);
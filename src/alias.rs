@@ -0,0 +1,243 @@
+use rand_core::RngCore;
+
+use crate::{
+    sampler::WeightedSampler,
+    types::{widen, Index, IndexDraw, Weight},
+    urn::draw_index_with,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                        Alias tables (Vose's method)                        */
+/* -------------------------------------------------------------------------- */
+
+/// A sampling table built with Vose's alias method, which samples in
+/// constant time: the elements are spread over `n` columns of height equal
+/// to the total weight, each of which holds (part of) its own element and
+/// (part of) at most one other element, its alias.
+/// Sampling picks a column uniformly at random, then either the column's
+/// own element or its alias, so it draws two random indexes and does no
+/// search at all.
+/// The columns are computed with exact integer arithmetic, so every element
+/// is sampled with probability exactly proportional to its weight.
+///
+/// The table has to be rebuilt whenever an element is inserted or removed,
+/// so it suits distributions which are sampled far more often than they
+/// are updated.
+///
+/// Time complexity: `from_list`, `insert` and `remove` take `O(n)`,
+/// and `sample` takes `O(1)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AliasTable<T> {
+    elems: Vec<(Weight, T)>,
+    /// `cutoffs[k]` is the height of the part of column `k` which holds
+    /// element `k` (the rest of the column holds element `aliases[k]`)
+    cutoffs: Vec<Index>,
+    aliases: Vec<usize>,
+    total: Index,
+    draw: IndexDraw,
+}
+
+impl<T> AliasTable<T> {
+    /// Builds an alias table of the (weight, element) pairs in `elems`
+    /// (which may be empty).
+    /// Panics if the total weight exceeds `Index::MAX`.
+    /// Time complexity: `O(n)`.
+    pub fn from_list(elems: Vec<(Weight, T)>) -> Self {
+        let total = elems
+            .iter()
+            .try_fold(0, |total: Index, (w, _)| total.checked_add(widen(*w)))
+            .expect("the total weight of an `AliasTable` overflows");
+        let mut table = Self {
+            elems,
+            cutoffs: Vec::new(),
+            aliases: Vec::new(),
+            total,
+            draw: IndexDraw::default(),
+        };
+        table.rebuild();
+        table
+    }
+
+    /// Sets the strategy for drawing random indexes (see `IndexDraw`)
+    pub fn with_index_draw(mut self, draw: IndexDraw) -> Self {
+        self.draw = draw;
+        self
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether the table contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> Index {
+        self.total
+    }
+
+    /// Recomputes the columns from the elements' weights (Vose's method).
+    /// The weights are scaled by `n` (in `u128`s, so nothing overflows),
+    /// which makes every column exactly `total` high.
+    fn rebuild(&mut self) {
+        let (n, total) = (self.elems.len(), u128::from(self.total));
+        let mut heights: Vec<u128> = self
+            .elems
+            .iter()
+            .map(|(w, _)| u128::from(widen(*w)) * n as u128)
+            .collect();
+        self.cutoffs = vec![self.total; n];
+        self.aliases = (0..n).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&k| heights[k] < total);
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            large.pop();
+            // Column `s` is topped up with (part of) element `l`
+            self.cutoffs[s] = heights[s] as Index;
+            self.aliases[s] = l;
+            heights[l] -= total - heights[s];
+            if heights[l] < total {
+                small.push(l);
+            } else {
+                large.push(l);
+            }
+        }
+        // The remaining columns are exactly full (or everything has weight
+        // 0, in which case nothing can be sampled anyway)
+    }
+
+    /// Picks the position of an element using `rng`, with probability
+    /// proportional to its weight
+    fn draw_position<R: RngCore + ?Sized>(&self, rng: &mut R) -> usize {
+        let k = draw_index_with(rng, self.elems.len() as Index, self.draw);
+        let k = k as usize;
+        if draw_index_with(rng, self.total, self.draw) < self.cutoffs[k] {
+            k
+        } else {
+            self.aliases[k]
+        }
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`.
+    /// Panics if the table is empty.
+    /// Time complexity: `O(1)`.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        assert!(!self.is_empty(), "cannot sample from an empty AliasTable");
+        &self.elems[self.draw_position(rng)].1
+    }
+}
+
+impl<T> WeightedSampler<T> for AliasTable<T> {
+    /// Panics if the total weight exceeds `Index::MAX`
+    fn insert(mut self, w: Weight, a: T) -> Self {
+        self.total = self
+            .total
+            .checked_add(widen(w))
+            .expect("the total weight of an `AliasTable` overflows");
+        self.elems.push((w, a));
+        self.rebuild();
+        self
+    }
+
+    /// Panics if the table is empty
+    fn remove<R: RngCore + ?Sized>(
+        mut self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>) {
+        assert!(!self.is_empty(), "cannot remove from an empty AliasTable");
+        let (w, a) = self.elems.swap_remove(self.draw_position(rng));
+        self.total -= widen(w);
+        self.rebuild();
+        ((w, a), if self.is_empty() { None } else { Some(self) })
+    }
+
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        AliasTable::sample(self, rng)
+    }
+
+    fn weight(&self) -> Index {
+        AliasTable::weight(self)
+    }
+
+    fn len(&self) -> usize {
+        AliasTable::len(self)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every element gets exactly its share of the `n * total` cells
+    #[test]
+    fn columns_match_weights() {
+        let table =
+            AliasTable::from_list(vec![(3, 'a'), (0, 'b'), (6, 'c'), (1, 'd')]);
+        assert_eq!((table.len(), table.weight()), (4, 10));
+        let mut cells = [0; 4];
+        for k in 0..table.len() {
+            cells[k] += table.cutoffs[k];
+            cells[table.aliases[k]] += table.total - table.cutoffs[k];
+        }
+        assert_eq!(cells, [12, 0, 24, 4]);
+    }
+
+    // The columns are rebuilt after every insertion and removal
+    #[test]
+    fn insert_and_remove_until_empty() {
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        let mut table = AliasTable::from_list(Vec::new());
+        assert!(table.is_empty());
+        for k in 1..=5 {
+            table = table.insert(k as Weight, k);
+            assert_eq!(
+                (table.len(), table.weight()),
+                (k, (k * (k + 1) / 2) as Index)
+            );
+        }
+        let (mut len, mut total) = (table.len(), table.weight());
+        let mut table_opt = Some(table);
+        while let Some(table) = table_opt {
+            let ((w, a), rest) = table.remove(&mut rng);
+            assert_eq!(w as usize, a);
+            (len, total) = (len - 1, total - widen(w));
+            match &rest {
+                Some(rest) => {
+                    assert_eq!((rest.len(), rest.weight()), (len, total));
+                    assert!(a != *rest.sample(&mut rng));
+                }
+                None => assert_eq!((len, total), (0, 0)),
+            }
+            table_opt = rest;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty AliasTable")]
+    fn removing_from_an_empty_table_panics() {
+        let table = AliasTable::<char>::from_list(Vec::new());
+        let _ = table.remove(&mut rand::thread_rng());
+    }
+
+    #[test]
+    fn zero_weight_elements_are_never_sampled() {
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        let table = AliasTable::from_list(vec![(0, 'a'), (2, 'b'), (0, 'c')])
+            .with_index_draw(IndexDraw::Lemire);
+        for _ in 0..100 {
+            assert_eq!(table.sample(&mut rng), &'b');
+        }
+    }
+}
@@ -1,36 +1,25 @@
-#![allow(dead_code)]
+use crate::{
+    metrics::record_rebuild,
+    types::{Tree, Tree::*, Weight},
+};
 
-use core::panic;
-
-use crate::types::{Tree, Tree::*, Weight};
-
-/// Reverses the lowest `n` bits of the number `x`
+/// Reverses the lowest `n` bits of the number `x`.     
+/// In each iteration, we shift the accumulator `r` left to make room for
+/// a new bit, move the LSB of `x` into `r`, and shift `x` right to set it
+/// up for the next bit.
 fn reverse_bits(n: u32, x: u32) -> u32 {
-    /// Helper function, where `r` is the accumulator
-    /// that builds up the reversed bits (the result).     
-    /// In each recursive call, we:
-    /// - Shift `r` left to make room for a new bit
-    /// - Extract the LSB from `x` and adds it to `r`
-    /// - Decrements our counter `n` (terminating when `n = 0`)
-    /// - Shift `x` right to set it up for the next bit
-    fn go(r: u32, n: u32, x: u32) -> u32 {
-        if n == 0 {
-            r
-        } else {
-            go(r << 1 | x & 1, n - 1, x >> 1)
-        }
+    let (mut r, mut x) = (0, x);
+    for _ in 0..n {
+        r = r << 1 | x & 1;
+        x >>= 1;
     }
-    go(0, n, x)
+    r
 }
 
 /// Smart constructor: builds a `Node` whose weight is the
 /// sum of the two subtree's weights
 fn node<T: Clone>(l: Tree<T>, r: Tree<T>) -> Tree<T> {
-    Node(
-        l.weight().wrapping_add(r.weight()),
-        Box::new(l),
-        Box::new(r),
-    )
+    Tree::node(Box::new(l), Box::new(r))
 }
 
 /// Alias for the `Leaf` constructor
@@ -40,74 +29,62 @@ fn leaf<T: Clone>(w: Weight, a: T) -> Tree<T> {
 
 /// Builds an *almost perfect* tree using the weights and values in `elems`.   
 /// (An almost perfect tree is one where the difference in depth between any
-/// two leaves is at most one).
+/// two leaves is at most one).     
+/// The elements are moved out of `elems` (rather than cloned).
 pub fn almost_perfect<T: Clone>(elems: Vec<(Weight, T)>) -> Tree<T> {
-    /// Helper function: recurses on the current `depth` of the tree
-    /// and the array `elem`s, either inserting two elements at a time
-    /// or one at a time
-    fn go<T: Clone>(
-        depth: u32,
-        index: u32,
-        elems: &[(Weight, T)],
-        og_size: u32,
-        perfect_depth: u32,
-        remainder: u32,
-    ) -> (Tree<T>, &[(Weight, T)], u32) {
-        if depth == 0 {
+    almost_perfect_iter(elems.into_iter())
+}
+
+/// Builds an *almost perfect* tree by consuming the (non-empty) iterator
+/// `elems` from front to back, without collecting it first.     
+/// Panics if `elems` yields fewer elements than `elems.len()`
+/// (any elements beyond `elems.len()` are ignored).
+pub fn almost_perfect_iter<T, I>(mut elems: I) -> Tree<T>
+where
+    T: Clone,
+    I: ExactSizeIterator<Item = (Weight, T)>,
+{
+    record_rebuild();
+    let og_size = elems.len() as u32;
+    let mut next = || {
+        elems.next().unwrap_or_else(|| {
+            panic!(
+                "Expected size {} but the input ran out of elements",
+                og_size
+            )
+        })
+    };
+
+    // The tree is a perfect tree of depth `perfect_depth`, whose "leaves"
+    // (the units below) are either single leaves or nodes with two leaves.
+    // The `remainder` units containing two leaves are spread out evenly
+    // by choosing the units whose indexes have reversed bits `< remainder`.
+    let perfect_depth = og_size.ilog2();
+    let remainder = og_size - (1 << perfect_depth);
+    let mut level: Vec<Tree<T>> = (0..1u32 << perfect_depth)
+        .map(|index| {
             if reverse_bits(perfect_depth, index) < remainder {
-                match elems {
-                    [(wl, tl), (wr, tr), tail @ ..] => (
-                        node(leaf(*wl, tl.clone()), leaf(*wr, tr.clone())),
-                        tail,
-                        index + 1,
-                    ),
-                    _ => panic!(
-                        "Expected size {} but got input of length {} instead",
-                        og_size,
-                        elems.len()
-                    ),
-                }
+                let (wl, tl) = next();
+                let (wr, tr) = next();
+                node(leaf(wl, tl), leaf(wr, tr))
             } else {
-                match elems {
-                    [(w, x), tail @ ..] => {
-                        (leaf(*w, x.clone()), tail, index + 1)
-                    }
-                    _ => panic!(
-                        "Expected size {} but got input of length {} instead",
-                        og_size,
-                        elems.len()
-                    ),
-                }
+                let (w, x) = next();
+                leaf(w, x)
             }
-        } else {
-            let (l, l_elems, l_index) =
-                go(depth - 1, index, elems, og_size, perfect_depth, remainder);
-            let (r, r_elems, r_index) = go(
-                depth - 1,
-                l_index,
-                l_elems,
-                og_size,
-                perfect_depth,
-                remainder,
-            );
-            (node(l, r), r_elems, r_index)
+        })
+        .collect();
+
+    // Build the perfect tree bottom-up, one level at a time
+    while level.len() > 1 {
+        let mut units = level.into_iter();
+        level = Vec::with_capacity(units.len() / 2);
+        while let (Some(l), Some(r)) = (units.next(), units.next()) {
+            level.push(node(l, r));
         }
     }
-
-    let original_size = elems.len() as u32;
-    let perfect_depth = original_size.ilog2();
-    let remainder = original_size - (1 << perfect_depth);
-    let depth = perfect_depth;
-    let index = 0;
-    let (tree, _, _) = go(
-        depth,
-        index,
-        elems.as_slice(),
-        original_size,
-        perfect_depth,
-        remainder,
-    );
-    tree
+    level
+        .pop()
+        .expect("an almost perfect tree has at least one leaf")
 }
 
 /* -------------------------------------------------------------------------- */
@@ -0,0 +1,571 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rand_core::RngCore;
+
+use crate::{
+    sampler::WeightedSampler,
+    types::{widen, Index, Weight},
+    urn::sample_weight_with,
+};
+
+/// A node of an `ArenaUrn`'s tree, stored in its arena.
+/// `Node`s refer to their children by their indexes in the arena, and cache
+/// the total and maximum weight of their leaves (like `Tree::Node`).
+#[derive(Debug, PartialEq, Clone)]
+enum Slot<T> {
+    Leaf(Weight, T),
+    Node(Index, Weight, usize, usize),
+    /// A slot on the free list
+    Free,
+}
+
+/// An urn whose tree lives in an arena: every node is a slot in a single
+/// `Vec`, and children are referred to by their indexes in it.
+///
+/// Slots vacated by removals are put on a free list and reused by later
+/// insertions, so once the arena has grown to fit the largest size of the
+/// urn, inserting and removing elements performs no allocations at all
+/// (unlike `Urn`, whose `insert` allocates new `Box`es along the insertion
+/// path). This suits churn-heavy workloads, e.g. event simulators.
+/// The tree has the same shape as an `Urn` of the same size: new elements
+/// are inserted along the same insertion paths (see `Urn::insert`), and
+/// removing an element splices the most recently inserted element into its
+/// bucket (see `Urn::remove`).
+///
+/// Time complexity: `push`, `remove_index` and `sample` take `O(log n)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArenaUrn<T> {
+    slots: Vec<Slot<T>>,
+    /// Indexes of the free slots in `slots`
+    free: Vec<usize>,
+    /// The index of the root (meaningless if the urn is empty)
+    root: usize,
+    size: u32,
+    /// The nodes along the most recently walked path, reused across
+    /// operations to avoid allocating
+    trail: Vec<usize>,
+    /// The version of the arena, which changes with every mutation
+    /// (see `snapshot_id`)
+    version: u64,
+    /// The version at which each slot in `slots` was last written
+    stamps: Vec<u64>,
+}
+
+/// The source of arena versions. Versions are unique across all arenas
+/// (only clones share them), so two arenas with the same version have the
+/// same contents.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Identifies the state of an `ArenaUrn` at some point in time
+/// (see `ArenaUrn::snapshot_id`)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct SnapshotId(u64);
+
+/// The slots of an `ArenaUrn` that changed between two of its snapshots,
+/// created by `ArenaUrn::snapshot_diff` and applied to a copy of the
+/// arena at the older snapshot by `ArenaUrn::apply_snapshot_diff`
+#[derive(Debug, PartialEq, Clone)]
+pub struct SnapshotDiff<T> {
+    base: SnapshotId,
+    target: SnapshotId,
+    /// The index, version and contents of every slot that changed
+    slots: Vec<(usize, u64, Slot<T>)>,
+    /// The length of the arena at the `target` snapshot
+    len: usize,
+    free: Vec<usize>,
+    root: usize,
+    size: u32,
+}
+
+impl<T> SnapshotDiff<T> {
+    /// The snapshot the diff applies to
+    pub fn base(&self) -> SnapshotId {
+        self.base
+    }
+
+    /// The snapshot an arena is at once the diff is applied
+    pub fn target(&self) -> SnapshotId {
+        self.target
+    }
+
+    /// The no. of slots (tree nodes) transferred by the diff
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the diff transfers no slots
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// The error returned by `ArenaUrn::apply_snapshot_diff` when the arena
+/// is not at the diff's base snapshot
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SnapshotMismatch {
+    /// The snapshot the diff applies to
+    pub expected: SnapshotId,
+    /// The snapshot the arena is at
+    pub found: SnapshotId,
+}
+
+impl fmt::Display for SnapshotMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "snapshot diff applies to snapshot {}, but the arena is at {}",
+            self.expected.0, self.found.0
+        )
+    }
+}
+
+impl Error for SnapshotMismatch {}
+
+impl<T> Default for ArenaUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ArenaUrn<T> {
+    /// Creates an empty `ArenaUrn`
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty `ArenaUrn` whose arena can hold `n` elements
+    /// (i.e. `2n - 1` nodes) without reallocating
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity((2 * n).saturating_sub(1)),
+            free: Vec::new(),
+            root: 0,
+            size: 0,
+            trail: Vec::new(),
+            version: next_version(),
+            stamps: Vec::with_capacity((2 * n).saturating_sub(1)),
+        }
+    }
+
+    /// Builds an `ArenaUrn` by inserting the elements of `elems` in order.
+    /// Time complexity: `O(n log n)`.
+    pub fn from_list(elems: Vec<(Weight, T)>) -> Self {
+        let mut urn = Self::with_capacity(elems.len());
+        for (w, a) in elems {
+            urn.push(w, a);
+        }
+        urn
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Whether the urn contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> Index {
+        if self.is_empty() {
+            0
+        } else {
+            self.weight_at(self.root)
+        }
+    }
+
+    /// The total weight of the subtree at slot `k`
+    fn weight_at(&self, k: usize) -> Index {
+        match self.slots[k] {
+            Slot::Leaf(w, _) => widen(w),
+            Slot::Node(w, _, _, _) => w,
+            Slot::Free => unreachable!("free slots are not part of the tree"),
+        }
+    }
+
+    /// The maximum leaf weight of the subtree at slot `k`
+    fn max_weight_at(&self, k: usize) -> Weight {
+        match self.slots[k] {
+            Slot::Leaf(w, _) => w,
+            Slot::Node(_, m, _, _) => m,
+            Slot::Free => unreachable!("free slots are not part of the tree"),
+        }
+    }
+
+    /// Overwrites slot `k` with `slot`, returning its old contents,
+    /// and stamps it with the current version
+    fn set(&mut self, k: usize, slot: Slot<T>) -> Slot<T> {
+        self.stamps[k] = self.version;
+        std::mem::replace(&mut self.slots[k], slot)
+    }
+
+    /// Puts `slot` into a free slot of the arena (growing the arena if there
+    /// is none), returning its index
+    fn alloc(&mut self, slot: Slot<T>) -> usize {
+        match self.free.pop() {
+            Some(k) => {
+                self.set(k, slot);
+                k
+            }
+            None => {
+                self.slots.push(slot);
+                self.stamps.push(self.version);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// Moves the contents out of slot `k`, putting it on the free list
+    fn release(&mut self, k: usize) -> Slot<T> {
+        self.free.push(k);
+        self.set(k, Slot::Free)
+    }
+
+    /// Recomputes the weights cached at the node at slot `k`
+    /// from its children
+    fn fix(&mut self, k: usize) {
+        if let Slot::Node(_, _, l, r) = self.slots[k] {
+            let w = self.weight_at(l).wrapping_add(self.weight_at(r));
+            let m = self.max_weight_at(l).max(self.max_weight_at(r));
+            self.set(k, Slot::Node(w, m, l, r));
+        }
+    }
+
+    /// Recomputes the weights cached at the nodes on the `trail`,
+    /// bottom-up
+    fn fix_trail(&mut self) {
+        while let Some(k) = self.trail.pop() {
+            self.fix(k);
+        }
+    }
+
+    /// Inserts element `a` with weight `w` at the end of the insertion path
+    /// `size` (see `Urn::insert`). The cached weights of the nodes along
+    /// the path are updated on the way down, so no trail is needed.
+    pub fn push(&mut self, w: Weight, a: T) {
+        self.version = next_version();
+        if self.is_empty() {
+            self.root = self.alloc(Slot::Leaf(w, a));
+            self.size = 1;
+            return;
+        }
+        let (mut k, mut path) = (self.root, self.size);
+        while let Slot::Node(wk, m, l, r) = self.slots[k] {
+            self.set(k, Slot::Node(wk.wrapping_add(widen(w)), m.max(w), l, r));
+            k = if path & 1 == 1 { r } else { l };
+            path >>= 1;
+        }
+        // The leaf at slot `k` becomes a node whose children are the old
+        // leaf and the new one
+        let old = self.set(k, Slot::Free);
+        let l = self.alloc(old);
+        let r = self.alloc(Slot::Leaf(w, a));
+        self.set(k, Slot::Node(0, 0, l, r));
+        self.fix(k);
+        self.size += 1;
+    }
+
+    /// Removes the most recently inserted element, returning it along with
+    /// the lower bound of its bucket (see `Urn::uninsert`).
+    /// Panics if the urn is empty.
+    fn uninsert(&mut self) -> ((Weight, T), Index) {
+        assert!(!self.is_empty(), "cannot remove from an empty ArenaUrn");
+        let (mut k, mut path) = (self.root, self.size - 1);
+        let mut lb: Index = 0;
+        let mut sibling = None;
+        while let Slot::Node(_, _, l, r) = self.slots[k] {
+            self.trail.push(k);
+            if path & 1 == 1 {
+                lb = lb.wrapping_add(self.weight_at(l));
+                (k, sibling) = (r, Some(l));
+            } else {
+                (k, sibling) = (l, Some(r));
+            }
+            path >>= 1;
+        }
+        let Slot::Leaf(w, a) = self.release(k) else {
+            unreachable!("the path ends at a leaf")
+        };
+        self.size -= 1;
+        // The sibling of the removed leaf takes the place of their parent
+        if let (Some(s), Some(parent)) = (sibling, self.trail.pop()) {
+            let slot = self.release(s);
+            self.set(parent, slot);
+        }
+        self.fix_trail();
+        ((w, a), lb)
+    }
+
+    /// Replaces the element at index `i` with element `a` with weight `w`,
+    /// returning the old element and its weight
+    fn replace_index(&mut self, i: Index, w: Weight, a: T) -> (Weight, T) {
+        let (mut k, mut i) = (self.root, i);
+        while let Slot::Node(_, _, l, r) = self.slots[k] {
+            self.trail.push(k);
+            let wl = self.weight_at(l);
+            if i < wl {
+                k = l;
+            } else {
+                (k, i) = (r, i - wl);
+            }
+        }
+        let Slot::Leaf(w_old, a_old) = self.set(k, Slot::Leaf(w, a)) else {
+            unreachable!("the path ends at a leaf")
+        };
+        self.fix_trail();
+        (w_old, a_old)
+    }
+
+    /// Removes the element at index `i`, returning it along with its weight.
+    /// The most recently inserted element is spliced into the bucket vacated
+    /// by the removed element (as in `Urn::remove_index`).
+    /// Panics if the urn is empty.
+    pub fn remove_index(&mut self, i: Index) -> (Weight, T) {
+        self.version = next_version();
+        let ((w, a), lb) = self.uninsert();
+        if self.is_empty() || (i >= lb && i < lb.wrapping_add(widen(w))) {
+            (w, a)
+        } else {
+            let j = if i < lb { i } else { i.wrapping_sub(widen(w)) };
+            self.replace_index(j, w, a)
+        }
+    }
+
+    /// Retrieves the weight and (a reference to) the element at index `i`.
+    /// Panics if the urn is empty.
+    pub fn lookup_index(&self, i: Index) -> (Weight, &T) {
+        assert!(!self.is_empty(), "cannot look up an empty ArenaUrn");
+        let (mut k, mut i) = (self.root, i);
+        loop {
+            match &self.slots[k] {
+                Slot::Leaf(w, a) => return (*w, a),
+                Slot::Node(_, _, l, r) => {
+                    let wl = self.weight_at(*l);
+                    if i < wl {
+                        k = *l;
+                    } else {
+                        (k, i) = (*r, i - wl);
+                    }
+                }
+                Slot::Free => {
+                    unreachable!("free slots are not part of the tree")
+                }
+            }
+        }
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`.
+    /// Panics if the urn is empty.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        self.lookup_index(sample_weight_with(rng, self.weight())).1
+    }
+
+    /// Identifies the current state of the arena. Every mutation moves the
+    /// arena to a new snapshot, and only clones of an arena share its
+    /// snapshots, so a copy of the arena (e.g. a replica in a live service)
+    /// can be brought up to date with `snapshot_diff` and
+    /// `apply_snapshot_diff`.
+    pub fn snapshot_id(&self) -> SnapshotId {
+        SnapshotId(self.version)
+    }
+
+    /// Collects the slots that changed since the earlier snapshot `since`
+    /// of this arena, to bring a copy of the arena at `since` up to date
+    /// with `apply_snapshot_diff`. Only the changed slots are cloned.
+    /// Panics if `since` is later than the current snapshot.
+    /// Time complexity: `O(n)` to find the changed slots.
+    pub fn snapshot_diff(&self, since: SnapshotId) -> SnapshotDiff<T>
+    where
+        T: Clone,
+    {
+        assert!(
+            since.0 <= self.version,
+            "snapshot {} is later than the arena's snapshot {}",
+            since.0,
+            self.version
+        );
+        let slots = self
+            .stamps
+            .iter()
+            .enumerate()
+            .filter(|&(_, &stamp)| stamp > since.0)
+            .map(|(k, &stamp)| (k, stamp, self.slots[k].clone()))
+            .collect();
+        SnapshotDiff {
+            base: since,
+            target: self.snapshot_id(),
+            slots,
+            len: self.slots.len(),
+            free: self.free.clone(),
+            root: self.root,
+            size: self.size,
+        }
+    }
+
+    /// Brings the arena up to date with the arena `diff` was collected
+    /// from (see `snapshot_diff`), overwriting only the slots that changed.
+    /// Returns an error (leaving the arena unchanged) if the arena is not
+    /// at the diff's base snapshot.
+    /// Time complexity: `O(d)`, where `d` is the size of the diff
+    /// (including the free list).
+    pub fn apply_snapshot_diff(
+        &mut self,
+        diff: SnapshotDiff<T>,
+    ) -> Result<(), SnapshotMismatch> {
+        if diff.base != self.snapshot_id() {
+            return Err(SnapshotMismatch {
+                expected: diff.base,
+                found: self.snapshot_id(),
+            });
+        }
+        // Slots are never removed from the arena, so it can only have grown
+        self.slots.resize_with(diff.len, || Slot::Free);
+        self.stamps.resize(diff.len, 0);
+        for (k, stamp, slot) in diff.slots {
+            self.slots[k] = slot;
+            self.stamps[k] = stamp;
+        }
+        self.free = diff.free;
+        (self.root, self.size) = (diff.root, diff.size);
+        self.version = diff.target.0;
+        Ok(())
+    }
+}
+
+impl<T> WeightedSampler<T> for ArenaUrn<T> {
+    fn insert(mut self, w: Weight, a: T) -> Self {
+        self.push(w, a);
+        self
+    }
+
+    fn remove<R: RngCore + ?Sized>(
+        mut self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>) {
+        let i = sample_weight_with(rng, self.weight());
+        let elem = self.remove_index(i);
+        (elem, if self.is_empty() { None } else { Some(self) })
+    }
+
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        ArenaUrn::sample(self, rng)
+    }
+
+    fn weight(&self) -> Index {
+        ArenaUrn::weight(self)
+    }
+
+    fn len(&self) -> usize {
+        ArenaUrn::len(self)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list_naive;
+
+    #[test]
+    fn arena_example() {
+        let mut urn = ArenaUrn::from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]);
+        assert_eq!((urn.len(), urn.weight()), (3, 9));
+        let buckets: Vec<char> =
+            (0..9).map(|i| *urn.lookup_index(i).1).collect();
+        assert_eq!(buckets, "RRBBBGGGG".chars().collect::<Vec<_>>());
+
+        // `B` (the last element) is moved into the bucket of `R`
+        assert_eq!(urn.remove_index(0), (2, 'R'));
+        assert_eq!(
+            (urn.lookup_index(0), urn.lookup_index(3)),
+            ((3, &'B'), (4, &'G'))
+        );
+        assert_eq!(urn.remove_index(3), (4, 'G'));
+        assert_eq!(urn.remove_index(0), (3, 'B'));
+        assert!(urn.is_empty());
+        assert_eq!(urn.weight(), 0);
+    }
+
+    // Every operation has the same outcome as on an `Urn`
+    #[test]
+    fn arena_agrees_with_urn() {
+        let elems: Vec<(Weight, u32)> =
+            (0..100).map(|k| ((k % 5) as Weight, k)).collect();
+        let mut arena = ArenaUrn::from_list(elems.clone());
+        let mut urn = from_list_naive(elems);
+        for k in 100..400 {
+            let i = Index::from(k * 7) % arena.weight();
+            let (removed, rest) = urn.unwrap().remove_index(i);
+            assert_eq!(arena.remove_index(i), removed);
+            urn = rest.map(|u| u.insert(1 + (k % 3) as Weight, k));
+            arena.push(1 + (k % 3) as Weight, k);
+        }
+        let urn = urn.unwrap();
+        assert_eq!((arena.len(), arena.weight()), (100, urn.weight()));
+        for i in 0..urn.weight() {
+            assert_eq!(arena.lookup_index(i), urn.tree.lookup_index(i));
+        }
+    }
+
+    // Once the arena has grown, churn reuses the freed slots
+    #[test]
+    fn arena_reuses_slots() {
+        let mut urn = ArenaUrn::from_list((0..50).map(|k| (1, k)).collect());
+        let slots = urn.slots.len();
+        for k in 0..1000 {
+            urn.remove_index(k % 50);
+            urn.push(1, k);
+        }
+        assert_eq!((urn.len(), urn.slots.len()), (50, slots));
+        assert_eq!(slots, 99);
+    }
+
+    #[test]
+    fn snapshot_diffs_transfer_changed_slots() {
+        let mut source = ArenaUrn::from_list((0..50).map(|k| (1, k)).collect());
+        let mut replica = source.clone();
+        let base = replica.snapshot_id();
+        assert_eq!(source.snapshot_id(), base);
+
+        source.remove_index(10);
+        source.push(3, 50);
+        source.push(2, 51);
+        let diff = source.snapshot_diff(base);
+        assert_eq!((diff.base(), diff.target()), (base, source.snapshot_id()));
+        assert!(!diff.is_empty() && diff.len() < source.slots.len() / 2);
+
+        replica.apply_snapshot_diff(diff.clone()).unwrap();
+        assert_eq!(replica, source);
+        for i in 0..source.weight() {
+            assert_eq!(replica.lookup_index(i), source.lookup_index(i));
+        }
+
+        // The replica has moved past the diff's base snapshot
+        assert_eq!(
+            replica.apply_snapshot_diff(diff),
+            Err(SnapshotMismatch {
+                expected: base,
+                found: source.snapshot_id()
+            })
+        );
+        // Arenas built independently never share snapshots
+        let mut other = ArenaUrn::from_list((0..50).map(|k| (1, k)).collect());
+        assert!(other
+            .apply_snapshot_diff(source.snapshot_diff(base))
+            .is_err());
+        assert!(source.snapshot_diff(source.snapshot_id()).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use rand_core::RngCore;
+
+use crate::{
+    shape::DepthStats,
+    stats::ChiSquare,
+    types::{Urn, Weight},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                              Sampling audits                               */
+/* -------------------------------------------------------------------------- */
+
+/// How often one element was drawn during an audit, compared to how often
+/// it should have been drawn
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AuditEntry<'a, T> {
+    /// The element's weight
+    pub weight: Weight,
+    /// The element itself
+    pub elem: &'a T,
+    /// The exact expected no. of draws `n * weight / total_weight`
+    pub expected: f64,
+    /// The no. of times the element was actually drawn
+    pub observed: u64,
+}
+
+/// The result of `Urn::audit`
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AuditReport<'a, T> {
+    /// The no. of draws
+    pub draws: u64,
+    /// The expected and observed counts of each element (in leaf order)
+    pub entries: Vec<AuditEntry<'a, T>>,
+    /// A chi-square goodness-of-fit test of the observed counts
+    pub chi_square: ChiSquare,
+    /// The largest difference `|observed - expected| / draws` over all
+    /// elements, i.e. the largest error in an element's empirical probability
+    pub max_deviation: f64,
+    /// The depths of the leaves of the urn's tree
+    pub depth: DepthStats,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Draws `n` samples from the urn using `rng`, and produces a report
+    /// comparing how often each element was drawn against its exact
+    /// probability (see `empirical_counts` and `chi_square`), along with
+    /// statistics about the shape of the urn's tree.
+    /// With the `serde` feature enabled, the report implements
+    /// `serde::Serialize`.
+    /// Time complexity: `O(size + n log size)`.
+    pub fn audit<R: RngCore + ?Sized>(
+        &self,
+        n: u64,
+        rng: &mut R,
+    ) -> AuditReport<'_, T> {
+        let observed = self.empirical_counts(n, rng);
+        let chi_square = self.chi_square(&observed);
+        let total = self.exact_weight() as f64;
+        let entries: Vec<AuditEntry<'_, T>> = self
+            .iter()
+            .zip(observed)
+            .map(|((weight, elem), observed)| AuditEntry {
+                weight,
+                elem,
+                expected: if total == 0.0 {
+                    0.0
+                } else {
+                    n as f64 * weight as f64 / total
+                },
+                observed,
+            })
+            .collect();
+        let max_deviation = if n == 0 {
+            0.0
+        } else {
+            entries
+                .iter()
+                .map(|e| (e.observed as f64 - e.expected).abs() / n as f64)
+                .fold(0.0, f64::max)
+        };
+        AuditReport {
+            draws: n,
+            entries,
+            chi_square,
+            max_deviation,
+            depth: self.tree.depth_stats(),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn audit_example() {
+        let urn = from_list(vec![(1, 'a'), (2, 'b'), (1, 'c')]).unwrap();
+        let report = urn.audit(1000, &mut StdRng::seed_from_u64(0));
+        assert_eq!(report.draws, 1000);
+        let expected: Vec<f64> =
+            report.entries.iter().map(|e| e.expected).collect();
+        assert_eq!(expected, vec![250.0, 500.0, 250.0]);
+        let observed: u64 = report.entries.iter().map(|e| e.observed).sum();
+        assert_eq!(observed, 1000);
+        assert!(report.max_deviation < 0.1);
+        assert_eq!(report.chi_square.degrees_of_freedom, 2);
+        assert_eq!(
+            report.depth,
+            DepthStats {
+                min: 1,
+                max: 2,
+                mean: 5.0 / 3.0
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn audit_report_is_serializable() {
+        fn assert_serialize<S: serde::Serialize>(_: &S) {}
+        let urn = from_list(vec![(1, 'a')]).unwrap();
+        assert_serialize(&urn.audit(10, &mut StdRng::seed_from_u64(0)));
+    }
+}
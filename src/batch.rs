@@ -0,0 +1,239 @@
+use rand_core::RngCore;
+
+use crate::types::{widen, Index, Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                     Batch sampling with replacement                        */
+/* -------------------------------------------------------------------------- */
+
+/// The result of `Urn::sample_k_with_replacement_distinct_report`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawReport<'a, T> {
+    /// The `k` samples (with replacement), in the order they were drawn
+    pub samples: Vec<&'a T>,
+    /// The distinct elements that were sampled (in leaf order), each paired
+    /// with its leaf position and the no. of times it was sampled
+    pub distinct: Vec<(u32, &'a T, usize)>,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Finds the leaf positions of the elements whose buckets contain the
+    /// (sorted) `indexes`, in a single left-to-right pass over the leaves.
+    /// Indexes beyond the urn's total weight map to the last leaf
+    /// (as with `sample_index`).     
+    /// Time complexity: `O(n + k)`, where `k = indexes.len()`.
+    pub(crate) fn positions_of_sorted_indexes(
+        &self,
+        indexes: &[Index],
+    ) -> Vec<u32> {
+        debug_assert!(indexes.is_sorted());
+        let last = self.size() - 1;
+        let mut positions = Vec::with_capacity(indexes.len());
+        let mut leaves = self.iter().enumerate();
+        let mut ub: Index = 0;
+        let mut pos = 0;
+        for &i in indexes {
+            // Advance to the first leaf whose bucket ends after `i`
+            while i >= ub {
+                match leaves.next() {
+                    Some((p, (w, _))) => {
+                        pos = p as u32;
+                        ub = ub.wrapping_add(widen(w));
+                    }
+                    None => {
+                        pos = last;
+                        break;
+                    }
+                }
+            }
+            positions.push(pos);
+        }
+        positions
+    }
+
+    /// Draws `k` samples with replacement using `rng`, and reports both the
+    /// samples themselves and the set of distinct elements that were hit
+    /// (along with their multiplicities).     
+    /// The `k` random indexes are sorted and then resolved in a single pass
+    /// over the leaves, so no hashing is needed.     
+    /// Time complexity: `O(n + k log k)`.
+    pub fn sample_k_with_replacement_distinct_report<R: RngCore + ?Sized>(
+        &self,
+        k: usize,
+        rng: &mut R,
+    ) -> DrawReport<'_, T> {
+        // Pair each index with the draw it belongs to, so that we can
+        // restore the order of the draws after sorting
+        let mut draws: Vec<(Index, usize)> =
+            (0..k).map(|d| (self.draw_index(rng), d)).collect();
+        draws.sort_unstable();
+        let indexes: Vec<Index> = draws.iter().map(|(i, _)| *i).collect();
+        let positions = self.positions_of_sorted_indexes(&indexes);
+
+        let mut distinct: Vec<(u32, &T, usize)> = vec![];
+        let mut by_draw: Vec<u32> = vec![0; k];
+        for (&pos, &(_, d)) in positions.iter().zip(draws.iter()) {
+            by_draw[d] = pos;
+            match distinct.last_mut() {
+                Some((p, _, count)) if *p == pos => *count += 1,
+                _ => {
+                    let (_, a) = self.tree.lookup_position(self.size(), pos);
+                    distinct.push((pos, a, 1));
+                }
+            }
+        }
+        // Every sampled position appears in `distinct` (which is sorted)
+        let samples = by_draw
+            .into_iter()
+            .map(|pos| {
+                let j = distinct.partition_point(|(p, _, _)| *p < pos);
+                distinct[j].1
+            })
+            .collect();
+        DrawReport { samples, distinct }
+    }
+
+    /// Draws `k` samples with replacement by systematic (low-variance)
+    /// sampling: a single random offset `u` in `[0, W)` is drawn using
+    /// `rng`, where `W` is the total weight, and the `j`-th sample is the
+    /// element whose bucket contains the index `(u + j * W) / k`.
+    /// The `k` indexes are thus equally spaced, so an element with weight
+    /// `w` is sampled either `floor(k * w / W)` or `ceil(k * w / W)` times,
+    /// and `k * w / W` times in expectation (as with `k` independent
+    /// draws, but with lower variance). This is the resampling step
+    /// commonly used by particle filters.
+    /// The samples are returned in leaf order.
+    /// Time complexity: `O(n + k)`.
+    pub fn sample_systematic<R: RngCore + ?Sized>(
+        &self,
+        k: usize,
+        rng: &mut R,
+    ) -> Vec<&T> {
+        let total = u128::from(self.weight());
+        let u = u128::from(self.draw_index(rng));
+        let mut samples = Vec::with_capacity(k);
+        let mut leaves = self.iter();
+        let (mut ub, mut current): (Index, Option<&T>) = (0, None);
+        for j in 0..k as u128 {
+            // Less than `W`, since `u < W` and `j < k`
+            let i = ((u + j * total) / k as u128) as Index;
+            // Advance to the first leaf whose bucket ends after `i`
+            // (or to the last leaf, if all weights are 0)
+            while i >= ub {
+                match leaves.next() {
+                    Some((w, a)) => {
+                        current = Some(a);
+                        ub = ub.wrapping_add(widen(w));
+                    }
+                    None => break,
+                }
+            }
+            samples.push(current.expect("urns are non-empty"));
+        }
+        samples
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                    Batch sampling without replacement                      */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone> Urn<T> {
+    /// Removes `k` distinct elements from the urn using `rng`, where each
+    /// element is drawn with probability proportional to its weight among
+    /// the elements that remain (as with `k` calls to `remove`).
+    /// Returns the removed elements (and their weights) in the order they
+    /// were drawn, along with the remaining urn (which is `None` if
+    /// `k == self.size()`), or `None` if `k > self.size()`.
+    /// Once the remaining elements all have weight 0, they are removed in an
+    /// arbitrary order.    
+    /// Time complexity: `O(k log n)`.
+    #[allow(clippy::type_complexity)]
+    pub fn sample_k_distinct<R: RngCore + ?Sized>(
+        self,
+        k: u32,
+        rng: &mut R,
+    ) -> Option<(Vec<(Weight, T)>, Option<Self>)> {
+        if k > self.size() {
+            return None;
+        }
+        let mut drawn = Vec::with_capacity(k as usize);
+        let mut urn_opt = Some(self);
+        for _ in 0..k {
+            // There are at least `k` elements, so the urn is non-empty
+            let urn = urn_opt.take()?;
+            let i = urn.draw_index(rng);
+            let (elem, rest) = urn.remove_index(i);
+            drawn.push(elem);
+            urn_opt = rest;
+        }
+        Some((drawn, urn_opt))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+    #[test]
+    fn distinct_report_example() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B')]).unwrap();
+        assert_eq!(
+            urn.positions_of_sorted_indexes(&[0, 1, 2, 4, 5, 9]),
+            vec![0, 0, 2, 2, 2, 2]
+        );
+
+        let report = urn
+            .sample_k_with_replacement_distinct_report(50, &mut thread_rng());
+        assert_eq!(report.samples.len(), 50);
+        let total: usize = report.distinct.iter().map(|(_, _, c)| c).sum();
+        assert_eq!(total, 50);
+        assert!(report.distinct.iter().all(|(_, a, _)| **a != 'G'));
+    }
+
+    #[test]
+    fn systematic_sampling_is_proportional() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (5, 'Y')]);
+        let urn = urn.unwrap();
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..20 {
+            // With 10 samples (the total weight), every element with weight
+            // `w` is drawn exactly `w` times
+            let samples: String =
+                urn.sample_systematic(10, &mut rng).into_iter().collect();
+            assert_eq!(samples, "RRBBBYYYYY");
+            // With 4 samples, it is drawn `floor(4 * w / 10)` or
+            // `ceil(4 * w / 10)` times
+            let samples = urn.sample_systematic(4, &mut rng);
+            let count = |a| samples.iter().filter(|&&&b| b == a).count();
+            assert!(count('R') <= 1 && (1..=2).contains(&count('B')));
+            assert_eq!(count('Y'), 2);
+        }
+        assert!(urn.sample_systematic(0, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn sample_k_distinct_example() {
+        let elems = vec![(2, 'R'), (0, 'G'), (3, 'B'), (1, 'Y')];
+        let urn = from_list(elems).unwrap();
+        assert_eq!(urn.clone().sample_k_distinct(5, &mut thread_rng()), None);
+
+        let (drawn, rest) =
+            urn.clone().sample_k_distinct(3, &mut thread_rng()).unwrap();
+        // The element of weight 0 is only drawn once nothing else is left
+        let mut drawn: Vec<char> = drawn.into_iter().map(|(_, a)| a).collect();
+        drawn.sort();
+        assert_eq!(drawn, vec!['B', 'R', 'Y']);
+        assert_eq!(rest.map(|u| u.to_vec()), Some(vec![(0, 'G')]));
+
+        let (drawn, rest) =
+            urn.sample_k_distinct(4, &mut thread_rng()).unwrap();
+        assert_eq!(drawn.len(), 4);
+        assert_eq!(rest, None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    types::{widen, Index, IndexDraw, Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                         Compact binary encoding                            */
+/* -------------------------------------------------------------------------- */
+
+/// The first bytes of every encoded urn
+const MAGIC: &[u8; 4] = b"URN\0";
+
+/// The version of the encoding produced by `Urn::to_bytes`
+const VERSION: u8 = 1;
+
+/// Errors arising when decoding an urn with `Urn::from_bytes`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CodecError {
+    /// The bytes don't start with the header of an encoded urn
+    BadHeader,
+    /// The bytes were encoded with an unsupported version of the encoding
+    UnsupportedVersion(u8),
+    /// The elements couldn't be decoded (or were followed by extra bytes)
+    Malformed,
+    /// The encoded urn has no elements (urns always contain at least one)
+    Empty,
+    /// A weight doesn't fit in a `Weight` (e.g. the urn was encoded with a
+    /// wider `Weight`), or the total weight doesn't fit in an `Index`
+    WeightOverflow,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::BadHeader => write!(f, "not an encoded urn"),
+            CodecError::UnsupportedVersion(v) => {
+                write!(f, "unsupported encoding version {}", v)
+            }
+            CodecError::Malformed => write!(f, "malformed urn elements"),
+            CodecError::Empty => write!(f, "cannot decode an empty urn"),
+            CodecError::WeightOverflow => write!(f, "weight overflow"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Encodes an index-drawing strategy as a single byte
+fn draw_tag(draw: IndexDraw) -> u8 {
+    match draw {
+        IndexDraw::Uniform => 0,
+        IndexDraw::Lemire => 1,
+        IndexDraw::Bitmask => 2,
+    }
+}
+
+impl<T: Clone + Serialize> Urn<T> {
+    /// Encodes the urn compactly as bytes (e.g. to snapshot a large urn to
+    /// disk). The encoding consists of a header (a magic number and a
+    /// version), followed by the urn's index-drawing strategy and its
+    /// (weight, element) pairs in leaf order, encoded with `postcard`
+    /// (so weights take up 1 to 10 bytes each, whatever the width of
+    /// `Weight`). The shape of the tree isn't stored: `from_bytes` rebuilds
+    /// an almost perfect tree.
+    /// Time complexity: `O(n)`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let elems: Vec<(u64, &T)> =
+            self.iter().map(|(w, a)| (widen(w), a)).collect();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        postcard::to_extend(&(draw_tag(self.draw), elems), bytes)
+            .expect("encoding into a `Vec` can't fail")
+    }
+}
+
+impl<T: Clone + DeserializeOwned> Urn<T> {
+    /// Decodes an urn encoded by `to_bytes`, rebuilding it as an almost
+    /// perfect tree (see `from_list`).
+    /// Fails if the header is missing or has an unsupported version, if the
+    /// elements can't be decoded, if there are no elements, or if a weight
+    /// doesn't fit in a `Weight` (or the total weight in an `Index`).
+    /// Time complexity: `O(n)`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or(CodecError::BadHeader)?;
+        let (&version, rest) =
+            rest.split_first().ok_or(CodecError::BadHeader)?;
+        if version != VERSION {
+            return Err(CodecError::UnsupportedVersion(version));
+        }
+        let ((tag, elems), rest): ((u8, Vec<(u64, T)>), _) =
+            postcard::take_from_bytes(rest)
+                .map_err(|_| CodecError::Malformed)?;
+        if !rest.is_empty() {
+            return Err(CodecError::Malformed);
+        }
+        let draw = match tag {
+            0 => IndexDraw::Uniform,
+            1 => IndexDraw::Lemire,
+            2 => IndexDraw::Bitmask,
+            _ => return Err(CodecError::Malformed),
+        };
+        let mut total: Index = 0;
+        let elems = elems
+            .into_iter()
+            .map(|(w, a)| {
+                let w = Weight::try_from(w)
+                    .map_err(|_| CodecError::WeightOverflow)?;
+                total = total
+                    .checked_add(widen(w))
+                    .ok_or(CodecError::WeightOverflow)?;
+                Ok((w, a))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let urn = from_list(elems).ok_or(CodecError::Empty)?;
+        Ok(urn.with_index_draw(draw))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let elems =
+            vec![(2, "R".to_string()), (0, "G".into()), (3, "B".into())];
+        let urn = from_list(elems).unwrap().with_index_draw(IndexDraw::Lemire);
+        let bytes = urn.to_bytes();
+        assert_eq!(&bytes[..5], b"URN\0\x01");
+        assert_eq!(Urn::<String>::from_bytes(&bytes), Ok(urn));
+    }
+
+    #[test]
+    fn from_bytes_validates_input() {
+        let urn = from_list(vec![(2, 'R'), (3, 'B')]).unwrap();
+        let bytes = urn.to_bytes();
+        let decode = |bytes: &[u8]| Urn::<char>::from_bytes(bytes);
+        assert_eq!(decode(b"URX\0\x01"), Err(CodecError::BadHeader));
+        let mut bad = bytes.clone();
+        bad[4] = 9;
+        assert_eq!(decode(&bad), Err(CodecError::UnsupportedVersion(9)));
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err(CodecError::Malformed)
+        );
+        let mut bad = bytes.clone();
+        bad.push(0);
+        assert_eq!(decode(&bad), Err(CodecError::Malformed));
+
+        // An empty list, and a weight that is too wide for any `Weight` but
+        // `u64` (or makes the total weight overflow)
+        let mut empty = b"URN\0\x01".to_vec();
+        empty.extend(
+            postcard::to_allocvec(&(0u8, Vec::<(u64, char)>::new())).unwrap(),
+        );
+        assert_eq!(decode(&empty), Err(CodecError::Empty));
+        let mut wide = b"URN\0\x01".to_vec();
+        let elems = vec![(u64::MAX, 'R'), (1, 'B')];
+        wide.extend(postcard::to_allocvec(&(0u8, elems)).unwrap());
+        assert_eq!(decode(&wide), Err(CodecError::WeightOverflow));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use rand_core::RngCore;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::singleton,
+};
+
+/// Which element a `BoundedUrn` evicts to make room for a new element
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum EvictionPolicy {
+    /// Evict an element with the lowest weight
+    /// (see `Urn::min_weight_element`)
+    #[default]
+    Lightest,
+    /// Evict the element that was inserted the longest time ago
+    Oldest,
+    /// Evict a random element, drawn with probability proportional to its
+    /// weight (or an arbitrary element if every weight is 0)
+    RandomWeighted,
+}
+
+/// An urn holding at most `capacity` elements, e.g. a sampling cache of
+/// recommendations or experiment arms. Inserting an element into a full
+/// urn first evicts one of the existing elements according to its
+/// `EvictionPolicy`, so the new element is always kept.
+///
+/// As in `HandleUrn`, removing the `k`-th inserted element of the urn moves
+/// the most recently inserted element into the `k`-th slot, so the age of
+/// each element is tracked by its insertion path.
+///
+/// Time complexity: `insert` and `sample` both take `O(log n)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundedUrn<T: Clone> {
+    urn: Option<Urn<T>>,
+    capacity: u32,
+    policy: EvictionPolicy,
+    /// `stamps[k]` is the insertion time of the `k`-th inserted element
+    stamps: Vec<u64>,
+    /// The insertion path of each element, keyed by its insertion time
+    by_age: BTreeMap<u64, u32>,
+    next_stamp: u64,
+}
+
+impl<T: Clone> BoundedUrn<T> {
+    /// Creates an empty `BoundedUrn` holding at most `capacity` elements,
+    /// which evicts elements according to `policy`.
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: u32, policy: EvictionPolicy) -> Self {
+        assert!(capacity > 0, "the capacity of an urn must be positive");
+        Self {
+            urn: None,
+            capacity,
+            policy,
+            stamps: Vec::new(),
+            by_age: BTreeMap::new(),
+            next_stamp: 0,
+        }
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.urn.as_ref()
+    }
+
+    /// The maximum no. of elements
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// The eviction policy
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.stamps.len()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.stamps.is_empty()
+    }
+
+    /// Whether the urn holds `capacity` elements
+    /// (so the next insertion evicts an element)
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity as usize
+    }
+
+    /// Inserts element `a` with weight `w`. If the urn is full, an element
+    /// is evicted first (according to the eviction policy, drawing from
+    /// `rng` for `EvictionPolicy::RandomWeighted`), and returned along with
+    /// its weight.
+    pub fn insert<R: RngCore + ?Sized>(
+        &mut self,
+        w: Weight,
+        a: T,
+        rng: &mut R,
+    ) -> Option<(Weight, T)> {
+        let evicted = if self.is_full() {
+            self.evict(rng)
+        } else {
+            None
+        };
+        let k = self.stamps.len() as u32;
+        self.urn = Some(match self.urn.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        });
+        self.stamps.push(self.next_stamp);
+        self.by_age.insert(self.next_stamp, k);
+        self.next_stamp += 1;
+        evicted
+    }
+
+    /// Evicts an element according to the eviction policy, returning it
+    /// along with its weight (or `None` if there are no elements)
+    pub fn evict<R: RngCore + ?Sized>(
+        &mut self,
+        rng: &mut R,
+    ) -> Option<(Weight, T)> {
+        let urn = self.urn.take()?;
+        let k = match self.policy {
+            EvictionPolicy::Lightest => urn.tree.locate_min().0,
+            EvictionPolicy::Oldest => {
+                *self.by_age.values().next().expect("urns are non-empty")
+            }
+            EvictionPolicy::RandomWeighted => {
+                urn.tree.locate_index(urn.draw_index(rng)).0
+            }
+        };
+        let (elem, urn_opt) = urn.remove_nth(k);
+        self.urn = urn_opt;
+
+        // The most recently inserted element moves into the `k`-th slot
+        self.by_age.remove(&self.stamps.swap_remove(k as usize));
+        if let Some(&moved) = self.stamps.get(k as usize) {
+            self.by_age.insert(moved, k);
+        }
+        Some(elem)
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`
+    /// (or `None` if there are no elements)
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        self.urn.as_ref().map(|urn| urn.sample_ref(rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// The elements of the urn, sorted
+    fn contents(urn: &BoundedUrn<u32>) -> Vec<u32> {
+        let mut elems: Vec<u32> = urn
+            .urn()
+            .map(|u| u.iter().map(|(_, &a)| a).collect())
+            .unwrap_or_default();
+        elems.sort();
+        elems
+    }
+
+    #[test]
+    fn eviction_policies() {
+        let mut rng = StdRng::seed_from_u64(10);
+        let weights = [5, 1, 4, 3, 2];
+
+        let mut oldest = BoundedUrn::new(3, EvictionPolicy::Oldest);
+        let mut lightest = BoundedUrn::new(3, EvictionPolicy::Lightest);
+        let mut evicted = (vec![], vec![]);
+        for (a, &w) in weights.iter().enumerate() {
+            let a = a as u32;
+            evicted.0.extend(oldest.insert(w, a, &mut rng));
+            evicted.1.extend(lightest.insert(w, a, &mut rng));
+        }
+        assert_eq!(evicted.0, vec![(5, 0), (1, 1)]);
+        assert_eq!(contents(&oldest), vec![2, 3, 4]);
+        assert_eq!(evicted.1, vec![(1, 1), (3, 3)]);
+        assert_eq!(contents(&lightest), vec![0, 2, 4]);
+        // Ages survive the reordering of the urn by removals
+        assert_eq!(oldest.evict(&mut rng), Some((4, 2)));
+        assert_eq!(oldest.evict(&mut rng), Some((3, 3)));
+
+        let mut random = BoundedUrn::new(2, EvictionPolicy::RandomWeighted);
+        for a in 0..20 {
+            random.insert(1, a, &mut rng);
+            assert!(random.len() <= 2);
+            assert!(contents(&random).contains(&a));
+        }
+        assert!(random.is_full() && random.sample(&mut rng).is_some());
+    }
+}
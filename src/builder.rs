@@ -0,0 +1,74 @@
+use crate::{
+    types::{Urn, Weight},
+    urn::from_list,
+};
+
+/// Incrementally collects `(weight, element)` pairs (e.g. as they are
+/// produced by a streaming parser), then builds an urn containing all of
+/// them in one pass using `from_list`.
+#[derive(Debug, Clone)]
+pub struct UrnBuilder<T: Clone> {
+    elems: Vec<(Weight, T)>,
+}
+
+impl<T: Clone> Default for UrnBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> UrnBuilder<T> {
+    /// Creates an empty builder
+    pub fn new() -> Self {
+        Self { elems: Vec::new() }
+    }
+
+    /// Creates an empty builder with space for at least `n` elements
+    /// (so that `n` calls to `push` don't reallocate)
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            elems: Vec::with_capacity(n),
+        }
+    }
+
+    /// Adds element `a` with weight `w` to the builder.
+    /// Time complexity: amortized `O(1)`.
+    pub fn push(&mut self, w: Weight, a: T) -> &mut Self {
+        self.elems.push((w, a));
+        self
+    }
+
+    /// The no. of elements pushed so far
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether no elements have been pushed yet
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// Builds an almost perfect urn containing all the pushed elements,
+    /// moving (rather than cloning) them into the urn's leaves.
+    /// Returns `None` if no elements were pushed.       
+    /// Time complexity: `O(n)`.
+    pub fn build(self) -> Option<Urn<T>> {
+        from_list(self.elems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_matches_from_list() {
+        let elems = vec![(2, 'R'), (4, 'G'), (3, 'B')];
+        let mut builder = UrnBuilder::with_capacity(elems.len());
+        for (w, a) in elems.clone() {
+            builder.push(w, a);
+        }
+        assert_eq!(builder.build(), from_list(elems));
+        assert_eq!(UrnBuilder::<char>::new().build(), None);
+    }
+}
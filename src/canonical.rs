@@ -0,0 +1,120 @@
+use std::hash::{Hash, Hasher};
+
+use crate::{types::Urn, urn::from_list};
+
+/* -------------------------------------------------------------------------- */
+/*                    Canonical forms and comparing contents                  */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone + Ord> Urn<T> {
+    /// Rebuilds the urn into its *canonical form*: an almost perfect tree
+    /// (see `from_list`) whose leaves are the urn's (weight, element) pairs
+    /// in increasing order. Two urns have the same canonical form iff they
+    /// contain the same multiset of (weight, element) pairs
+    /// (see `same_distribution`), so the canonical forms of urns can be
+    /// compared with `==`. The urn's index-drawing strategy is kept.
+    /// Time complexity: `O(n log n)`.
+    pub fn canonicalize(self) -> Self {
+        let draw = self.draw;
+        let mut elems = self.into_vec();
+        elems.sort_unstable();
+        let urn = from_list(elems)
+            .expect("urns are non-empty")
+            .with_index_draw(draw);
+        urn.debug_check_invariants();
+        urn
+    }
+
+    /// Checks whether two urns contain the same multiset of
+    /// (weight, element) pairs, regardless of the shapes of their trees or
+    /// the order of their leaves (unlike `==`, see `Urn`).
+    /// Their index-drawing strategies are ignored.
+    /// Note: this is stricter than comparing the probabilities of elements,
+    /// e.g. `[(2, 'a')]` and `[(1, 'a'), (1, 'a')]` are considered different,
+    /// and so are urns whose weights differ by a constant factor.
+    /// Time complexity: `O(n log n)`.
+    pub fn same_distribution(&self, other: &Self) -> bool {
+        if self.size != other.size || self.weight() != other.weight() {
+            return false;
+        }
+        let mut xs: Vec<_> = self.iter().collect();
+        let mut ys: Vec<_> = other.iter().collect();
+        xs.sort_unstable();
+        ys.sort_unstable();
+        xs == ys
+    }
+}
+
+/// Hashes the urn's multiset of (weight, element) pairs (in increasing
+/// order), so urns with the same contents have the same hash regardless of
+/// the shapes of their trees (see `same_distribution`). This is consistent
+/// with `==`, which implies equal contents. To memoize computations keyed by
+/// distributions, `canonicalize` urns before using them as keys, since `==`
+/// is structural.
+/// Time complexity: `O(n log n)`.
+impl<T: Clone + Ord + Hash> Hash for Urn<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut elems: Vec<_> = self.iter().collect();
+        elems.sort_unstable();
+        elems.hash(state);
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::hash::{BuildHasher, RandomState};
+
+    use crate::types::IndexDraw;
+    use crate::urn::{from_list, from_list_naive};
+
+    #[test]
+    fn canonicalize_example() {
+        let elems = vec![(3, 'c'), (1, 'a'), (2, 'b'), (1, 'd'), (1, 'a')];
+        let u1 = from_list(elems.clone()).unwrap();
+        let u2 = from_list_naive(elems.into_iter().rev().collect()).unwrap();
+        assert_ne!(u1, u2);
+        assert!(u1.same_distribution(&u2));
+
+        let u2 = u2.with_index_draw(IndexDraw::Lemire).canonicalize();
+        assert_eq!(u2.draw, IndexDraw::Lemire);
+        assert_eq!(
+            u2.to_vec(),
+            vec![(1, 'a'), (1, 'a'), (1, 'd'), (2, 'b'), (3, 'c')]
+        );
+        assert!(u2.validate().is_ok());
+        assert_eq!(u1.canonicalize().with_index_draw(IndexDraw::Lemire), u2);
+    }
+
+    #[test]
+    fn same_distribution_compares_multisets() {
+        let u = from_list(vec![(2, 'a')]).unwrap();
+        let v = from_list(vec![(1, 'a'), (1, 'a')]).unwrap();
+        assert!(!u.same_distribution(&v));
+
+        let u = from_list(vec![(1, 'a'), (1, 'a'), (2, 'b')]).unwrap();
+        let v = from_list(vec![(1, 'a'), (2, 'b'), (1, 'b')]).unwrap();
+        assert!(!u.same_distribution(&v));
+        assert!(u.same_distribution(&u.clone().canonicalize()));
+    }
+
+    #[test]
+    fn hash_depends_on_contents() {
+        let elems = vec![(3, 'c'), (1, 'a'), (2, 'b'), (1, 'd')];
+        let u1 = from_list(elems.clone()).unwrap();
+        let u2 = from_list_naive(elems.into_iter().rev().collect()).unwrap();
+        let state = RandomState::new();
+        assert_ne!(u1, u2);
+        assert_eq!(state.hash_one(&u1), state.hash_one(&u2));
+
+        // Memoizing the total weight of canonicalized urns
+        let mut memo = HashMap::new();
+        memo.insert(u1.clone().canonicalize(), u1.weight());
+        assert_eq!(memo.get(&u2.canonicalize()), Some(&7));
+        assert_eq!(memo.get(&u1.insert(1, 'e').canonicalize()), None);
+    }
+}
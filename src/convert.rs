@@ -0,0 +1,183 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+};
+
+use crate::{
+    types::{widen, Index, Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                        Conversions to & from maps                          */
+/* -------------------------------------------------------------------------- */
+
+/// Errors arising when converting a collection into an urn
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConversionError {
+    /// The collection is empty (urns always contain at least one element)
+    Empty,
+    /// The total weight of the elements doesn't fit in an `Index`
+    WeightOverflow,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Empty => write!(f, "cannot build an empty urn"),
+            ConversionError::WeightOverflow => {
+                write!(f, "the total weight overflows")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Builds an urn from (weight, element) pairs with `from_list`, checking
+/// that there is at least one element and that the total weight doesn't
+/// overflow
+fn try_from_list<T: Clone>(
+    elems: Vec<(Weight, T)>,
+) -> Result<Urn<T>, ConversionError> {
+    elems
+        .iter()
+        .try_fold(0 as Index, |total, (w, _)| total.checked_add(widen(*w)))
+        .ok_or(ConversionError::WeightOverflow)?;
+    from_list(elems).ok_or(ConversionError::Empty)
+}
+
+/// Builds an urn from a vector of (weight, element) pairs, in order
+/// (like `from_list`).
+/// Fails with `ConversionError::Empty` if the vector is empty, and with
+/// `ConversionError::WeightOverflow` if the total weight doesn't fit in
+/// an `Index`.
+impl<T: Clone> TryFrom<Vec<(Weight, T)>> for Urn<T> {
+    type Error = ConversionError;
+
+    fn try_from(elems: Vec<(Weight, T)>) -> Result<Self, Self::Error> {
+        try_from_list(elems)
+    }
+}
+
+/// Moves the (weight, element) pairs of an urn into a vector, in leaf order
+/// (see `Urn::into_vec`)
+impl<T: Clone> From<Urn<T>> for Vec<(Weight, T)> {
+    fn from(urn: Urn<T>) -> Self {
+        urn.into_vec()
+    }
+}
+
+/// Builds an urn from a map from elements to their weights
+/// (e.g. a frequency count). The elements are stored in the map's
+/// iteration order.
+/// Fails with `ConversionError::Empty` if the map is empty, and with
+/// `ConversionError::WeightOverflow` if the total weight doesn't fit in
+/// an `Index`.
+impl<T: Clone> TryFrom<HashMap<T, Weight>> for Urn<T> {
+    type Error = ConversionError;
+
+    fn try_from(map: HashMap<T, Weight>) -> Result<Self, Self::Error> {
+        try_from_list(map.into_iter().map(|(a, w)| (w, a)).collect())
+    }
+}
+
+/// Builds an urn from a map from elements to their weights
+/// (e.g. a frequency count). The elements are stored in sorted order.
+/// Fails with `ConversionError::Empty` if the map is empty, and with
+/// `ConversionError::WeightOverflow` if the total weight doesn't fit in
+/// an `Index`.
+impl<T: Clone> TryFrom<BTreeMap<T, Weight>> for Urn<T> {
+    type Error = ConversionError;
+
+    fn try_from(map: BTreeMap<T, Weight>) -> Result<Self, Self::Error> {
+        try_from_list(map.into_iter().map(|(a, w)| (w, a)).collect())
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Consumes the urn, collecting its elements into a map from each
+    /// distinct element to its weight. The weights of duplicate elements
+    /// are summed as `Index`es, since their sum may exceed `Weight::MAX`
+    /// (but not the urn's total weight).
+    /// Time complexity: `O(n)` (expected).
+    pub fn into_map(self) -> HashMap<T, Index>
+    where
+        T: Eq + Hash,
+    {
+        let mut map = HashMap::with_capacity(self.size() as usize);
+        for (w, a) in self.into_vec() {
+            let total: &mut Index = map.entry(a).or_default();
+            *total = total.wrapping_add(widen(w));
+        }
+        map
+    }
+
+    /// Like `into_map`, but collects the elements into a `BTreeMap`.
+    /// Time complexity: `O(n log n)`.
+    pub fn into_btree_map(self) -> BTreeMap<T, Index>
+    where
+        T: Ord,
+    {
+        let mut map = BTreeMap::new();
+        for (w, a) in self.into_vec() {
+            let total: &mut Index = map.entry(a).or_default();
+            *total = total.wrapping_add(widen(w));
+        }
+        map
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_round_trip_merges_duplicates() {
+        let counts = BTreeMap::from([("a", 3), ("b", 1), ("c", 6)]);
+        let urn = Urn::try_from(counts.clone()).unwrap();
+        assert_eq!(urn.weight(), 10);
+        let totals: BTreeMap<&str, Index> =
+            counts.iter().map(|(&a, &w)| (a, widen(w))).collect();
+        assert_eq!(urn.clone().into_btree_map(), totals);
+
+        let urn = urn.insert(2, "a");
+        let map = urn.into_map();
+        assert_eq!(map, HashMap::from([("a", 5), ("b", 1), ("c", 6)]));
+
+        let empty: HashMap<char, Weight> = HashMap::new();
+        assert_eq!(Urn::try_from(empty), Err(ConversionError::Empty));
+    }
+
+    // With `u64` weights, the totals could overflow an `Index`
+    #[cfg(not(feature = "weight-u64"))]
+    #[test]
+    fn map_totals_may_exceed_max_weight() {
+        let urn = from_list(vec![(Weight::MAX, 'a'), (Weight::MAX, 'a')]);
+        let total = 2 * widen(Weight::MAX);
+        assert_eq!(urn.unwrap().into_map(), HashMap::from([('a', total)]));
+    }
+
+    #[test]
+    fn vec_round_trip() {
+        let elems = vec![(2, 'R'), (0, 'G'), (3, 'B')];
+        let urn = Urn::try_from(elems.clone()).unwrap();
+        assert_eq!(Some(&urn), from_list(elems.clone()).as_ref());
+        assert_eq!(Vec::from(urn), elems);
+
+        let empty: Vec<(Weight, char)> = Vec::new();
+        assert_eq!(Urn::try_from(empty), Err(ConversionError::Empty));
+    }
+
+    #[cfg(feature = "weight-u64")]
+    #[test]
+    fn try_from_rejects_overflowing_weights() {
+        let elems = vec![(Weight::MAX, 'R'), (1, 'B')];
+        assert_eq!(Urn::try_from(elems), Err(ConversionError::WeightOverflow));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    types::{Urn, Weight, ZeroWeightPolicy},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                                Aging weights                               */
+/* -------------------------------------------------------------------------- */
+
+/// Multiplies the weight `w` by `factor`, rounding to the nearest weight
+/// and clamping the result to the range `[min_weight, Weight::MAX]`
+fn decay_weight(w: Weight, factor: f64, min_weight: Weight) -> Weight {
+    // `as` saturates at `Weight::MAX` (and maps 0 * inf = NaN to 0)
+    let scaled = (w as f64 * factor).round() as Weight;
+    scaled.max(min_weight)
+}
+
+impl<T: Clone> Urn<T> {
+    /// Multiplies the weight of every element by `factor`, rounding each
+    /// new weight to the nearest integer and clamping it so that it is at
+    /// least `min_weight` (and at most `Weight::MAX`).      
+    /// If `on_zero` is `ZeroWeightPolicy::Remove`, elements whose new weight
+    /// is 0 (which is only possible when `min_weight` is 0) are removed,
+    /// and the result is `None` if every element was removed.
+    /// Otherwise, the weights are updated in place in a single traversal
+    /// of the tree, without rebuilding it.      
+    /// Panics if `factor` is negative or NaN.      
+    /// Time complexity: `O(n)`.
+    pub fn decay(
+        mut self,
+        factor: f64,
+        min_weight: Weight,
+        on_zero: ZeroWeightPolicy,
+    ) -> Option<Self> {
+        assert!(factor >= 0.0, "decay factor must be non-negative");
+        let mut decay = |w| decay_weight(w, factor, min_weight);
+        match on_zero {
+            ZeroWeightPolicy::Remove if min_weight == 0 => from_list(
+                self.into_vec()
+                    .into_iter()
+                    .map(|(w, a)| (decay(w), a))
+                    .filter(|(w, _)| *w > 0)
+                    .collect(),
+            ),
+            _ => {
+                self.tree.map_weights(&mut decay);
+                self.debug_check_invariants();
+                Some(self)
+            }
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::{types::ZeroWeightPolicy, urn::from_list};
+
+    #[test]
+    fn decay_example() {
+        let urn = from_list(vec![(10, 'R'), (1, 'G'), (5, 'B')]).unwrap();
+
+        let kept = urn.clone().decay(0.5, 0, ZeroWeightPolicy::Keep).unwrap();
+        assert_eq!(kept.to_vec(), vec![(5, 'R'), (1, 'G'), (3, 'B')]);
+        assert_eq!(kept.weight(), 9);
+
+        let floored = urn.clone().decay(0.1, 1, ZeroWeightPolicy::Remove);
+        let floored = floored.unwrap().to_vec();
+        assert_eq!(floored, vec![(1, 'R'), (1, 'G'), (1, 'B')]);
+
+        let dropped = urn.clone().decay(0.1, 0, ZeroWeightPolicy::Remove);
+        assert_eq!(dropped.map(|u| u.to_vec()), Some(vec![(1, 'R'), (1, 'B')]));
+
+        assert!(urn.decay(0.0, 0, ZeroWeightPolicy::Remove).is_none());
+    }
+}
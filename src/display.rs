@@ -0,0 +1,75 @@
+use std::fmt;
+
+use crate::types::Urn;
+
+/* -------------------------------------------------------------------------- */
+/*                             Formatting urns                                */
+/* -------------------------------------------------------------------------- */
+
+/// Prints the distribution represented by the urn as a table, with one
+/// `value: weight (probability%)` line per element, sorted by decreasing
+/// weight (elements with equal weights appear in leaf order).
+impl<T: Clone + fmt::Display> fmt::Display for Urn<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.exact_weight() as f64;
+        let mut elems: Vec<_> = self.iter().collect();
+        elems.sort_by(|(w1, _), (w2, _)| w2.cmp(w1));
+        for (k, (w, a)) in elems.into_iter().enumerate() {
+            if k > 0 {
+                writeln!(f)?;
+            }
+            let percent = if total == 0.0 {
+                0.0
+            } else {
+                100.0 * w as f64 / total
+            };
+            write!(f, "{}: {} ({:.2}%)", a, w, percent)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the urn's size, the depth of its tree, its total weight, its
+/// index-drawing strategy, and its elements (in leaf order) instead of
+/// the nested `Node`s of its tree.
+impl<T: Clone + fmt::Debug> fmt::Debug for Urn<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Urn")
+            .field("size", &self.size)
+            .field("depth", &self.tree.depth_stats().max)
+            .field("weight", &self.weight())
+            .field("draw", &self.draw)
+            .field("elems", &self.iter().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+
+    #[test]
+    fn display_example() {
+        let urn =
+            from_list(vec![(1, "blue"), (3, "red"), (0, "grey"), (4, "green")])
+                .unwrap();
+        assert_eq!(
+            urn.to_string(),
+            "green: 4 (50.00%)\nred: 3 (37.50%)\nblue: 1 (12.50%)\ngrey: 0 (0.00%)"
+        );
+    }
+
+    #[test]
+    fn debug_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (2, 'B')]).unwrap();
+        assert_eq!(
+            format!("{:?}", urn),
+            "Urn { size: 3, depth: 2, weight: 8, draw: Uniform, \
+             elems: [(2, 'R'), (4, 'G'), (2, 'B')] }"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::types::{Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                         Entries by insertion ordinal                       */
+/* -------------------------------------------------------------------------- */
+
+/// The `i`-th inserted element of an urn (see `Urn::get_nth`), which may
+/// or may not exist yet, created by `Urn::entry_nth`.
+/// Like `Urn::insert`, entries take ownership of the urn,
+/// and give it back once they are consumed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Entry<T: Clone> {
+    /// The urn has an `i`-th element
+    Occupied(OccupiedEntry<T>),
+    /// The urn has exactly `i` elements, so the next element inserted
+    /// becomes the `i`-th one
+    Vacant(VacantEntry<T>),
+}
+
+/// An entry for an existing element of an urn (see `Entry`)
+#[derive(Debug, PartialEq, Clone)]
+pub struct OccupiedEntry<T: Clone> {
+    urn: Urn<T>,
+    i: u32,
+}
+
+/// An entry for the next element to be inserted into an urn (see `Entry`)
+#[derive(Debug, PartialEq, Clone)]
+pub struct VacantEntry<T: Clone> {
+    urn: Urn<T>,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Gets the entry for the `i`-th inserted element of the urn (see
+    /// `get_nth`), e.g. to update the element if it exists, or insert
+    /// it otherwise (see `Entry::and_modify` and `Entry::or_insert`).
+    /// Panics if `i > self.size()` (the only vacant entry is the one for
+    /// the next element to be inserted, i.e. the `size`-th one).
+    /// Time complexity: `O(1)`.
+    pub fn entry_nth(self, i: u32) -> Entry<T> {
+        assert!(
+            i <= self.size(),
+            "ordinal {} out of bounds for urn of size {}",
+            i,
+            self.size()
+        );
+        if i < self.size() {
+            Entry::Occupied(OccupiedEntry { urn: self, i })
+        } else {
+            Entry::Vacant(VacantEntry { urn: self })
+        }
+    }
+}
+
+impl<T: Clone> Entry<T> {
+    /// Applies `f` to the weight and the element in place if the entry is
+    /// occupied (see `OccupiedEntry::modify`), returning the entry.
+    /// Time complexity: `O(log n)`.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Weight, &mut T),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            entry.modify(f);
+        }
+        self
+    }
+
+    /// Inserts element `a` with weight `w` if the entry is vacant,
+    /// returning the urn.
+    /// Time complexity: `O(log n)`.
+    pub fn or_insert(self, w: Weight, a: T) -> Urn<T> {
+        self.or_insert_with(|| (w, a))
+    }
+
+    /// Inserts the (weight, element) pair returned by `f` if the entry is
+    /// vacant (so `f` is only called if it is), returning the urn.
+    /// Time complexity: `O(log n)`.
+    pub fn or_insert_with<F>(self, f: F) -> Urn<T>
+    where
+        F: FnOnce() -> (Weight, T),
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_urn(),
+            Entry::Vacant(entry) => {
+                let (w, a) = f();
+                entry.insert(w, a)
+            }
+        }
+    }
+
+    /// The ordinal of the entry's element
+    pub fn ordinal(&self) -> u32 {
+        match self {
+            Entry::Occupied(entry) => entry.i,
+            Entry::Vacant(entry) => entry.urn.size(),
+        }
+    }
+
+    /// Gives back the urn, leaving it unchanged
+    pub fn into_urn(self) -> Urn<T> {
+        match self {
+            Entry::Occupied(entry) => entry.into_urn(),
+            Entry::Vacant(entry) => entry.into_urn(),
+        }
+    }
+}
+
+impl<T: Clone> OccupiedEntry<T> {
+    /// Retrieves the weight and (a reference to) the element.
+    /// Time complexity: `O(log n)`.
+    pub fn get(&self) -> (Weight, &T) {
+        self.urn.tree.lookup_path(self.i)
+    }
+
+    /// Retrieves a mutable reference to the element (but not its weight,
+    /// see `modify`).
+    /// Time complexity: `O(log n)`.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.urn.tree.lookup_path_mut(self.i).1
+    }
+
+    /// Applies `f` to the weight and the element in place, repairing the
+    /// cached weights of the element's ancestors in the same pass,
+    /// and returns the result of `f`.
+    /// Time complexity: `O(log n)`.
+    pub fn modify<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Weight, &mut T) -> R,
+    {
+        let result = self.urn.tree.modify_path(self.i, f);
+        self.urn.debug_check_invariants();
+        result
+    }
+
+    /// Removes the element, returning it along with its weight, and the
+    /// resultant urn in an `Option` (which is `None` if the urn is empty).
+    /// As with `Urn::remove_position`, the most recently inserted element
+    /// is moved into the vacated slot (and takes over the ordinal).
+    /// Time complexity: `O(log n)`.
+    pub fn remove(self) -> ((Weight, T), Option<Urn<T>>) {
+        self.urn.remove_nth(self.i)
+    }
+
+    /// Gives back the urn
+    pub fn into_urn(self) -> Urn<T> {
+        self.urn
+    }
+}
+
+impl<T: Clone> VacantEntry<T> {
+    /// Inserts element `a` with weight `w` (see `Urn::insert`),
+    /// returning the urn.
+    /// Time complexity: `O(log n)`.
+    pub fn insert(self, w: Weight, a: T) -> Urn<T> {
+        self.urn.insert(w, a)
+    }
+
+    /// Gives back the urn, leaving it unchanged
+    pub fn into_urn(self) -> Urn<T> {
+        self.urn
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list_naive;
+
+    #[test]
+    fn entries_modify_or_insert() {
+        let urn = from_list_naive(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let urn = urn
+            .entry_nth(1)
+            .and_modify(|w, a| {
+                *w += 1;
+                *a = 'g';
+            })
+            .or_insert(9, 'Y');
+        assert_eq!(urn.get_nth(1), Some((5, &'g')));
+        assert_eq!(urn.weight(), 10);
+
+        let urn = urn.entry_nth(3).and_modify(|w, _| *w = 0).or_insert(9, 'Y');
+        assert_eq!(urn.get_nth(3), Some((9, &'Y')));
+        assert_eq!(urn.weight(), 19);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn entry_beyond_the_next_ordinal_panics() {
+        let urn = from_list_naive(vec![(2, 'R')]).unwrap();
+        urn.entry_nth(2);
+    }
+}
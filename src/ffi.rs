@@ -0,0 +1,229 @@
+//! A C API for urns, whose elements are opaque `void *` payloads.
+//!
+//! Ownership: `urn_new` returns a heap-allocated handle, which the caller
+//! owns, and must release with `urn_free` (exactly once). The payloads are
+//! owned by the caller: the urn only stores the pointers, never dereferences
+//! or frees them, and `urn_remove` hands a payload back to the caller.
+//!
+//! Randomness is supplied by the caller: `urn_sample_index` and `urn_remove`
+//! take an index in `[0, urn_weight(urn))`, so that C/C++ code can use its
+//! own (e.g. seeded) RNG. (If the total weight is 0, the only valid index
+//! is 0.) `Weight` is `uint8_t` by default, or the width selected by the
+//! `weight-*` features.
+//!
+//! Panic safety: no panic unwinds across the boundary. If a panic occurs
+//! (which would indicate a bug in this crate), the function returns its
+//! error value instead. Functions which update the urn move it out of the
+//! handle while updating it, so after such a panic the urn is empty.
+//!
+//! To link against the C API, build the crate as a static or dynamic
+//! library, e.g. `cargo rustc --release --features ffi --crate-type staticlib`.
+
+use std::{
+    ffi::{c_int, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    types::{Index, Urn, Weight},
+    urn::singleton,
+};
+
+/// An opaque handle to a (possibly empty) urn of `void *` payloads
+pub struct UrnHandle {
+    urn: Option<Urn<*mut c_void>>,
+}
+
+impl UrnHandle {
+    /// Whether `i` is a valid index into the urn
+    fn is_valid_index(urn: &Urn<*mut c_void>, i: Index) -> bool {
+        i < urn.weight().max(1)
+    }
+}
+
+/// Runs `f` on the urn referenced by `urn`, returning `default` if `urn` is
+/// `NULL` or if `f` panics.
+///
+/// # Safety
+/// `urn` must be `NULL`, or a pointer returned by `urn_new` which hasn't
+/// been freed yet.
+unsafe fn with_handle<B>(
+    urn: *mut UrnHandle,
+    default: B,
+    f: impl FnOnce(&mut UrnHandle) -> B,
+) -> B {
+    // SAFETY: the caller guarantees that a non-null `urn` is a live handle
+    match unsafe { urn.as_mut() } {
+        None => default,
+        Some(handle) => {
+            catch_unwind(AssertUnwindSafe(|| f(handle))).unwrap_or(default)
+        }
+    }
+}
+
+/// Creates an empty urn. The returned handle must be released
+/// with `urn_free`.
+#[no_mangle]
+pub extern "C" fn urn_new() -> *mut UrnHandle {
+    Box::into_raw(Box::new(UrnHandle { urn: None }))
+}
+
+/// Frees the urn (but not its payloads). Does nothing if `urn` is `NULL`.
+///
+/// # Safety
+/// `urn` must be `NULL`, or a pointer returned by `urn_new` which hasn't
+/// been freed yet. It must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn urn_free(urn: *mut UrnHandle) {
+    if !urn.is_null() {
+        // SAFETY: `urn` was allocated by `urn_new` via `Box::into_raw`
+        drop(unsafe { Box::from_raw(urn) });
+    }
+}
+
+/// Inserts `data` with weight `weight`.
+/// Returns 0 on success, or -1 if `urn` is `NULL` (or on a panic).
+/// Time complexity: `O(log n)`.
+///
+/// # Safety
+/// `urn` must be `NULL`, or a live handle returned by `urn_new`.
+#[no_mangle]
+pub unsafe extern "C" fn urn_insert(
+    urn: *mut UrnHandle,
+    weight: Weight,
+    data: *mut c_void,
+) -> c_int {
+    unsafe {
+        with_handle(urn, -1, |handle| {
+            handle.urn = Some(match handle.urn.take() {
+                None => singleton(weight, data),
+                Some(urn) => urn.insert(weight, data),
+            });
+            0
+        })
+    }
+}
+
+/// The no. of elements (0 if `urn` is `NULL`)
+///
+/// # Safety
+/// `urn` must be `NULL`, or a live handle returned by `urn_new`.
+#[no_mangle]
+pub unsafe extern "C" fn urn_size(urn: *mut UrnHandle) -> u32 {
+    unsafe { with_handle(urn, 0, |h| h.urn.as_ref().map_or(0, |u| u.size())) }
+}
+
+/// The total weight of all the elements (0 if `urn` is `NULL`)
+///
+/// # Safety
+/// `urn` must be `NULL`, or a live handle returned by `urn_new`.
+#[no_mangle]
+pub unsafe extern "C" fn urn_weight(urn: *mut UrnHandle) -> Index {
+    unsafe { with_handle(urn, 0, |h| h.urn.as_ref().map_or(0, |u| u.weight())) }
+}
+
+/// Returns the payload of the element at index `index` (without removing
+/// it), or `NULL` if `urn` is `NULL` or empty, or if `index` is out of range.
+/// Time complexity: `O(log n)`.
+///
+/// # Safety
+/// `urn` must be `NULL`, or a live handle returned by `urn_new`.
+#[no_mangle]
+pub unsafe extern "C" fn urn_sample_index(
+    urn: *mut UrnHandle,
+    index: Index,
+) -> *mut c_void {
+    unsafe {
+        with_handle(urn, ptr::null_mut(), |handle| match &handle.urn {
+            Some(urn) if UrnHandle::is_valid_index(urn, index) => {
+                *urn.sample_index_ref(index)
+            }
+            _ => ptr::null_mut(),
+        })
+    }
+}
+
+/// Removes the element at index `index`, returning its payload (and
+/// storing its weight in `*weight_out`, unless `weight_out` is `NULL`).
+/// Returns `NULL` (and leaves `*weight_out` unchanged) if `urn` is `NULL`
+/// or empty, or if `index` is out of range.
+/// Time complexity: `O(log n)`.
+///
+/// # Safety
+/// `urn` must be `NULL`, or a live handle returned by `urn_new`, and
+/// `weight_out` must be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn urn_remove(
+    urn: *mut UrnHandle,
+    index: Index,
+    weight_out: *mut Weight,
+) -> *mut c_void {
+    let removed = unsafe {
+        with_handle(urn, None, |handle| match handle.urn.take() {
+            Some(urn) if UrnHandle::is_valid_index(&urn, index) => {
+                let (elem, rest) = urn.remove_index(index);
+                handle.urn = rest;
+                Some(elem)
+            }
+            urn => {
+                handle.urn = urn;
+                None
+            }
+        })
+    };
+    match removed {
+        None => ptr::null_mut(),
+        Some((w, data)) => {
+            // SAFETY: the caller guarantees that a non-null `weight_out`
+            // is valid for writes
+            if let Some(out) = unsafe { weight_out.as_mut() } {
+                *out = w;
+            }
+            data
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_round_trip() {
+        let mut payloads = [10, 20, 30];
+        let [a, b, c] =
+            payloads.each_mut().map(|p| p as *mut i32 as *mut c_void);
+        unsafe {
+            let urn = urn_new();
+            assert_eq!(urn_insert(urn, 1, a), 0);
+            assert_eq!(urn_insert(urn, 0, b), 0);
+            assert_eq!(urn_insert(urn, 2, c), 0);
+            assert_eq!((urn_size(urn), urn_weight(urn)), (3, 3));
+            assert_eq!(urn_sample_index(urn, 0), a);
+            assert_eq!(urn_sample_index(urn, 2), c);
+            assert!(urn_sample_index(urn, 3).is_null());
+
+            let mut w = 0;
+            assert_eq!(urn_remove(urn, 1, &mut w), c);
+            assert_eq!(w, 2);
+            assert!(urn_remove(urn, 1, ptr::null_mut()).is_null());
+            assert_eq!(urn_remove(urn, 0, ptr::null_mut()), a);
+            // Only the element with weight 0 is left
+            assert_eq!(urn_remove(urn, 0, &mut w), b);
+            assert_eq!((w, urn_size(urn)), (0, 0));
+            assert!(urn_sample_index(urn, 0).is_null());
+            urn_free(urn);
+
+            // `NULL` handles are rejected
+            assert_eq!(urn_insert(ptr::null_mut(), 1, a), -1);
+            assert!(urn_sample_index(ptr::null_mut(), 0).is_null());
+            urn_free(ptr::null_mut());
+        }
+        assert_eq!(payloads, [10, 20, 30]);
+    }
+}
@@ -0,0 +1,229 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{widen, Index, Weight},
+    urn::sample_weight_with,
+};
+
+/// A weighted sampler with a fixed capacity of `N` elements, which never
+/// allocates: the elements and the Fenwick tree of their partial weight
+/// sums (see `FenwickUrn`) live in arrays stored inline, so a `FixedUrn` can
+/// live on the stack or in a `static`. This module only uses `core` (and
+/// `rand`), with embedded targets without an allocator in mind, although
+/// the rest of the crate still depends on `std`.
+///
+/// Removing an element moves the last element into the vacated index
+/// (see `FixedUrn::swap_remove`).
+///
+/// Time complexity: `push`, `swap_remove`, `set_weight` and `sample` take
+/// `O(log N)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FixedUrn<T, const N: usize> {
+    /// `elems[i]` is `Some` iff `i < len`
+    elems: [Option<(Weight, T)>; N],
+    /// `sums[k - 1]` is the (wrapping) sum of the weights of the elements
+    /// at indexes `(k - lowbit(k), k]` (1-indexed), for `k <= len`
+    sums: [Index; N],
+    len: usize,
+}
+
+/// The lowest set bit of `k`
+fn lowbit(k: usize) -> usize {
+    k & k.wrapping_neg()
+}
+
+impl<T, const N: usize> Default for FixedUrn<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> FixedUrn<T, N> {
+    /// Creates an empty sampler (this is a `const fn`, so it can initialize
+    /// a `static`)
+    pub const fn new() -> Self {
+        Self {
+            elems: [const { None }; N],
+            sums: [0; N],
+            len: 0,
+        }
+    }
+
+    /// The no. of elements
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the sampler contains no elements
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The maximum no. of elements, `N`
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Whether the sampler contains `N` elements
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// The sum of the weights of the first `k` elements
+    fn prefix_weight(&self, k: usize) -> Index {
+        let (mut k, mut sum): (usize, Index) = (k, 0);
+        while k > 0 {
+            sum = sum.wrapping_add(self.sums[k - 1]);
+            k -= lowbit(k);
+        }
+        sum
+    }
+
+    /// Adds `delta` (modulo the range of `Index`) to the weight of the
+    /// element at (0-based) index `i` in the Fenwick tree
+    fn add_weight(&mut self, i: usize, delta: Index) {
+        let mut k = i + 1;
+        while k <= self.len {
+            self.sums[k - 1] = self.sums[k - 1].wrapping_add(delta);
+            k += lowbit(k);
+        }
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> Index {
+        self.prefix_weight(self.len)
+    }
+
+    /// Retrieves the weight and (a reference to) the element at index `i`
+    pub fn get(&self, i: usize) -> Option<(Weight, &T)> {
+        match self.elems.get(i) {
+            Some(Some((w, a))) => Some((*w, a)),
+            _ => None,
+        }
+    }
+
+    /// Appends element `a` with weight `w`, returning its index, or gives
+    /// the element back if the sampler is full
+    pub fn push(&mut self, w: Weight, a: T) -> Result<usize, (Weight, T)> {
+        if self.is_full() {
+            return Err((w, a));
+        }
+        let k = self.len + 1;
+        let covered = self
+            .prefix_weight(k - 1)
+            .wrapping_sub(self.prefix_weight(k - lowbit(k)));
+        self.sums[k - 1] = covered.wrapping_add(widen(w));
+        self.elems[k - 1] = Some((w, a));
+        self.len = k;
+        Ok(k - 1)
+    }
+
+    /// Sets the weight of the element at index `i` to `w`,
+    /// returning its old weight.
+    /// Panics if `i` is out of bounds.
+    pub fn set_weight(&mut self, i: usize, w: Weight) -> Weight {
+        let (w_elem, _) = self.elems[..self.len][i]
+            .as_mut()
+            .expect("elements below `len` are present");
+        let w_old = core::mem::replace(w_elem, w);
+        self.add_weight(i, widen(w).wrapping_sub(widen(w_old)));
+        w_old
+    }
+
+    /// Removes the element at index `i`, returning it along with its weight.
+    /// The last element is moved into index `i`.
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> (Weight, T) {
+        assert!(i < self.len, "index out of bounds");
+        let last = self.len - 1;
+        let w_last = self.get(last).map_or(0, |(w, _)| w);
+        let w = self.get(i).map_or(0, |(w, _)| w);
+        // No partial sum other than the last one covers the last element
+        self.add_weight(i, widen(w_last).wrapping_sub(widen(w)));
+        self.len = last;
+        self.elems.swap(i, last);
+        self.elems[last]
+            .take()
+            .expect("elements below `len` are present")
+    }
+
+    /// Finds the index of the element whose bucket contains index `i`
+    /// (by descending the implicit Fenwick tree)
+    pub fn position_of_index(&self, i: Index) -> usize {
+        let n = self.len;
+        let (mut pos, mut i) = (0, i);
+        let mut step = if n == 0 { 0 } else { 1 << n.ilog2() };
+        while step > 0 {
+            if pos + step <= n && self.sums[pos + step - 1] <= i {
+                pos += step;
+                i -= self.sums[pos - 1];
+            }
+            step >>= 1;
+        }
+        pos.min(n.saturating_sub(1))
+    }
+
+    /// Randomly samples an element using `rng`, returning its index along
+    /// with (a reference to) the element, or `None` if the sampler is empty
+    /// or all its weights are 0
+    pub fn sample<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<(usize, &T)> {
+        let total = self.weight();
+        if total == 0 {
+            return None;
+        }
+        let pos = self.position_of_index(sample_weight_with(rng, total));
+        self.get(pos).map(|(_, a)| (pos, a))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn fixed_example() {
+        let mut urn: FixedUrn<char, 4> = FixedUrn::new();
+        assert_eq!(urn.push(2, 'R'), Ok(0));
+        assert_eq!(urn.push(4, 'G'), Ok(1));
+        assert_eq!(urn.push(3, 'B'), Ok(2));
+        assert_eq!(urn.weight(), 9);
+        let buckets: Vec<usize> =
+            (0..9).map(|i| urn.position_of_index(i)).collect();
+        assert_eq!(buckets, vec![0, 0, 1, 1, 1, 1, 2, 2, 2]);
+
+        assert_eq!(urn.push(1, 'Y'), Ok(3));
+        assert_eq!(urn.push(1, 'Z'), Err((1, 'Z')));
+        assert_eq!(urn.set_weight(0, 5), 2);
+        assert_eq!(urn.weight(), 13);
+        assert_eq!(urn.swap_remove(1), (4, 'G'));
+        assert_eq!(urn.get(1), Some((1, &'Y')));
+        assert_eq!((urn.len(), urn.weight()), (3, 9));
+        assert_eq!(urn.push(6, 'Z'), Ok(3));
+        assert_eq!(urn.weight(), 15);
+    }
+
+    #[test]
+    fn fixed_sample_skips_zero_weights() {
+        static EMPTY: FixedUrn<char, 2> = FixedUrn::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(EMPTY.sample(&mut rng), None);
+
+        let mut urn: FixedUrn<char, 3> = FixedUrn::new();
+        for (w, a) in [(0, 'a'), (2, 'b'), (0, 'c')] {
+            urn.push(w, a).unwrap();
+        }
+        for _ in 0..20 {
+            assert_eq!(urn.sample(&mut rng), Some((1, &'b')));
+        }
+        urn.set_weight(1, 0);
+        assert_eq!(urn.sample(&mut rng), None);
+    }
+}
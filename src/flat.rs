@@ -0,0 +1,375 @@
+use rand_core::RngCore;
+
+use crate::{
+    sampler::WeightedSampler,
+    types::{widen, Index, IndexDraw, Urn, Weight},
+    urn::draw_index_with,
+};
+
+#[cfg(feature = "rkyv")]
+use crate::types::ArchivedIndexDraw;
+
+/* -------------------------------------------------------------------------- */
+/*                      Flattened urns (const-time sampling)                  */
+/* -------------------------------------------------------------------------- */
+
+/// A read-only, flattened copy of an urn, laid out as a complete binary tree
+/// in an array (the children of node `k` are nodes `2k` and `2k + 1`).
+/// The leaves are padded with elements of weight 0 up to the next power
+/// of two, so every leaf has the same depth.
+/// `FlatUrn::sample_const_time` always walks the full depth of the tree and
+/// chooses each child with a branchless select, so the sequence of
+/// instructions it executes doesn't depend on which element is sampled.
+/// (The final lookup of the sampled element is still a data-dependent
+/// memory access, which may be observable through the cache.)
+///
+/// `FlatUrn` also implements `WeightedSampler`: inserting an element fills
+/// the next padding leaf (doubling the capacity once there is none), and
+/// removing one moves the last element into its leaf, so both update only
+/// the weights along the affected paths.
+///
+/// With the `rkyv` feature, `FlatUrn` can be archived with `rkyv`, and an
+/// `ArchivedFlatUrn` (e.g. in a memory-mapped file) can be sampled in place,
+/// without deserializing it.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct FlatUrn<T> {
+    /// `left_weights[k]` is the total weight of the left subtree of
+    /// (internal) node `k`, for `1 <= k < capacity` (index 0 is unused)
+    left_weights: Vec<Index>,
+    /// The elements, in the leaf order of the original urn
+    elems: Vec<(Weight, T)>,
+    /// The no. of levels below the root (`capacity == 2^depth`)
+    depth: u32,
+    total: Index,
+    draw: IndexDraw,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Flattens the urn into a `FlatUrn` (cloning every element), which
+    /// supports constant-time sampling. The `FlatUrn` uses the same
+    /// index-drawing strategy as the urn.
+    /// Time complexity: `O(n)`.
+    pub fn flatten(&self) -> FlatUrn<T> {
+        let elems: Vec<(Weight, T)> =
+            self.iter().map(|(w, a)| (w, a.clone())).collect();
+        let capacity = elems.len().next_power_of_two();
+        FlatUrn::build(elems, capacity, self.draw)
+    }
+}
+
+impl<T> FlatUrn<T> {
+    /// Lays out `elems` as the leaves of a complete binary tree with
+    /// `capacity` leaves (a power of two, at least `elems.len()`),
+    /// padded with elements of weight 0
+    fn build(
+        elems: Vec<(Weight, T)>,
+        capacity: usize,
+        draw: IndexDraw,
+    ) -> Self {
+        // `sums[k]` is the total weight of the subtree rooted at node `k`
+        let mut sums: Vec<Index> = vec![0; 2 * capacity];
+        for (j, (w, _)) in elems.iter().enumerate() {
+            sums[capacity + j] = widen(*w);
+        }
+        for k in (1..capacity).rev() {
+            sums[k] = sums[2 * k].wrapping_add(sums[2 * k + 1]);
+        }
+        let left_weights = (0..capacity)
+            .map(|k| if k == 0 { 0 } else { sums[2 * k] })
+            .collect();
+        FlatUrn {
+            left_weights,
+            elems,
+            depth: capacity.trailing_zeros(),
+            total: sums[1],
+            draw,
+        }
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether there are no elements (always `false`,
+    /// since `FlatUrn`s are created from non-empty urns)
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// The total weight of all elements
+    pub fn weight(&self) -> Index {
+        self.total
+    }
+
+    /// The depth of every leaf of the flattened tree, i.e. the no. of
+    /// levels walked by every call to `sample_const_time`
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Finds the leaf whose bucket contains the index `i` by walking all
+    /// `depth` levels of the tree, returning the leaf's position
+    /// along with the no. of levels walked
+    fn descend(&self, i: Index) -> (usize, u32) {
+        descend_with(self.depth, self.left_weights.len(), i, |k| {
+            self.left_weights[k]
+        })
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`, walking the
+    /// full depth of the tree regardless of which element is sampled
+    /// (see `FlatUrn`).
+    /// Time complexity: `O(log n)` (exactly `depth()` levels).
+    pub fn sample_const_time<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        let i = draw_index_with(rng, self.total, self.draw);
+        let (pos, _) = self.descend(i);
+        // Padding leaves have weight 0, so they are never reached
+        // (unless the total weight has wrapped around)
+        &self.elems[pos.min(self.elems.len() - 1)].1
+    }
+
+    /// Changes the weight of the leaf at position `pos` from `old` to `new`,
+    /// updating the total and the left-subtree weights of its ancestors.
+    /// Time complexity: `O(log n)`.
+    fn reweigh(&mut self, pos: usize, old: Weight, new: Weight) {
+        let update =
+            |w: Index| w.wrapping_sub(widen(old)).wrapping_add(widen(new));
+        let mut k = self.left_weights.len() + pos;
+        while k > 1 {
+            if k & 1 == 0 {
+                self.left_weights[k / 2] = update(self.left_weights[k / 2]);
+            }
+            k /= 2;
+        }
+        self.total = update(self.total);
+    }
+}
+
+impl<T> WeightedSampler<T> for FlatUrn<T> {
+    fn insert(mut self, w: Weight, a: T) -> Self {
+        if self.elems.len() == self.left_weights.len() {
+            // No padding leaves are left, so double the capacity
+            let capacity = 2 * self.left_weights.len();
+            let mut elems = std::mem::take(&mut self.elems);
+            elems.reserve(capacity - elems.len());
+            self = FlatUrn::build(elems, capacity, self.draw);
+        }
+        self.elems.push((0, a));
+        self.reweigh(self.elems.len() - 1, 0, w);
+        self.elems.last_mut().unwrap().0 = w;
+        self
+    }
+
+    fn remove<R: RngCore + ?Sized>(
+        mut self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>) {
+        let i = draw_index_with(rng, self.total, self.draw);
+        let pos = self.descend(i).0.min(self.elems.len() - 1);
+        // The last element moves into the leaf of the removed one,
+        // and its own leaf becomes padding
+        let last = self.elems.len() - 1;
+        let w_last = self.elems[last].0;
+        self.reweigh(last, w_last, 0);
+        let (w, a) = self.elems.swap_remove(pos);
+        if pos < last {
+            self.reweigh(pos, w, w_last);
+        }
+        (
+            (w, a),
+            if self.elems.is_empty() {
+                None
+            } else {
+                Some(self)
+            },
+        )
+    }
+
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        self.sample_const_time(rng)
+    }
+
+    fn weight(&self) -> Index {
+        FlatUrn::weight(self)
+    }
+
+    fn len(&self) -> usize {
+        FlatUrn::len(self)
+    }
+}
+
+/// Walks all `depth` levels of a flattened tree with `capacity` leaves to
+/// find the leaf whose bucket contains the index `i`, where `left_weight(k)`
+/// is the total weight of the left subtree of node `k`. Returns the leaf's
+/// position along with the no. of levels walked.
+fn descend_with(
+    depth: u32,
+    capacity: usize,
+    i: Index,
+    left_weight: impl Fn(usize) -> Index,
+) -> (usize, u32) {
+    let (mut k, mut i, mut steps) = (1, i, 0);
+    for _ in 0..depth {
+        let wl = left_weight(k);
+        // `go_right` is 1 if `i >= wl` and 0 otherwise, and `mask` is
+        // all ones if `go_right` is 1, so neither select branches
+        let go_right = Index::from(i >= wl);
+        let mask = go_right.wrapping_neg();
+        i = i.wrapping_sub(wl & mask);
+        k = 2 * k + go_right as usize;
+        steps += 1;
+    }
+    (k - capacity, steps)
+}
+
+/* -------------------------------------------------------------------------- */
+/*                          Archived flattened urns                           */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "rkyv")]
+impl<T: rkyv::Archive> ArchivedFlatUrn<T> {
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether there are no elements (always `false`,
+    /// since `FlatUrn`s are created from non-empty urns)
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// The total weight of all elements
+    pub fn weight(&self) -> Index {
+        self.total.to_native()
+    }
+
+    /// The depth of every leaf of the flattened tree
+    /// (see `FlatUrn::depth`)
+    pub fn depth(&self) -> u32 {
+        self.depth.to_native()
+    }
+
+    /// The index-drawing strategy of the archived urn
+    pub fn index_draw(&self) -> IndexDraw {
+        match self.draw {
+            ArchivedIndexDraw::Uniform => IndexDraw::Uniform,
+            ArchivedIndexDraw::Lemire => IndexDraw::Lemire,
+            ArchivedIndexDraw::Bitmask => IndexDraw::Bitmask,
+        }
+    }
+
+    /// Randomly samples (a reference to) an archived element using `rng`,
+    /// reading the archive in place. This draws the same elements as
+    /// `FlatUrn::sample_const_time` on the original `FlatUrn`
+    /// for a given stream of random numbers.
+    /// Time complexity: `O(log n)` (exactly `depth()` levels).
+    pub fn sample_const_time<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> &T::Archived {
+        let i = draw_index_with(rng, self.weight(), self.index_draw());
+        let (pos, _) =
+            descend_with(self.depth(), self.left_weights.len(), i, |k| {
+                self.left_weights[k].to_native()
+            });
+        &self.elems[pos.min(self.elems.len() - 1)].1
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+    use rand::thread_rng;
+
+    #[test]
+    fn descent_depth_is_outcome_independent() {
+        let elems = vec![(3, 'a'), (1, 'b'), (0, 'c'), (4, 'd'), (2, 'e')];
+        let urn = from_list(elems).unwrap();
+        let leaves = urn.to_vec();
+        let flat = urn.flatten();
+        assert_eq!(flat.depth(), 3);
+        assert_eq!(flat.weight(), 10);
+        for i in 0..flat.weight() {
+            let (pos, steps) = flat.descend(i);
+            assert_eq!(steps, flat.depth());
+            assert_eq!(&leaves[pos].1, urn.sample_index_ref(i));
+        }
+        let a = flat.sample_const_time(&mut thread_rng());
+        assert!(*a != 'c');
+    }
+
+    #[test]
+    fn singleton_has_depth_zero() {
+        let flat = from_list(vec![(5, 'a')]).unwrap().flatten();
+        assert_eq!(flat.depth(), 0);
+        assert_eq!(flat.descend(4), (0, 0));
+        assert_eq!(flat.sample_const_time(&mut thread_rng()), &'a');
+    }
+
+    // Inserting and removing elements keeps the flattened tree consistent
+    // with one rebuilt from scratch
+    #[test]
+    fn flat_urn_as_weighted_sampler() {
+        use crate::sampler::WeightedSampler;
+        use crate::types::{widen, Index, Weight};
+
+        let mut rng = thread_rng();
+        let mut flat =
+            from_list(vec![(3, 0), (1, 1), (2, 2)]).unwrap().flatten();
+        for k in 3..20 {
+            flat = flat.insert((k % 4) as Weight, k);
+        }
+        assert_eq!((flat.len(), flat.depth()), (20, 5));
+        for _ in 0..10 {
+            let (_, rest) = flat.remove(&mut rng);
+            flat = rest.unwrap();
+            let total: Index = flat.elems.iter().map(|(w, _)| widen(*w)).sum();
+            assert_eq!(flat.weight(), total);
+        }
+        let rebuilt = super::FlatUrn::build(
+            flat.elems.clone(),
+            flat.left_weights.len(),
+            flat.draw,
+        );
+        assert_eq!(flat, rebuilt);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_urn_samples_in_place() {
+        use crate::types::IndexDraw;
+        use rand::{rngs::StdRng, SeedableRng};
+        use rkyv::rancor::Error;
+
+        let elems = vec![(3, 'a'), (1, 'b'), (0, 'c'), (4, 'd'), (2, 'e')];
+        let urn = from_list(elems).unwrap().with_index_draw(IndexDraw::Lemire);
+        let flat = urn.flatten();
+        let bytes = rkyv::to_bytes::<Error>(&flat).unwrap();
+        let archived =
+            rkyv::access::<super::ArchivedFlatUrn<char>, Error>(&bytes)
+                .unwrap();
+        assert_eq!(archived.len(), 5);
+        assert_eq!(archived.weight(), 10);
+        assert_eq!(archived.depth(), 3);
+        assert_eq!(archived.index_draw(), IndexDraw::Lemire);
+
+        let (mut rng1, mut rng2) =
+            (StdRng::seed_from_u64(7), StdRng::seed_from_u64(7));
+        for _ in 0..50 {
+            let a = archived.sample_const_time(&mut rng1);
+            assert_eq!(*a, *flat.sample_const_time(&mut rng2));
+        }
+        let copy = rkyv::deserialize::<super::FlatUrn<char>, Error>(archived);
+        assert_eq!(copy.unwrap(), flat);
+    }
+}
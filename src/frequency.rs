@@ -0,0 +1,108 @@
+use std::rc::Rc;
+
+use rand_core::RngCore;
+
+use crate::{
+    random::below_u128,
+    types::{Urn, Weight},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                       Weighted choice of generators                        */
+/* -------------------------------------------------------------------------- */
+
+/// A generator of random values of type `T` (e.g. for property-based testing
+/// or fuzzing), which draws its randomness from an `R`.
+/// Generators are reference-counted so that urns of generators can be cloned.
+pub type Generator<R, T> = Rc<dyn Fn(&mut R) -> T>;
+
+/// Chooses one of the generators `gens` with probability proportional to its
+/// weight using `rng`, and runs it with `rng`
+/// (like `frequency` in Haskell's QuickCheck).
+/// Panics if the total weight of `gens` is 0 (including if `gens` is empty).
+/// Time complexity: `O(n)` (build an urn of `Generator`s and use
+/// `Urn::generate` to choose among many generators repeatedly
+/// in `O(log n)`).
+pub fn frequency<R, T, F>(rng: &mut R, gens: &[(Weight, F)]) -> T
+where
+    R: RngCore + ?Sized,
+    F: Fn(&mut R) -> T,
+{
+    // The total weight may not fit in a `Weight`
+    let total: u128 = gens.iter().map(|(w, _)| u128::from(*w)).sum();
+    assert!(total > 0, "frequency: the total weight must be positive");
+    let mut i = below_u128(rng, total);
+    for (w, gen) in gens {
+        let w = u128::from(*w);
+        if i < w {
+            return gen(rng);
+        }
+        i -= w;
+    }
+    unreachable!("sampled index {} is out of range", i)
+}
+
+impl<R: RngCore + ?Sized, T> Urn<Generator<R, T>> {
+    /// Chooses a generator with probability proportional to its weight
+    /// using `rng`, and runs it with `rng`.
+    /// The weights can be recomputed in between calls using `reweight_with`,
+    /// e.g. to make recursive generators less likely as the size of the
+    /// generated values (or of a shrunk test case) decreases.
+    /// Time complexity: `O(log n)` (plus the time taken by the generator).
+    pub fn generate(&self, rng: &mut R) -> T {
+        let gen = self.sample_ref(rng).clone();
+        gen(rng)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type Gen = fn(&mut StdRng) -> u32;
+
+    #[test]
+    fn frequency_skips_zero_weights() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let gens: [(Weight, Gen); 3] =
+            [(0, |_| 0), (3, |rng| rng.gen_range(1..10)), (1, |_| 10)];
+        for _ in 0..100 {
+            assert!(frequency(&mut rng, &gens) > 0);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn frequency_panics_on_zero_total_weight() {
+        let gens: [(Weight, Gen); 1] = [(0, |_| 0)];
+        frequency(&mut StdRng::seed_from_u64(0), &gens);
+    }
+
+    #[test]
+    fn generate_with_dynamic_weights() {
+        let leaf: Generator<StdRng, &str> = Rc::new(|_| "leaf");
+        let node: Generator<StdRng, &str> = Rc::new(|_| "node");
+        let mut gens = from_list(vec![(1, leaf), (4, node)]).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let nodes = (0..100).filter(|_| gens.generate(&mut rng) == "node");
+        assert!(nodes.count() > 50);
+
+        // At size 0, only leaves may be generated
+        let size = 0;
+        gens.reweight_with(|w, gen| {
+            let is_node = gen(&mut StdRng::seed_from_u64(0)) == "node";
+            if is_node {
+                w.min(size)
+            } else {
+                w
+            }
+        });
+        assert!((0..100).all(|_| gens.generate(&mut rng) == "leaf"));
+    }
+}
@@ -0,0 +1,344 @@
+use rand_core::RngCore;
+
+use crate::random::unit_f64;
+
+/* -------------------------------------------------------------------------- */
+/*                        Urns with floating-point weights                    */
+/* -------------------------------------------------------------------------- */
+
+/// Errors caused by invalid floating-point weights
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FloatWeightError {
+    /// The weight is NaN
+    NaN,
+    /// The weight is negative (`-0.0` is treated as 0)
+    Negative,
+    /// The weight is infinite
+    Infinite,
+}
+
+impl std::fmt::Display for FloatWeightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FloatWeightError::NaN => write!(f, "weight is NaN"),
+            FloatWeightError::Negative => write!(f, "weight is negative"),
+            FloatWeightError::Infinite => write!(f, "weight is infinite"),
+        }
+    }
+}
+
+impl std::error::Error for FloatWeightError {}
+
+/// Checks that `w` is a valid weight (finite and non-negative)
+fn validate(w: f64) -> Result<f64, FloatWeightError> {
+    if w.is_nan() {
+        Err(FloatWeightError::NaN)
+    } else if w < 0.0 {
+        Err(FloatWeightError::Negative)
+    } else if w.is_infinite() {
+        Err(FloatWeightError::Infinite)
+    } else {
+        Ok(w)
+    }
+}
+
+/// Adds two floats, returning their (rounded) sum along with the rounding
+/// error, so that `s + err == a + b` exactly (Knuth's TwoSum)
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let a_virt = s - b;
+    let b_virt = s - a_virt;
+    (s, (a - a_virt) + (b - b_virt))
+}
+
+/// A weighted sampler whose weights are `f64`s (e.g. probabilities), for
+/// when quantizing weights to integers would lose too much precision.
+///
+/// Elements live in a flat `Vec` and are addressed by their index in it
+/// (like `FenwickUrn`), while the subtree sums are stored in a complete
+/// binary tree laid out in an array (like `FlatUrn`), whose leaves are padded
+/// with zeros up to the next power of two.
+/// Weights are validated on the way in, so every weight is finite and
+/// non-negative (see `FloatWeightError`).
+///
+/// Updates recompute the sums along one path from the sums of their
+/// children, so rounding errors don't accumulate across updates.
+/// `rebuild` recomputes every sum from the leaves using compensated
+/// (Kahan-style) summation, and `check_sums` verifies the cached sums
+/// up to a relative tolerance.
+///
+/// Time complexity: `push`, `swap_remove`, `set_weight` and `sample` take
+/// `O(log n)` (`push` is amortized), and `from_list` takes `O(n)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FUrn<T> {
+    elems: Vec<(f64, T)>,
+    /// `sums[k]` is the total weight of the subtree rooted at node `k`
+    /// (the children of node `k` are nodes `2k` and `2k + 1`, the leaves
+    /// start at node `capacity`, and index 0 is unused)
+    sums: Vec<f64>,
+}
+
+impl<T> Default for FUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FUrn<T> {
+    /// Creates an empty sampler
+    pub fn new() -> Self {
+        Self {
+            elems: Vec::new(),
+            sums: vec![0.0; 2],
+        }
+    }
+
+    /// Builds a sampler containing `elems` (element `i` of the vector
+    /// ends up at index `i`), failing if any weight is invalid.
+    /// Time complexity: `O(n)`.
+    pub fn from_list(elems: Vec<(f64, T)>) -> Result<Self, FloatWeightError> {
+        for (w, _) in &elems {
+            validate(*w)?;
+        }
+        let capacity = elems.len().next_power_of_two();
+        let mut urn = Self {
+            elems,
+            sums: vec![0.0; 2 * capacity],
+        };
+        urn.rebuild();
+        Ok(urn)
+    }
+
+    /// The no. of leaves of the tree of sums (a power of two)
+    fn capacity(&self) -> usize {
+        self.sums.len() / 2
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether the sampler contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> f64 {
+        self.sums[1]
+    }
+
+    /// Retrieves the weight and (a reference to) the element at index `i`
+    pub fn get(&self, i: usize) -> Option<(f64, &T)> {
+        self.elems.get(i).map(|(w, a)| (*w, a))
+    }
+
+    /// The probability of sampling the element at index `i`
+    /// (`None` if `i` is out of bounds, or if the total weight is 0)
+    pub fn probability(&self, i: usize) -> Option<f64> {
+        let (w, _) = self.get(i)?;
+        let total = self.weight();
+        (total > 0.0).then(|| w / total)
+    }
+
+    /// Sets the leaf for index `i` to `w`, and recomputes the sums of all its
+    /// ancestors from their children
+    fn update_path(&mut self, i: usize, w: f64) {
+        let mut k = self.capacity() + i;
+        self.sums[k] = w;
+        while k > 1 {
+            k /= 2;
+            self.sums[k] = self.sums[2 * k] + self.sums[2 * k + 1];
+        }
+    }
+
+    /// Computes the sum of every subtree from the leaves, using compensated
+    /// summation: each node carries the rounding errors of all the additions
+    /// below it, which are folded back in once at the end
+    fn compensated_sums(&self) -> Vec<f64> {
+        let capacity = self.capacity();
+        let mut sums = vec![(0.0, 0.0); 2 * capacity];
+        for (j, (w, _)) in self.elems.iter().enumerate() {
+            sums[capacity + j] = (*w, 0.0);
+        }
+        for k in (1..capacity).rev() {
+            let ((l, l_err), (r, r_err)) = (sums[2 * k], sums[2 * k + 1]);
+            let (s, err) = two_sum(l, r);
+            sums[k] = (s, l_err + r_err + err);
+        }
+        sums.into_iter().map(|(s, err)| s + err).collect()
+    }
+
+    /// Recomputes the cached sums from the weights of the elements
+    /// using compensated summation (see `FUrn`).
+    /// Time complexity: `O(n)`.
+    pub fn rebuild(&mut self) {
+        self.sums = self.compensated_sums();
+    }
+
+    /// Sets the weight of every element at once (to `f(i)` for the element at
+    /// index `i`), then recomputes the sums. The caller must ensure that every
+    /// new weight is valid.
+    /// Time complexity: `O(n)`.
+    pub(crate) fn reweight_all<F: FnMut(usize) -> f64>(&mut self, mut f: F) {
+        for (i, (w, _)) in self.elems.iter_mut().enumerate() {
+            *w = f(i);
+        }
+        self.rebuild();
+    }
+
+    /// Checks whether every cached sum is within a relative tolerance
+    /// `rel_tol` of the (compensated) sum of the weights below it, and that
+    /// every weight is valid.
+    /// Time complexity: `O(n)`.
+    pub fn check_sums(&self, rel_tol: f64) -> bool {
+        let exact = self.compensated_sums();
+        self.elems.iter().all(|(w, _)| validate(*w).is_ok())
+            && self.sums.iter().zip(exact).skip(1).all(|(&cached, exact)| {
+                (cached - exact).abs() <= rel_tol * exact.abs()
+            })
+    }
+
+    /// Appends element `a` with weight `w`, returning its index
+    /// (or an error if the weight is invalid)
+    pub fn push(&mut self, w: f64, a: T) -> Result<usize, FloatWeightError> {
+        let w = validate(w)?;
+        self.elems.push((w, a));
+        if self.len() > self.capacity() {
+            // Double the capacity (the sums are recomputed from scratch,
+            // which takes amortized constant time)
+            self.sums = vec![0.0; 4 * self.capacity()];
+            self.rebuild();
+        } else {
+            self.update_path(self.len() - 1, w);
+        }
+        Ok(self.len() - 1)
+    }
+
+    /// Sets the weight of the element at index `i` to `w`, returning its old
+    /// weight (or an error if the new weight is invalid).
+    /// Panics if `i` is out of bounds.
+    pub fn set_weight(
+        &mut self,
+        i: usize,
+        w: f64,
+    ) -> Result<f64, FloatWeightError> {
+        let w = validate(w)?;
+        let w_old = std::mem::replace(&mut self.elems[i].0, w);
+        self.update_path(i, w);
+        Ok(w_old)
+    }
+
+    /// Removes the element at index `i`, returning it along with its weight.
+    /// The last element is moved into index `i` (as in `Vec::swap_remove`).
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> (f64, T) {
+        let last = self.len() - 1;
+        self.update_path(i, self.elems[last].0);
+        self.update_path(last, 0.0);
+        self.elems.swap_remove(i)
+    }
+
+    /// Finds the index of the element whose bucket contains `u`,
+    /// where `0 <= u < weight()`.
+    /// Subtrees of weight 0 are never entered, so if `u` lies past the last
+    /// bucket (due to rounding), the last element of non-zero weight is
+    /// returned instead (and if the total weight is 0, the first element).
+    pub fn position_of(&self, u: f64) -> usize {
+        let (mut k, mut u) = (1, u);
+        while k < self.capacity() {
+            let (l, r) = (self.sums[2 * k], self.sums[2 * k + 1]);
+            if u < l || r <= 0.0 {
+                k *= 2;
+            } else {
+                u -= l;
+                k = 2 * k + 1;
+            }
+        }
+        (k - self.capacity()).min(self.len().saturating_sub(1))
+    }
+
+    /// Randomly samples an element using `rng`, returning its index along
+    /// with (a reference to) the element.
+    /// Elements with weight 0 are never sampled (unless every element has
+    /// weight 0, in which case the first element is returned).
+    /// Panics if the sampler is empty.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> (usize, &T) {
+        assert!(!self.is_empty(), "cannot sample from an empty FUrn");
+        let u = unit_f64(rng) * self.weight();
+        let pos = self.position_of(u);
+        (pos, &self.elems[pos].1)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn furn_example() {
+        let elems = vec![(0.25, 'R'), (0.5, 'G'), (0.0, 'Y'), (0.25, 'B')];
+        let mut urn = FUrn::from_list(elems).unwrap();
+        assert_eq!(urn.weight(), 1.0);
+        let buckets: Vec<usize> = [0.0, 0.2, 0.25, 0.7, 0.75, 0.99]
+            .iter()
+            .map(|&u| urn.position_of(u))
+            .collect();
+        assert_eq!(buckets, vec![0, 0, 1, 1, 3, 3]);
+        assert_eq!(urn.probability(1), Some(0.5));
+
+        assert_eq!(urn.push(1.0, 'P'), Ok(4));
+        assert_eq!(urn.set_weight(0, 0.0), Ok(0.25));
+        assert_eq!(urn.swap_remove(1), (0.5, 'G'));
+        assert_eq!(urn.get(1), Some((1.0, &'P')));
+        assert_eq!(urn.weight(), 1.25);
+        assert!(urn.check_sums(0.0));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let (i, _) = urn.sample(&mut rng);
+            assert!(urn.get(i).unwrap().0 > 0.0);
+        }
+    }
+
+    #[test]
+    fn invalid_weights_are_rejected() {
+        assert_eq!(
+            FUrn::from_list(vec![(1.0, 'a'), (f64::NAN, 'b')]),
+            Err(FloatWeightError::NaN)
+        );
+        let mut urn = FUrn::new();
+        assert_eq!(urn.push(-0.5, 'a'), Err(FloatWeightError::Negative));
+        assert_eq!(
+            urn.push(f64::INFINITY, 'a'),
+            Err(FloatWeightError::Infinite)
+        );
+        assert!(urn.is_empty());
+        assert_eq!(urn.push(-0.0, 'a'), Ok(0));
+        assert_eq!(urn.set_weight(0, f64::NAN), Err(FloatWeightError::NaN));
+        assert_eq!(urn.sample(&mut StdRng::seed_from_u64(0)), (0, &'a'));
+    }
+
+    #[test]
+    fn rebuild_restores_compensated_sums() {
+        // 0.1 isn't exactly representable, so naive sums drift
+        let mut urn = FUrn::new();
+        for i in 0..1000 {
+            urn.push(0.1, i).unwrap();
+        }
+        for i in 0..1000 {
+            urn.set_weight(i, 0.1 + f64::EPSILON).unwrap();
+            urn.set_weight(i, 0.1).unwrap();
+        }
+        assert!(urn.check_sums(1e-12));
+        urn.rebuild();
+        assert!(urn.check_sums(0.0));
+        assert!((urn.weight() - 100.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,63 @@
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    types::{Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                        `arbitrary` crate integration                       */
+/* -------------------------------------------------------------------------- */
+
+/// Builds well-formed urns from the raw bytes of a fuzzer: the urn contains
+/// at least one element (since urns are non-empty), followed by as many
+/// further elements as the fuzzer's input provides. The urn is built with
+/// `from_list`, so its tree always has the canonical shape for its size.
+/// (With 64-bit weights, the total weight of the urn may overflow.)
+impl<'a, T: Arbitrary<'a> + Clone> Arbitrary<'a> for Urn<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let first: (Weight, T) = u.arbitrary()?;
+        let mut elems = vec![first];
+        for elem in u.arbitrary_iter()? {
+            elems.push(elem?);
+        }
+        // There is at least one element
+        Ok(from_list(elems).unwrap())
+    }
+
+    fn arbitrary_take_rest(mut u: Unstructured<'a>) -> Result<Self> {
+        let first: (Weight, T) = u.arbitrary()?;
+        let mut elems = vec![first];
+        for elem in u.arbitrary_take_rest_iter()? {
+            elems.push(elem?);
+        }
+        Ok(from_list(elems).unwrap())
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        // At least one element, and no upper bound
+        (<(Weight, T)>::size_hint(depth).0, None)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitrary_urns_are_non_empty() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut u = Unstructured::new(&bytes);
+        let urn = Urn::<u16>::arbitrary(&mut u).unwrap();
+        assert!(urn.size() >= 1);
+        assert_eq!(urn.size() as usize, urn.to_vec().len());
+
+        // Even an empty input produces an urn (of default values)
+        let urn = Urn::<u16>::arbitrary_take_rest(Unstructured::new(&[]));
+        assert_eq!(urn.unwrap().to_vec(), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::types::{Urn, Weight};
+
+/// A mutable handle to the weight of a single element of an urn,
+/// created by `Urn::weight_mut`.      
+/// The guard dereferences to the element's weight, which may be modified
+/// freely. When the guard is dropped, the new weight is written back to the
+/// urn, and the weights cached at all of the element's ancestors are
+/// repaired, so the urn is always well-formed once the guard is gone.
+#[derive(Debug)]
+pub struct WeightGuard<'a, T: Clone> {
+    urn: &'a mut Urn<T>,
+    pos: u32,
+    weight: Weight,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Returns a guard which gives mutable access to the weight of the
+    /// element at (leaf) position `pos`, see `WeightGuard`.       
+    /// Panics if `pos >= self.size()`.     
+    /// Time complexity: `O(log n)` (the ancestors are repaired on `drop`).
+    pub fn weight_mut(&mut self, pos: u32) -> WeightGuard<'_, T> {
+        assert!(
+            pos < self.size(),
+            "position {} out of bounds for urn of size {}",
+            pos,
+            self.size()
+        );
+        let (weight, _) = self.tree.lookup_position(self.size(), pos);
+        WeightGuard {
+            urn: self,
+            pos,
+            weight,
+        }
+    }
+}
+
+impl<T: Clone> Deref for WeightGuard<'_, T> {
+    type Target = Weight;
+
+    fn deref(&self) -> &Weight {
+        &self.weight
+    }
+}
+
+impl<T: Clone> DerefMut for WeightGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Weight {
+        &mut self.weight
+    }
+}
+
+impl<T: Clone> Drop for WeightGuard<'_, T> {
+    fn drop(&mut self) {
+        let size = self.urn.size();
+        self.urn.tree.reweight_position(size, self.pos, self.weight);
+        self.urn.debug_check_invariants();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+
+    #[test]
+    fn weight_mut_repairs_ancestors() {
+        let mut urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        {
+            let mut w = urn.weight_mut(1);
+            assert_eq!(*w, 4);
+            *w += 6;
+        }
+        assert_eq!(urn.weight(), 15);
+        assert_eq!(urn.to_vec(), vec![(2, 'R'), (10, 'G'), (3, 'B')]);
+    }
+}
@@ -0,0 +1,283 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::{path_of_position, singleton},
+};
+
+/// A stable, generational handle to an element of a `HandleUrn`.      
+/// Handles remain valid across subsequent insertions and removals of other
+/// elements. Once the element itself is removed, its handle is invalidated
+/// (even if its slot is later reused for a new element).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Handle {
+    slot: u32,
+    generation: u32,
+}
+
+/// Bookkeeping for one handle slot
+#[derive(Debug, PartialEq, Clone)]
+struct Slot {
+    generation: u32,
+    /// The insertion path (see `Urn::insert`) of the slot's element in the
+    /// urn, or `None` if the slot is free
+    ordinal: Option<u32>,
+}
+
+/// An urn whose elements can be addressed via `Handle`s returned by `insert`.
+///     
+/// Since removing the `k`-th inserted element of an urn moves the most
+/// recently inserted element into the `k`-th slot (and leaves all other
+/// elements in place), we only need to keep track of each element's
+/// insertion path, and update a single path per removal.
+///     
+/// Time complexity: `insert`, `get`, `remove_handle`, `set_weight_handle`
+/// and `sample` all take `O(log n)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HandleUrn<T: Clone> {
+    urn: Option<Urn<T>>,
+    slots: Vec<Slot>,
+    /// Indexes of the free slots in `slots`
+    free: Vec<u32>,
+    /// `owners[k]` is the slot of the `k`-th inserted element of the urn
+    owners: Vec<u32>,
+}
+
+impl<T: Clone> Default for HandleUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> HandleUrn<T> {
+    /// Creates an empty `HandleUrn`
+    pub fn new() -> Self {
+        Self {
+            urn: None,
+            slots: Vec::new(),
+            free: Vec::new(),
+            owners: Vec::new(),
+        }
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.urn.as_ref()
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    /// Looks up the insertion path of the element referred to by `h`
+    /// (`None` if the handle has been invalidated)
+    fn ordinal(&self, h: Handle) -> Option<u32> {
+        let slot = self.slots.get(h.slot as usize)?;
+        if slot.generation == h.generation {
+            slot.ordinal
+        } else {
+            None
+        }
+    }
+
+    /// Inserts element `a` with weight `w`, returning a handle to it
+    pub fn insert(&mut self, w: Weight, a: T) -> Handle {
+        let ordinal = self.owners.len() as u32;
+        self.urn = Some(match self.urn.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        });
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot as usize].ordinal = Some(ordinal);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    ordinal: Some(ordinal),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        self.owners.push(slot);
+        Handle {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        }
+    }
+
+    /// Retrieves the weight and (a reference to) the element referred to
+    /// by `h` (`None` if the handle has been invalidated)
+    pub fn get(&self, h: Handle) -> Option<(Weight, &T)> {
+        let k = self.ordinal(h)?;
+        self.urn.as_ref().map(|urn| urn.tree.lookup_path(k))
+    }
+
+    /// Retrieves the weight and a mutable reference to the element referred
+    /// to by `h` (`None` if the handle has been invalidated).
+    /// The weight can be changed with `set_weight_handle` or `modify_handle`.
+    pub fn get_mut(&mut self, h: Handle) -> Option<(Weight, &mut T)> {
+        let k = self.ordinal(h)?;
+        self.urn.as_mut().map(|urn| urn.tree.lookup_path_mut(k))
+    }
+
+    /// Applies `f` to the weight and the element referred to by `h` in
+    /// place, repairing the cached weights of the element's ancestors,
+    /// and returns the result of `f` (or `None` if the handle has been
+    /// invalidated)
+    pub fn modify_handle<F, R>(&mut self, h: Handle, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Weight, &mut T) -> R,
+    {
+        let k = self.ordinal(h)?;
+        let urn = self.urn.as_mut()?;
+        Some(urn.tree.modify_path(k, f))
+    }
+
+    /// Removes the element referred to by `h`, returning it along with its
+    /// weight (or `None` if the handle has been invalidated).
+    /// The handle is invalidated.
+    pub fn remove_handle(&mut self, h: Handle) -> Option<(Weight, T)> {
+        let k = self.ordinal(h)?;
+        let urn = self.urn.take()?;
+        let (elem, urn_opt) = urn.remove_nth(k);
+        self.urn = urn_opt;
+
+        // The most recently inserted element moves into the `k`-th slot
+        self.owners.swap_remove(k as usize);
+        if let Some(&moved) = self.owners.get(k as usize) {
+            self.slots[moved as usize].ordinal = Some(k);
+        }
+        let slot = &mut self.slots[h.slot as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.ordinal = None;
+        self.free.push(h.slot);
+        Some(elem)
+    }
+
+    /// Sets the weight of the element referred to by `h` to `w`,
+    /// returning its old weight (or `None` if the handle has been invalidated)
+    pub fn set_weight_handle(
+        &mut self,
+        h: Handle,
+        w: Weight,
+    ) -> Option<Weight> {
+        let k = self.ordinal(h)?;
+        let urn = self.urn.as_mut()?;
+        Some(urn.tree.reweight_path(k, w))
+    }
+
+    /// Removes all the elements, returning an iterator over them (along with
+    /// their weights and their handles, which are all invalidated)
+    /// in leaf order. The slots are kept for reuse by later insertions.
+    /// Time complexity: `O(n log n)`.
+    pub fn drain(&mut self) -> std::vec::IntoIter<(Handle, Weight, T)> {
+        let elems = self.urn.take().map_or_else(Vec::new, Urn::into_vec);
+        let n = elems.len() as u32;
+        let drained: Vec<_> = (0..n)
+            .zip(elems)
+            .map(|(pos, (w, a))| {
+                let slot = self.owners[path_of_position(n, pos) as usize];
+                let h = Handle {
+                    slot,
+                    generation: self.slots[slot as usize].generation,
+                };
+                (h, w, a)
+            })
+            .collect();
+        self.release_all();
+        drained.into_iter()
+    }
+
+    /// Removes all the elements, invalidating all handles.
+    /// The slots are kept for reuse by later insertions.
+    /// Time complexity: `O(n)`.
+    pub fn clear(&mut self) {
+        self.urn = None;
+        self.release_all();
+    }
+
+    /// Invalidates the handles of all the elements, and frees their slots
+    fn release_all(&mut self) {
+        for slot in self.owners.drain(..) {
+            let s = &mut self.slots[slot as usize];
+            s.generation = s.generation.wrapping_add(1);
+            s.ordinal = None;
+            self.free.push(slot);
+        }
+    }
+
+    /// Randomly samples an element using `rng`, returning (a reference to) it
+    /// along with its handle (or `None` if there are no elements)
+    pub fn sample<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<(Handle, &T)> {
+        let urn = self.urn.as_ref()?;
+        let i = urn.draw_index(rng);
+        let (k, _, a) = urn.tree.locate_index(i);
+        let slot = self.owners[k as usize];
+        let h = Handle {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        };
+        Some((h, a))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn handles_survive_removals() {
+        let mut urn = HandleUrn::new();
+        let handles: Vec<Handle> =
+            (0..10).map(|i| urn.insert(i + 1, i)).collect();
+        assert_eq!(urn.remove_handle(handles[3]), Some((4, 3)));
+        assert_eq!(urn.remove_handle(handles[3]), None);
+        assert_eq!(urn.set_weight_handle(handles[9], 20), Some(10));
+        assert_eq!(urn.get(handles[9]), Some((20, &9)));
+        for (i, h) in handles.iter().enumerate().filter(|(i, _)| *i != 3) {
+            assert_eq!(urn.get(*h).map(|(_, a)| *a), Some(i as Weight));
+        }
+
+        // The freed slot is reused, but the old handle stays invalid
+        let h = urn.insert(1, 100);
+        assert_eq!(urn.get(handles[3]), None);
+        assert_eq!(urn.get(h), Some((1, &100)));
+
+        let (h, a) = urn.sample(&mut thread_rng()).unwrap();
+        assert_eq!(urn.get(h).map(|(_, b)| b), Some(a));
+    }
+
+    #[test]
+    fn drain_invalidates_every_handle() {
+        let mut urn = HandleUrn::new();
+        let handles: Vec<Handle> =
+            (0..5).map(|i| urn.insert(i + 1, i)).collect();
+        urn.remove_handle(handles[1]);
+        let mut drained: Vec<_> = urn.drain().collect();
+        drained.sort_by_key(|&(_, _, a)| a);
+        let expected =
+            [0, 2, 3, 4].map(|i| (handles[i], i as Weight + 1, i as Weight));
+        assert_eq!(drained, expected);
+        assert!(urn.is_empty());
+        assert!(handles.iter().all(|&h| urn.get(h).is_none()));
+
+        // The slots are reused
+        let h = urn.insert(7, 7);
+        assert_eq!(urn.get(h), Some((7, &7)));
+        assert_eq!(urn.slots.len(), 5);
+        urn.clear();
+        assert_eq!((urn.get(h), urn.len()), (None, 0));
+    }
+}
@@ -0,0 +1,546 @@
+use std::{collections::HashMap, hash::Hash};
+
+use rand_core::RngCore;
+
+use crate::{
+    handle::{Handle, HandleUrn},
+    types::{Index, Urn, Weight, WeightError},
+};
+
+/// How to merge elements which map to the same key when converting an urn
+/// into an `UrnMap` (see `Urn::map_into_keyed`)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MergePolicy {
+    /// Keep the first element (in leaf order) with the key,
+    /// discarding the others
+    KeepFirst,
+    /// Keep the last element (in leaf order) with the key,
+    /// discarding the others
+    KeepLast,
+    /// Keep the first element (in leaf order) with the key,
+    /// with the sum of the weights of all elements with the key
+    /// (which must not exceed `Weight::MAX`)
+    SumWeights,
+}
+
+/// An urn whose elements are addressed by keys of type `K`
+/// (at most one element per key).
+/// This is a thin layer over `HandleUrn` which maps each key to the handle
+/// of its element (and vice versa, so that sampling can return the key).
+/// Time complexity: `insert`, `get`, `remove`, `set_weight` and `sample`
+/// all take `O(log n)` (expected, due to hashing).
+#[derive(Debug, Clone)]
+pub struct UrnMap<K, T: Clone> {
+    elems: HandleUrn<T>,
+    handles: HashMap<K, Handle>,
+    keys: HashMap<Handle, K>,
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> Default for UrnMap<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> UrnMap<K, T> {
+    /// Creates an empty `UrnMap`
+    pub fn new() -> Self {
+        Self {
+            elems: HandleUrn::new(),
+            handles: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.elems.urn()
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// Inserts element `a` with weight `w` under the key `key`, returning
+    /// the element previously stored under `key` (if any) along with
+    /// its weight
+    pub fn insert(&mut self, key: K, w: Weight, a: T) -> Option<(Weight, T)> {
+        let old = self.remove(&key);
+        let h = self.elems.insert(w, a);
+        self.keys.insert(h, key.clone());
+        self.handles.insert(key, h);
+        old
+    }
+
+    /// Retrieves the weight and (a reference to) the element stored
+    /// under `key`
+    pub fn get(&self, key: &K) -> Option<(Weight, &T)> {
+        self.elems.get(*self.handles.get(key)?)
+    }
+
+    /// Whether there is an element stored under `key`
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.handles.contains_key(key)
+    }
+
+    /// Removes the element stored under `key`, returning it along with
+    /// its weight
+    pub fn remove(&mut self, key: &K) -> Option<(Weight, T)> {
+        let h = self.handles.remove(key)?;
+        self.keys.remove(&h);
+        self.elems.remove_handle(h)
+    }
+
+    /// Sets the weight of the element stored under `key` to `w`,
+    /// returning its old weight
+    pub fn set_weight(&mut self, key: &K, w: Weight) -> Option<Weight> {
+        self.elems.set_weight_handle(*self.handles.get(key)?, w)
+    }
+
+    /// Removes all the keys and their elements, returning an iterator over
+    /// them (along with the elements' weights) in the urn's leaf order.
+    /// Time complexity: `O(n log n)`.
+    pub fn drain(&mut self) -> std::vec::IntoIter<(K, Weight, T)> {
+        let drained: Vec<_> = self
+            .elems
+            .drain()
+            .map(|(h, w, a)| {
+                (self.keys.remove(&h).expect("every handle has a key"), w, a)
+            })
+            .collect();
+        self.handles.clear();
+        drained.into_iter()
+    }
+
+    /// Removes all the keys and their elements.
+    /// Time complexity: `O(n)`.
+    pub fn clear(&mut self) {
+        self.elems.clear();
+        self.handles.clear();
+        self.keys.clear();
+    }
+
+    /// Randomly samples an element using `rng`, returning (references to)
+    /// its key and the element itself (or `None` if there are no elements)
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<(&K, &T)> {
+        let (h, a) = self.elems.sample(rng)?;
+        Some((&self.keys[&h], a))
+    }
+}
+
+/// The element stored under a key of an `UrnMap`, which may or may not
+/// exist, created by `UrnMap::entry`
+#[derive(Debug)]
+pub enum MapEntry<'a, K, T: Clone> {
+    /// There is an element stored under the key
+    Occupied(OccupiedMapEntry<'a, K, T>),
+    /// There is no element stored under the key
+    Vacant(VacantMapEntry<'a, K, T>),
+}
+
+/// An entry for a key with an element in an `UrnMap` (see `MapEntry`)
+#[derive(Debug)]
+pub struct OccupiedMapEntry<'a, K, T: Clone> {
+    map: &'a mut UrnMap<K, T>,
+    h: Handle,
+}
+
+/// An entry for a key without an element in an `UrnMap` (see `MapEntry`)
+#[derive(Debug)]
+pub struct VacantMapEntry<'a, K, T: Clone> {
+    map: &'a mut UrnMap<K, T>,
+    key: K,
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> UrnMap<K, T> {
+    /// Gets the entry for `key`, e.g. to update its element if there is one,
+    /// or insert one otherwise (see `MapEntry::and_modify` and
+    /// `MapEntry::or_insert`), looking up the key only once.
+    /// Time complexity: `O(1)` (expected).
+    pub fn entry(&mut self, key: K) -> MapEntry<'_, K, T> {
+        match self.handles.get(&key) {
+            Some(&h) => MapEntry::Occupied(OccupiedMapEntry { map: self, h }),
+            None => MapEntry::Vacant(VacantMapEntry { map: self, key }),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, T: Clone> MapEntry<'a, K, T> {
+    /// The entry's key
+    pub fn key(&self) -> &K {
+        match self {
+            MapEntry::Occupied(entry) => entry.key(),
+            MapEntry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Applies `f` to the weight and the element in place if the entry is
+    /// occupied (see `OccupiedMapEntry::modify`), returning the entry.
+    /// Time complexity: `O(log n)`.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Weight, &mut T),
+    {
+        if let MapEntry::Occupied(entry) = &mut self {
+            entry.modify(f);
+        }
+        self
+    }
+
+    /// Inserts element `a` with weight `w` under the key if the entry is
+    /// vacant, returning a mutable reference to the element stored under
+    /// the key.
+    /// Time complexity: `O(log n)` (expected).
+    pub fn or_insert(self, w: Weight, a: T) -> &'a mut T {
+        self.or_insert_with(|| (w, a))
+    }
+
+    /// Inserts the (weight, element) pair returned by `f` under the key if
+    /// the entry is vacant (so `f` is only called if it is), returning a
+    /// mutable reference to the element stored under the key.
+    /// Time complexity: `O(log n)` (expected).
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut T
+    where
+        F: FnOnce() -> (Weight, T),
+    {
+        match self {
+            MapEntry::Occupied(entry) => entry.into_mut(),
+            MapEntry::Vacant(entry) => {
+                let (w, a) = f();
+                entry.insert(w, a)
+            }
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, T: Clone> OccupiedMapEntry<'a, K, T> {
+    /// The entry's key
+    pub fn key(&self) -> &K {
+        &self.map.keys[&self.h]
+    }
+
+    /// Retrieves the weight and (a reference to) the element
+    pub fn get(&self) -> (Weight, &T) {
+        self.map
+            .elems
+            .get(self.h)
+            .expect("occupied entries are valid")
+    }
+
+    /// Retrieves a mutable reference to the element (but not its weight,
+    /// see `set_weight` and `modify`)
+    pub fn get_mut(&mut self) -> &mut T {
+        self.map
+            .elems
+            .get_mut(self.h)
+            .expect("occupied entries are valid")
+            .1
+    }
+
+    /// Converts the entry into a mutable reference to the element
+    pub fn into_mut(self) -> &'a mut T {
+        self.map
+            .elems
+            .get_mut(self.h)
+            .expect("occupied entries are valid")
+            .1
+    }
+
+    /// Sets the weight of the element to `w`, returning its old weight
+    pub fn set_weight(&mut self, w: Weight) -> Weight {
+        self.modify(|w_old, _| std::mem::replace(w_old, w))
+    }
+
+    /// Applies `f` to the weight and the element in place (see
+    /// `HandleUrn::modify_handle`), returning the result of `f`
+    pub fn modify<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Weight, &mut T) -> R,
+    {
+        self.map
+            .elems
+            .modify_handle(self.h, f)
+            .expect("occupied entries are valid")
+    }
+
+    /// Removes the key and its element, returning the element along with
+    /// its weight
+    pub fn remove(self) -> (Weight, T) {
+        let key = self.map.keys.remove(&self.h).expect("entries have keys");
+        self.map.handles.remove(&key);
+        self.map
+            .elems
+            .remove_handle(self.h)
+            .expect("occupied entries are valid")
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, T: Clone> VacantMapEntry<'a, K, T> {
+    /// The entry's key
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Inserts element `a` with weight `w` under the key, returning a
+    /// mutable reference to it
+    pub fn insert(self, w: Weight, a: T) -> &'a mut T {
+        let h = self.map.elems.insert(w, a);
+        self.map.keys.insert(h, self.key.clone());
+        self.map.handles.insert(self.key, h);
+        self.map
+            .elems
+            .get_mut(h)
+            .expect("the handle was just created")
+            .1
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Converts the urn into an `UrnMap`, storing each element under the key
+    /// `f(&a)`. Elements which map to the same key are merged according
+    /// to `policy`.
+    /// Fails with `WeightError::Overflow` if `policy` is `SumWeights` and
+    /// the summed weights of the elements with some key exceed `Weight::MAX`.
+    /// Time complexity: `O(n log n)`.
+    pub fn map_into_keyed<K, F>(
+        self,
+        f: F,
+        policy: MergePolicy,
+    ) -> Result<UrnMap<K, T>, WeightError>
+    where
+        K: Hash + Eq + Clone,
+        F: Fn(&T) -> K,
+    {
+        let mut map = UrnMap::new();
+        for (w, a) in self.into_vec() {
+            let key = f(&a);
+            match (policy, map.handles.get(&key)) {
+                (_, None) | (MergePolicy::KeepLast, Some(_)) => {
+                    map.insert(key, w, a);
+                }
+                (MergePolicy::KeepFirst, Some(_)) => {}
+                (MergePolicy::SumWeights, Some(&h)) => {
+                    let (w_old, _) = map.elems.get(h).unwrap();
+                    let w_new =
+                        w_old.checked_add(w).ok_or(WeightError::Overflow)?;
+                    map.elems.set_weight_handle(h, w_new);
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// An urn which stores each distinct element at most once: inserting an
+/// element which is already present adds to its weight instead of storing
+/// a duplicate leaf (see `insert_merge`).
+/// This is an `UrnMap` keyed by the elements themselves, so looking up an
+/// element's leaf takes expected `O(1)` time.
+/// Time complexity: `insert_merge`, `weight_of`, `remove` and `sample`
+/// all take `O(log n)` (expected, due to hashing).
+#[derive(Debug, Clone)]
+pub struct MultisetUrn<T: Clone> {
+    map: UrnMap<T, ()>,
+}
+
+impl<T: Hash + Eq + Clone> Default for MultisetUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash + Eq + Clone> MultisetUrn<T> {
+    /// Creates an empty `MultisetUrn`
+    pub fn new() -> Self {
+        Self { map: UrnMap::new() }
+    }
+
+    /// The no. of distinct elements
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The total weight of all elements
+    pub fn weight(&self) -> Index {
+        self.map.urn().map_or(0, |urn| urn.weight())
+    }
+
+    /// Adds `w` to the weight of element `a`, inserting `a` if it isn't
+    /// present yet. Returns the element's new weight.
+    /// Fails with `WeightError::Overflow` (leaving the element's weight
+    /// unchanged) if the new weight would exceed `Weight::MAX`.
+    pub fn insert_merge(
+        &mut self,
+        w: Weight,
+        a: T,
+    ) -> Result<Weight, WeightError> {
+        match self.map.get(&a) {
+            Some((w_old, _)) => {
+                let w_new =
+                    w_old.checked_add(w).ok_or(WeightError::Overflow)?;
+                self.map.set_weight(&a, w_new);
+                Ok(w_new)
+            }
+            None => {
+                self.map.insert(a, w, ());
+                Ok(w)
+            }
+        }
+    }
+
+    /// The weight of element `a` (`None` if it isn't present)
+    pub fn weight_of(&self, a: &T) -> Option<Weight> {
+        self.map.get(a).map(|(w, _)| w)
+    }
+
+    /// Removes element `a`, returning its weight
+    /// (`None` if it isn't present)
+    pub fn remove(&mut self, a: &T) -> Option<Weight> {
+        self.map.remove(a).map(|(w, _)| w)
+    }
+
+    /// Removes all the elements, returning an iterator over them (and their
+    /// weights) in the urn's leaf order.
+    /// Time complexity: `O(n log n)`.
+    pub fn drain(&mut self) -> std::vec::IntoIter<(Weight, T)> {
+        let drained: Vec<_> =
+            self.map.drain().map(|(a, w, ())| (w, a)).collect();
+        drained.into_iter()
+    }
+
+    /// Removes all the elements.
+    /// Time complexity: `O(n)`.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`
+    /// (`None` if there are no elements)
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        self.map.sample(rng).map(|(a, _)| a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use rand::thread_rng;
+
+    #[test]
+    fn map_into_keyed_merges_duplicates() {
+        let urn = from_list(vec![(1, "ab"), (2, "cd"), (3, "ax"), (4, "b")]);
+        let first = |s: &&str| s.chars().next().unwrap();
+
+        let map = urn
+            .clone()
+            .unwrap()
+            .map_into_keyed(first, MergePolicy::KeepFirst)
+            .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&'a'), Some((1, &"ab")));
+
+        let map = urn
+            .clone()
+            .unwrap()
+            .map_into_keyed(first, MergePolicy::KeepLast)
+            .unwrap();
+        assert_eq!(map.get(&'a'), Some((3, &"ax")));
+
+        let mut map = urn
+            .unwrap()
+            .map_into_keyed(first, MergePolicy::SumWeights)
+            .unwrap();
+        assert_eq!(map.get(&'a'), Some((4, &"ab")));
+        assert_eq!(map.urn().map(|u| u.weight()), Some(10));
+
+        assert_eq!(map.set_weight(&'c', 5), Some(2));
+        assert_eq!(map.insert('b', 6, "bb"), Some((4, "b")));
+        assert_eq!(map.remove(&'a'), Some((4, "ab")));
+        assert!(!map.contains_key(&'a'));
+        assert_eq!(map.urn().map(|u| u.weight()), Some(11));
+
+        let (key, a) = map.sample(&mut thread_rng()).unwrap();
+        assert_eq!(map.get(key).map(|(_, b)| b), Some(a));
+    }
+
+    #[test]
+    fn summing_weights_rejects_overflow() {
+        let half = Weight::MAX / 2 + 1;
+        let urn = from_list(vec![(half, 'a'), (1, 'b'), (half, 'a')]).unwrap();
+        let keep = urn.clone().map_into_keyed(|&a| a, MergePolicy::KeepFirst);
+        assert_eq!(keep.map(|map| map.len()), Ok(2));
+        let sum = urn.map_into_keyed(|&a| a, MergePolicy::SumWeights);
+        assert_eq!(sum.err(), Some(WeightError::Overflow));
+    }
+
+    #[test]
+    fn entries_modify_or_insert() {
+        let mut map = UrnMap::new();
+        for word in ["to", "be", "or", "not", "to", "be"] {
+            map.entry(word)
+                .and_modify(|w, count| {
+                    *w += 1;
+                    *count += 1;
+                })
+                .or_insert(1, 1);
+        }
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(&"to"), Some((2, &2)));
+        assert_eq!(map.urn().map(|u| u.weight()), Some(6));
+
+        match map.entry("or") {
+            MapEntry::Occupied(mut entry) => {
+                assert_eq!(entry.set_weight(5), 1);
+                assert_eq!(entry.remove(), (5, 1));
+            }
+            MapEntry::Vacant(_) => panic!("`or` has an element"),
+        }
+        assert!(!map.contains_key(&"or"));
+        *map.entry("question").or_insert(3, 0) += 10;
+        assert_eq!(map.get(&"question"), Some((3, &10)));
+        assert_eq!(map.urn().map(|u| u.weight()), Some(8));
+    }
+
+    #[test]
+    fn insert_merge_accumulates_weights() {
+        let mut urn = MultisetUrn::new();
+        for token in "abracadabra".chars() {
+            urn.insert_merge(1, token).unwrap();
+        }
+        assert_eq!(urn.len(), 5);
+        assert_eq!(urn.weight(), 11);
+        assert_eq!(urn.weight_of(&'a'), Some(5));
+        assert_eq!(urn.insert_merge(2, 'd'), Ok(3));
+        assert_eq!(urn.remove(&'r'), Some(2));
+        assert_eq!(urn.weight_of(&'r'), None);
+        assert_eq!(urn.weight(), 11);
+        assert!(urn.sample(&mut thread_rng()).is_some());
+
+        let mut drained: Vec<_> = urn.drain().collect();
+        drained.sort_by_key(|&(_, a)| a);
+        assert_eq!(drained, [(5, 'a'), (2, 'b'), (1, 'c'), (3, 'd')]);
+        assert!(urn.is_empty() && urn.sample(&mut thread_rng()).is_none());
+    }
+
+    #[test]
+    fn insert_merge_rejects_overflowing_weights() {
+        let mut urn = MultisetUrn::new();
+        assert_eq!(urn.insert_merge(Weight::MAX - 1, 'a'), Ok(Weight::MAX - 1));
+        assert_eq!(urn.insert_merge(1, 'a'), Ok(Weight::MAX));
+        assert_eq!(urn.insert_merge(1, 'a'), Err(WeightError::Overflow));
+        assert_eq!(urn.weight_of(&'a'), Some(Weight::MAX));
+        assert_eq!(urn.insert_merge(0, 'a'), Ok(Weight::MAX));
+    }
+}
@@ -1,10 +1,151 @@
-#![allow(dead_code)]
+//! A Rust implementation of *Urns* (Lampropulos et al. 2017),
+//! a data structure for sampling from and updating discrete distributions
+//! in logarithmic time.
+//!
+//! The public API consists of the [`Urn`] type (along with its underlying
+//! [`Tree`] representation) and the constructors [`singleton`],
+//! [`from_list`], [`from_iter_exact`], [`from_chunks`] and [`from_list_naive`]
+//! (or [`UrnBuilder`] for building urns incrementally, and the [`urn!`] macro
+//! for writing down urns literally).
+//! All weights have type [`Weight`].
+//!
+//! The [`prelude`] module re-exports the most commonly used items, and the
+//! [`reservoir`] module samples from streams of weighted items.
+//! With the `ffi` feature, the [`ffi`] module provides a C API.
 
+mod alias;
 mod almost_perfect;
+mod arena;
+mod audit;
+mod batch;
+#[cfg(feature = "binary")]
+mod binary;
+mod bounded;
+mod builder;
+mod canonical;
+mod convert;
+mod decay;
+mod display;
+mod entry;
+mod fenwick;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fixed")]
+mod fixed;
+mod flat;
+mod frequency;
+mod furn;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod guard;
+mod handle;
+mod keyed;
+mod log_weight;
+mod loot;
+mod macros;
+mod metrics;
+mod nested;
+mod observer;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod positions;
+pub mod prelude;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(test)]
 mod quickcheck_tests;
+mod random;
+mod random_urn;
+mod resample;
+pub mod reservoir;
+mod sampler;
+mod search;
+mod shape;
+mod shared;
+mod slice;
+mod split;
+mod static_urn;
+mod stats;
+mod strata;
+#[cfg(feature = "proptest")]
+mod strategy;
+mod sync;
+#[cfg(any(feature = "csv", feature = "json"))]
+mod tabular;
+mod tempered;
+#[cfg(feature = "trace")]
+mod trace;
+mod transaction;
+mod transfer;
 mod types;
+mod undo;
 mod urn;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-fn main() {
-    println!("Hello!");
-}
+pub use crate::alias::AliasTable;
+pub use crate::arena::{ArenaUrn, SnapshotDiff, SnapshotId, SnapshotMismatch};
+pub use crate::audit::{AuditEntry, AuditReport};
+pub use crate::batch::DrawReport;
+#[cfg(feature = "binary")]
+pub use crate::binary::CodecError;
+pub use crate::bounded::{BoundedUrn, EvictionPolicy};
+pub use crate::builder::UrnBuilder;
+pub use crate::convert::ConversionError;
+pub use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::fenwick::FenwickUrn;
+#[cfg(feature = "fixed")]
+pub use crate::fixed::FixedUrn;
+#[cfg(feature = "rkyv")]
+pub use crate::flat::ArchivedFlatUrn;
+pub use crate::flat::FlatUrn;
+pub use crate::frequency::{frequency, Generator};
+pub use crate::furn::{FUrn, FloatWeightError};
+pub use crate::guard::WeightGuard;
+pub use crate::handle::{Handle, HandleUrn};
+pub use crate::keyed::{
+    MapEntry, MergePolicy, MultisetUrn, OccupiedMapEntry, UrnMap,
+    VacantMapEntry,
+};
+pub use crate::log_weight::LogWeightUrn;
+pub use crate::loot::LootTable;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::Metrics;
+pub use crate::nested::NestedUrn;
+pub use crate::observer::{ObservedUrn, UrnObserver};
+#[cfg(feature = "python")]
+pub use crate::python::PyUrn;
+pub use crate::random_urn::RandomUrn;
+pub use crate::resample::ResampleScheme;
+pub use crate::sampler::WeightedSampler;
+pub use crate::shape::DepthStats;
+pub use crate::shared::{from_list_shared, singleton_shared, SharedUrn};
+pub use crate::slice::UrnSliceExt;
+pub use crate::static_urn::StaticUrn;
+pub use crate::stats::{
+    CapacityPlan, ChiSquare, DrawDistribution, ExpectedCount,
+};
+pub use crate::strata::Strata;
+#[cfg(feature = "proptest")]
+pub use crate::strategy::{UrnStrategy, UrnValueTree};
+pub use crate::sync::{ShardedUrn, SyncUrn};
+#[cfg(any(feature = "csv", feature = "json"))]
+pub use crate::tabular::{ImportError, RowError};
+#[cfg(feature = "trace")]
+pub use crate::trace::{Replay, SampleTrace};
+pub use crate::transaction::{Transaction, TransactionError};
+pub use crate::transfer::{move_element, MoveError};
+#[cfg(feature = "rkyv")]
+pub use crate::types::ArchivedIndexDraw;
+pub use crate::types::{
+    Index, IndexDraw, Leaves, Tree, Urn, Weight, WeightError, ZeroWeightPolicy,
+};
+pub use crate::undo::{Checkpoint, UndoUrn};
+pub use crate::urn::{
+    from_chunks, from_iter_exact, from_list, from_list_naive, singleton,
+    BucketsExpanded, RandomOrder, ShuffleBag, DEFAULT_REBUILD_FACTOR,
+};
+pub use crate::validate::InvariantViolation;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::JsUrn;
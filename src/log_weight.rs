@@ -0,0 +1,301 @@
+use rand_core::RngCore;
+
+use crate::{
+    furn::{FUrn, FloatWeightError},
+    random::open01_f64,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                              Log-space weights                             */
+/* -------------------------------------------------------------------------- */
+
+/// How far (in log space) a log-weight may exceed the offset of a
+/// `LogWeightUrn`, or its total weight may fall below the offset,
+/// before the weights are rescaled
+const REBASE_RANGE: f64 = 64.0;
+
+/// Checks that `lw` is a valid log-weight (not NaN, and not `+inf`).
+/// `-inf` is allowed, and stands for weight 0.
+fn validate_log(lw: f64) -> Result<f64, FloatWeightError> {
+    if lw.is_nan() {
+        Err(FloatWeightError::NaN)
+    } else if lw == f64::INFINITY {
+        Err(FloatWeightError::Infinite)
+    } else {
+        Ok(lw)
+    }
+}
+
+/// A weighted sampler whose weights are given in log space, for extremely
+/// skewed distributions whose weights don't fit in an `f64`
+/// (e.g. the log-likelihoods of a set of hypotheses).
+///
+/// The element at index `i` is stored in an `FUrn` with weight
+/// `exp(lw_i - offset)`, where the offset is the largest log-weight at the
+/// time the weights were last rescaled. The weights are rescaled (in `O(n)`)
+/// whenever a log-weight exceeds the offset by a large margin, or the total
+/// weight falls far below it, so no weight overflows, and every weight whose
+/// probability is representable stays representable.
+///
+/// Time complexity: `push`, `swap_remove`, `set_log_weight` and `sample` take
+/// amortized `O(log n)`, and `sample_k_distinct_gumbel` takes `O(n + k log k)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogWeightUrn<T> {
+    urn: FUrn<T>,
+    /// `log_weights[i]` is the log-weight of the element at index `i`
+    log_weights: Vec<f64>,
+    /// The no. of finite log-weights (i.e. of non-zero weights)
+    finite: usize,
+    offset: f64,
+}
+
+impl<T> Default for LogWeightUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> LogWeightUrn<T> {
+    /// Creates an empty sampler
+    pub fn new() -> Self {
+        Self {
+            urn: FUrn::new(),
+            log_weights: Vec::new(),
+            finite: 0,
+            offset: 0.0,
+        }
+    }
+
+    /// Builds a sampler containing `elems` (given as pairs of log-weights
+    /// and elements, where element `i` of the vector ends up at index `i`),
+    /// failing if any log-weight is NaN or `+inf`.
+    /// Time complexity: `O(n)`.
+    pub fn from_log_weights(
+        elems: Vec<(f64, T)>,
+    ) -> Result<Self, FloatWeightError> {
+        let mut log_weights = Vec::with_capacity(elems.len());
+        for (lw, _) in &elems {
+            log_weights.push(validate_log(*lw)?);
+        }
+        let finite = log_weights.iter().filter(|lw| lw.is_finite()).count();
+        // The weights are recomputed by `rebase`
+        let elems = elems.into_iter().map(|(_, a)| (0.0, a)).collect();
+        let mut urn = Self {
+            urn: FUrn::from_list(elems)?,
+            log_weights,
+            finite,
+            offset: 0.0,
+        };
+        urn.rebase();
+        Ok(urn)
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.urn.len()
+    }
+
+    /// Whether the sampler contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.urn.is_empty()
+    }
+
+    /// Retrieves the log-weight and (a reference to) the element
+    /// at index `i`
+    pub fn get(&self, i: usize) -> Option<(f64, &T)> {
+        let (_, a) = self.urn.get(i)?;
+        Some((self.log_weights[i], a))
+    }
+
+    /// The logarithm of the total weight of all the elements
+    /// (`-inf` if every weight is 0)
+    pub fn log_weight(&self) -> f64 {
+        self.offset + self.urn.weight().ln()
+    }
+
+    /// The probability of sampling the element at index `i`
+    /// (`None` if `i` is out of bounds, or if every weight is 0)
+    pub fn probability(&self, i: usize) -> Option<f64> {
+        let (lw, _) = self.get(i)?;
+        let total = self.log_weight();
+        (total > f64::NEG_INFINITY).then(|| (lw - total).exp())
+    }
+
+    /// Sets the offset to the largest log-weight, and recomputes the weights
+    /// of all the elements relative to it
+    fn rebase(&mut self) {
+        let max = self
+            .log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        self.offset = if max.is_finite() { max } else { 0.0 };
+        // Weights relative to the largest one are at most 1, so they are
+        // always valid
+        let (log_weights, offset) = (&self.log_weights, self.offset);
+        self.urn.reweight_all(|i| (log_weights[i] - offset).exp());
+    }
+
+    /// Rescales the weights if the log-weight `lw` (which was just inserted)
+    /// is too large, or the total weight has become too small, compared to
+    /// the offset
+    fn rebase_if_needed(&mut self, lw: f64) {
+        let too_large = lw > self.offset + REBASE_RANGE;
+        let too_small =
+            self.finite > 0 && self.urn.weight() < (-REBASE_RANGE).exp();
+        if too_large || too_small {
+            self.rebase();
+        }
+    }
+
+    /// Appends element `a` with log-weight `lw`, returning its index
+    /// (or an error if `lw` is NaN or `+inf`)
+    pub fn push(&mut self, lw: f64, a: T) -> Result<usize, FloatWeightError> {
+        let lw = validate_log(lw)?;
+        // The weight may overflow to `+inf` until the weights are rescaled
+        let w = (lw - self.offset).exp().min(f64::MAX);
+        let i = self.urn.push(w, a)?;
+        self.log_weights.push(lw);
+        self.finite += usize::from(lw.is_finite());
+        self.rebase_if_needed(lw);
+        Ok(i)
+    }
+
+    /// Sets the log-weight of the element at index `i` to `lw`, returning its
+    /// old log-weight (or an error if `lw` is NaN or `+inf`).
+    /// Panics if `i` is out of bounds.
+    pub fn set_log_weight(
+        &mut self,
+        i: usize,
+        lw: f64,
+    ) -> Result<f64, FloatWeightError> {
+        let lw = validate_log(lw)?;
+        let w = (lw - self.offset).exp().min(f64::MAX);
+        self.urn.set_weight(i, w)?;
+        let lw_old = std::mem::replace(&mut self.log_weights[i], lw);
+        self.finite -= usize::from(lw_old.is_finite());
+        self.finite += usize::from(lw.is_finite());
+        self.rebase_if_needed(lw);
+        Ok(lw_old)
+    }
+
+    /// Removes the element at index `i`, returning it along with its
+    /// log-weight.
+    /// The last element is moved into index `i` (as in `Vec::swap_remove`).
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> (f64, T) {
+        let (_, a) = self.urn.swap_remove(i);
+        let lw = self.log_weights.swap_remove(i);
+        self.finite -= usize::from(lw.is_finite());
+        self.rebase_if_needed(f64::NEG_INFINITY);
+        (lw, a)
+    }
+
+    /// Randomly samples an element using `rng`, returning its index along
+    /// with (a reference to) the element.
+    /// Elements with log-weight `-inf` are never sampled (unless every
+    /// element has log-weight `-inf`, in which case the first element
+    /// is returned).
+    /// Panics if the sampler is empty.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> (usize, &T) {
+        self.urn.sample(rng)
+    }
+
+    /// Samples up to `k` distinct elements without replacement using `rng`
+    /// (the Gumbel-top-k trick), returning their indexes along with
+    /// (references to) the elements, in the order in which they were drawn.
+    ///
+    /// Each log-weight is perturbed with independent Gumbel noise, and the `k`
+    /// elements with the largest perturbed log-weights are returned. This has
+    /// the same distribution as drawing `k` times in a row, removing the drawn
+    /// element after each draw, but works directly on the log-weights (so it
+    /// is exact even for weights too small to be represented relative to the
+    /// largest one).
+    /// Fewer than `k` elements are returned if there are fewer than `k`
+    /// elements with log-weight other than `-inf`.
+    /// Time complexity: `O(n + k log k)`.
+    pub fn sample_k_distinct_gumbel<R: RngCore + ?Sized>(
+        &self,
+        k: usize,
+        rng: &mut R,
+    ) -> Vec<(usize, &T)> {
+        let mut keys: Vec<(f64, usize)> = self
+            .log_weights
+            .iter()
+            .enumerate()
+            .filter(|(_, lw)| lw.is_finite())
+            .map(|(i, lw)| {
+                let u = open01_f64(rng);
+                (lw - (-u.ln()).ln(), i)
+            })
+            .collect();
+        let by_key_desc =
+            |a: &(f64, usize), b: &(f64, usize)| b.0.total_cmp(&a.0);
+        if k < keys.len() {
+            keys.select_nth_unstable_by(k, by_key_desc);
+            keys.truncate(k);
+        }
+        keys.sort_unstable_by(by_key_desc);
+        keys.into_iter()
+            .map(|(_, i)| (i, self.urn.get(i).unwrap().1))
+            .collect()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn log_weight_urn_example() {
+        // Weights e^1000, e^1000 and e^-1000 don't fit in an `f64`
+        let elems = vec![(1000.0, 'a'), (1000.0, 'b'), (-1000.0, 'c')];
+        let mut urn = LogWeightUrn::from_log_weights(elems).unwrap();
+        assert!((urn.log_weight() - (1000.0 + 2f64.ln())).abs() < 1e-9);
+        assert!((urn.probability(0).unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(urn.probability(2), Some(0.0));
+
+        // Removing the heavy elements rescales the remaining one
+        assert_eq!(urn.swap_remove(0), (1000.0, 'a'));
+        assert_eq!(urn.swap_remove(0), (-1000.0, 'c'));
+        assert_eq!(urn.set_log_weight(0, -2000.0), Ok(1000.0));
+        assert!((urn.probability(0).unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(urn.push(f64::NEG_INFINITY, 'd'), Ok(1));
+        assert_eq!(urn.push(f64::NAN, 'e'), Err(FloatWeightError::NaN));
+        assert_eq!(urn.push(-1999.0, 'f'), Ok(2));
+        let expected = -1999.0 + (-1f64).exp().ln_1p();
+        assert!((urn.log_weight() - expected).abs() < 1e-9);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_ne!(urn.sample(&mut rng).1, &'d');
+        }
+    }
+
+    #[test]
+    fn gumbel_top_k_draws_distinct_elements() {
+        let elems = vec![
+            (0.0, 'a'),
+            (f64::NEG_INFINITY, 'b'),
+            (-500.0, 'c'),
+            (2.0, 'd'),
+        ];
+        let urn = LogWeightUrn::from_log_weights(elems).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let drawn = urn.sample_k_distinct_gumbel(10, &mut rng);
+        let mut indexes: Vec<usize> = drawn.iter().map(|(i, _)| *i).collect();
+        indexes.sort();
+        assert_eq!(indexes, vec![0, 2, 3]);
+        assert_eq!(urn.sample_k_distinct_gumbel(0, &mut rng), vec![]);
+
+        // The heaviest element is almost always drawn first
+        let firsts = (0..1000)
+            .filter(|_| urn.sample_k_distinct_gumbel(1, &mut rng)[0].0 == 3)
+            .count();
+        assert!(firsts > 800);
+    }
+}
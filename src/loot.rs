@@ -0,0 +1,169 @@
+use std::borrow::Cow;
+
+use rand_core::RngCore;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::singleton,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                                 Loot tables                                */
+/* -------------------------------------------------------------------------- */
+
+/// A loot table, i.e. a policy layer over an urn for drawing several drops
+/// at once. Each entry is either
+/// - *guaranteed*: dropped by every roll,
+/// - *weighted*: drawn with probability proportional to its weight, and may
+///   be dropped several times by the same roll, or
+/// - *unique*: drawn like a weighted entry, but dropped at most once per roll.
+///
+/// The weighted and unique entries share a single urn, so they compete with
+/// each other for every drop.
+#[derive(Debug, Clone)]
+pub struct LootTable<T: Clone> {
+    guaranteed: Vec<T>,
+    /// The weighted and unique entries, tagged with whether they are unique
+    pool: Option<Urn<(bool, T)>>,
+}
+
+impl<T: Clone> Default for LootTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> LootTable<T> {
+    /// Creates an empty loot table
+    pub fn new() -> Self {
+        Self {
+            guaranteed: Vec::new(),
+            pool: None,
+        }
+    }
+
+    /// Adds a guaranteed entry `a`, which is dropped by every roll
+    /// (see `roll`)
+    pub fn guaranteed(&mut self, a: T) -> &mut Self {
+        self.guaranteed.push(a);
+        self
+    }
+
+    /// Adds a weighted entry `a` with weight `w`
+    /// (which may be dropped several times per roll).
+    /// Time complexity: `O(log n)`.
+    pub fn weighted(&mut self, w: Weight, a: T) -> &mut Self {
+        self.add_to_pool(w, false, a)
+    }
+
+    /// Adds a unique entry `a` with weight `w`
+    /// (which is dropped at most once per roll).
+    /// Time complexity: `O(log n)`.
+    pub fn unique(&mut self, w: Weight, a: T) -> &mut Self {
+        self.add_to_pool(w, true, a)
+    }
+
+    /// Adds entry `a` with weight `w` to the urn of weighted and unique entries
+    fn add_to_pool(&mut self, w: Weight, unique: bool, a: T) -> &mut Self {
+        self.pool = Some(match self.pool.take() {
+            None => singleton(w, (unique, a)),
+            Some(pool) => pool.insert(w, (unique, a)),
+        });
+        self
+    }
+
+    /// The no. of entries (of every kind)
+    pub fn len(&self) -> usize {
+        self.guaranteed.len()
+            + self.pool.as_ref().map_or(0, |p| p.size() as usize)
+    }
+
+    /// Whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rolls the table using `rng`, returning `n` drops: first the guaranteed
+    /// entries (in the order they were added, and only the first `n` of them
+    /// if there are more than `n`), followed by entries drawn from the
+    /// weighted and unique entries.
+    /// Fewer than `n` drops are returned if the remaining entries all have
+    /// weight 0 (e.g. once every unique entry has been dropped), since
+    /// entries of weight 0 are never drawn.
+    /// Time complexity: `O(n log m)`, where `m` is the no. of weighted and
+    /// unique entries, plus `O(m)` if a unique entry is dropped
+    /// (to copy the urn, which is then shrunk).
+    pub fn roll<R: RngCore + ?Sized>(&self, rng: &mut R, n: usize) -> Vec<T> {
+        let mut drops: Vec<T> =
+            self.guaranteed.iter().take(n).cloned().collect();
+        // The pool is only copied once a unique entry has to be removed
+        let mut pool: Option<Cow<'_, Urn<(bool, T)>>> =
+            self.pool.as_ref().map(Cow::Borrowed);
+        while drops.len() < n {
+            let Some(urn) = pool.take() else { break };
+            if urn.weight() == 0 {
+                break;
+            }
+            let i = urn.draw_index(rng);
+            let (unique, a) = urn.sample_index_ref(i);
+            drops.push(a.clone());
+            pool = if *unique {
+                urn.into_owned().remove_index(i).1.map(Cow::Owned)
+            } else {
+                Some(urn)
+            };
+        }
+        drops
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn roll_honors_entry_kinds() {
+        let mut table = LootTable::new();
+        table
+            .guaranteed("gold")
+            .unique(5, "crown")
+            .unique(5, "sceptre")
+            .weighted(1, "arrow")
+            .weighted(0, "rock");
+        assert_eq!(table.len(), 5);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let drops = table.roll(&mut rng, 6);
+            assert_eq!(drops.len(), 6);
+            assert_eq!(drops[0], "gold");
+            for unique in ["crown", "sceptre"] {
+                assert!(drops.iter().filter(|&&a| a == unique).count() <= 1);
+            }
+            assert!(!drops.contains(&"rock"));
+        }
+        assert_eq!(table.roll(&mut rng, 0), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn roll_stops_when_pool_is_exhausted() {
+        let mut table = LootTable::new();
+        table
+            .guaranteed('a')
+            .guaranteed('b')
+            .unique(1, 'c')
+            .unique(0, 'd');
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(table.roll(&mut rng, 1), vec!['a']);
+        assert_eq!(table.roll(&mut rng, 10), vec!['a', 'b', 'c']);
+        assert_eq!(
+            LootTable::<char>::new().roll(&mut rng, 3),
+            Vec::<char>::new()
+        );
+    }
+}
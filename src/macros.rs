@@ -0,0 +1,61 @@
+/* -------------------------------------------------------------------------- */
+/*                                   Macros                                   */
+/* -------------------------------------------------------------------------- */
+
+/// Builds an urn from a literal list of `weight => element` pairs,
+/// e.g. `urn![3 => "red", 1 => "blue", 6 => "green"]`.
+/// This expands to a call to `from_list`, and evaluates to an `Urn`
+/// (not an `Option`), since an empty `urn![]` is a compile-time error.
+#[macro_export]
+macro_rules! urn {
+    () => {
+        compile_error!("`urn!` needs at least one `weight => element` pair")
+    };
+    ($($w:expr => $a:expr),+ $(,)?) => {
+        $crate::from_list(vec![$(($w, $a)),+])
+            .expect("`urn!` always receives at least one element")
+    };
+}
+
+/// Builds a `StaticUrn` from a literal list of `weight => element` pairs,
+/// e.g. `static_urn![3 => "red", 1 => "blue", 6 => "green"]`.
+/// This expands to a call to the `const fn` `StaticUrn::new`, so it can
+/// initialize `const`s and `static`s (the table is then built at compile
+/// time). An empty `static_urn![]` is a compile-time error.
+#[macro_export]
+macro_rules! static_urn {
+    () => {
+        compile_error!(
+            "`static_urn!` needs at least one `weight => element` pair"
+        )
+    };
+    ($($w:expr => $a:expr),+ $(,)?) => {
+        $crate::StaticUrn::new([$(($w, $a)),+])
+    };
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+
+    #[test]
+    fn urn_macro_matches_from_list() {
+        let urn = crate::urn![3 => "red", 1 => "blue", 6 => "green",];
+        let expected = from_list(vec![(3, "red"), (1, "blue"), (6, "green")]);
+        assert_eq!(Some(urn), expected);
+        assert_eq!(crate::urn![2 => 'a'].size(), 1);
+    }
+
+    #[test]
+    fn static_urn_macro_matches_new() {
+        static URN: crate::StaticUrn<&str, 3> =
+            crate::static_urn![3 => "red", 1 => "blue", 6 => "green"];
+        let expected =
+            crate::StaticUrn::new([(3, "red"), (1, "blue"), (6, "green")]);
+        assert_eq!(URN, expected);
+    }
+}
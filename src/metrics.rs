@@ -0,0 +1,155 @@
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+#[cfg(feature = "metrics")]
+use crate::types::{Tree, Urn};
+
+/* -------------------------------------------------------------------------- */
+/*                            Operation counters                              */
+/* -------------------------------------------------------------------------- */
+
+// The counters are only maintained with the `metrics` feature enabled.
+// Otherwise, the `record_*` functions below do nothing (and are inlined
+// away), so the counters cost nothing unless they're used.
+
+#[cfg(feature = "metrics")]
+static NODES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static SUBTREES_CLONED: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static SAMPLES_DRAWN: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "metrics")]
+static REBUILDS: AtomicU64 = AtomicU64::new(0);
+
+/// Records the allocation of a `Node` (see `Tree::node`)
+#[inline]
+pub(crate) fn record_node_allocated() {
+    #[cfg(feature = "metrics")]
+    NODES_ALLOCATED.fetch_add(1, Relaxed);
+}
+
+/// Records a sample (or a random index drawn from an urn)
+#[inline]
+pub(crate) fn record_sample_drawn() {
+    #[cfg(feature = "metrics")]
+    SAMPLES_DRAWN.fetch_add(1, Relaxed);
+}
+
+/// Records an urn being (re)built from scratch as an almost perfect tree
+#[inline]
+pub(crate) fn record_rebuild() {
+    #[cfg(feature = "metrics")]
+    REBUILDS.fetch_add(1, Relaxed);
+}
+
+/// A snapshot of the operation counters, returned by `Urn::metrics`
+/// (with the `metrics` feature), e.g. to diagnose why an urn-heavy
+/// workload is slow, or to compare different ways of using urns.
+/// The counters are shared by all urns (of every element type) in the
+/// process, and count operations since the start of the process
+/// (or since the last `Metrics::reset`).
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Metrics {
+    /// The no. of internal nodes allocated (see `Tree::node`)
+    pub nodes_allocated: u64,
+    /// The no. of subtrees (or whole trees) cloned, e.g. by cloning an urn
+    /// or by the borrowing methods `replace` and `adjust`
+    pub subtrees_cloned: u64,
+    /// The no. of random indexes drawn from urns (by sampling, or by
+    /// methods which remove or update random elements), plus the no. of
+    /// samples from singleton urns (which don't need to draw an index)
+    pub samples_drawn: u64,
+    /// The no. of trees built from scratch as almost perfect trees
+    /// (e.g. by `from_list`, or when `insert_many` rebuilds an urn)
+    pub rebuilds: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    /// Resets all the counters to 0
+    pub fn reset() {
+        for counter in [
+            &NODES_ALLOCATED,
+            &SUBTREES_CLONED,
+            &SAMPLES_DRAWN,
+            &REBUILDS,
+        ] {
+            counter.store(0, Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<T: Clone> Urn<T> {
+    /// A snapshot of the operation counters (see `Metrics`)
+    pub fn metrics() -> Metrics {
+        Metrics {
+            nodes_allocated: NODES_ALLOCATED.load(Relaxed),
+            subtrees_cloned: SUBTREES_CLONED.load(Relaxed),
+            samples_drawn: SAMPLES_DRAWN.load(Relaxed),
+            rebuilds: REBUILDS.load(Relaxed),
+        }
+    }
+}
+
+/// Cloning a tree is counted as one subtree clone
+/// (however many nodes the tree has)
+#[cfg(feature = "metrics")]
+impl<T: Clone> Clone for Tree<T> {
+    fn clone(&self) -> Self {
+        SUBTREES_CLONED.fetch_add(1, Relaxed);
+        clone_uncounted(self)
+    }
+}
+
+/// Deeply clones a tree without recording the clones of its subtrees
+#[cfg(feature = "metrics")]
+fn clone_uncounted<T: Clone>(tree: &Tree<T>) -> Tree<T> {
+    match tree {
+        Tree::Leaf(w, a) => Tree::Leaf(*w, a.clone()),
+        Tree::Node(w, m, k, l, r) => Tree::Node(
+            *w,
+            *m,
+            *k,
+            Box::new(clone_uncounted(l)),
+            Box::new(clone_uncounted(r)),
+        ),
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::{
+        types::Urn,
+        urn::{from_list, singleton},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // Other tests run concurrently and share the counters,
+    // so we can only check lower bounds on how much they grow
+    #[test]
+    fn metrics_count_operations() {
+        let before = Urn::<char>::metrics();
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let urn = urn.insert(1, 'Y');
+        let _copy = urn.clone();
+        let mut rng = StdRng::seed_from_u64(12);
+        for _ in 0..5 {
+            urn.sample_ref(&mut rng);
+        }
+        singleton(1, 'P').sample_ref(&mut rng);
+        let after = Urn::<char>::metrics();
+
+        // `from_list` allocates 2 nodes, and `insert` rebuilds a path of 2
+        assert!(after.nodes_allocated >= before.nodes_allocated + 4);
+        assert!(after.subtrees_cloned > before.subtrees_cloned);
+        assert!(after.samples_drawn >= before.samples_drawn + 6);
+        assert!(after.rebuilds > before.rebuilds);
+    }
+}
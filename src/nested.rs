@@ -0,0 +1,212 @@
+use std::{collections::HashMap, hash::Hash};
+
+use rand_core::RngCore;
+
+use crate::{
+    furn::FUrn,
+    types::{Index, Urn, Weight},
+    urn::singleton,
+};
+
+/// A two-level urn, which groups elements into categories with keys of type
+/// `K` (e.g. the rarity tiers of a loot table, or the components of a
+/// mixture). Each category is an `Urn`, and is weighted by its total weight,
+/// so `sample_nested` (which samples a category, then an element of it)
+/// samples every element with probability proportional to its weight.
+///
+/// The categories' weights are kept in sync with their urns: every update
+/// goes through `insert`, `insert_category`, `update_category` or
+/// `remove_category`, which recompute the weight of the affected category.
+/// The categories are stored in an `FUrn`, so sampling a category is exact
+/// as long as the total weight is less than `2^53`.
+///
+/// Time complexity: `insert`, `update_category` (excluding the time taken by
+/// the callback) and `sample_nested` all take `O(log c + log n)` (expected,
+/// due to hashing), where `c` is the no. of categories and `n` the size of
+/// the category's urn.
+#[derive(Debug, Clone)]
+pub struct NestedUrn<K, T: Clone> {
+    /// The keys of the categories, weighted by the total weights
+    /// of their urns
+    categories: FUrn<K>,
+    /// `urns[i]` is the urn of the category at index `i` of `categories`
+    urns: Vec<Urn<T>>,
+    /// The index in `categories` of each key
+    positions: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> Default for NestedUrn<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> NestedUrn<K, T> {
+    /// Creates a `NestedUrn` with no categories
+    pub fn new() -> Self {
+        Self {
+            categories: FUrn::new(),
+            urns: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// The no. of categories
+    pub fn len(&self) -> usize {
+        self.urns.len()
+    }
+
+    /// Whether there are no categories
+    pub fn is_empty(&self) -> bool {
+        self.urns.is_empty()
+    }
+
+    /// The total weight of all the elements of all the categories.
+    /// Time complexity: `O(c)`.
+    pub fn weight(&self) -> Index {
+        self.urns
+            .iter()
+            .fold(0, |total: Index, urn| total.wrapping_add(urn.weight()))
+    }
+
+    /// The urn of the category with key `key`
+    pub fn category(&self, key: &K) -> Option<&Urn<T>> {
+        self.positions.get(key).map(|&i| &self.urns[i])
+    }
+
+    /// Sets the weight of the category at index `i` to the total weight
+    /// of its urn
+    fn sync(&mut self, i: usize) {
+        // Total weights are finite and non-negative
+        let _ = self.categories.set_weight(i, self.urns[i].weight() as f64);
+    }
+
+    /// Inserts (or replaces) the category with key `key`, returning the
+    /// category's previous urn (if any)
+    pub fn insert_category(&mut self, key: K, urn: Urn<T>) -> Option<Urn<T>> {
+        match self.positions.get(&key) {
+            Some(&i) => {
+                let old = std::mem::replace(&mut self.urns[i], urn);
+                self.sync(i);
+                Some(old)
+            }
+            None => {
+                let w = urn.weight() as f64;
+                // Total weights are finite and non-negative
+                let i = self.categories.push(w, key.clone()).unwrap();
+                self.urns.push(urn);
+                self.positions.insert(key, i);
+                None
+            }
+        }
+    }
+
+    /// Inserts element `a` with weight `w` into the category with key `key`
+    /// (creating the category if it doesn't exist)
+    pub fn insert(&mut self, key: K, w: Weight, a: T) {
+        if self.positions.contains_key(&key) {
+            self.update_category(&key, |urn| Some(urn.insert(w, a)));
+        } else {
+            self.insert_category(key, singleton(w, a));
+        }
+    }
+
+    /// Removes the category with key `key`, returning its urn
+    pub fn remove_category(&mut self, key: &K) -> Option<Urn<T>> {
+        let i = self.positions.remove(key)?;
+        // The last category moves into index `i`
+        self.categories.swap_remove(i);
+        let urn = self.urns.swap_remove(i);
+        if let Some((_, moved)) = self.categories.get(i) {
+            self.positions.insert(moved.clone(), i);
+        }
+        Some(urn)
+    }
+
+    /// Replaces the urn of the category with key `key` by `f(urn)`, and
+    /// updates the category's weight accordingly. If `f` returns `None`,
+    /// the category is removed.
+    /// Returns whether the category exists (if it doesn't, `f` isn't called).
+    pub fn update_category<F>(&mut self, key: &K, f: F) -> bool
+    where
+        F: FnOnce(Urn<T>) -> Option<Urn<T>>,
+    {
+        let Some(urn) = self.remove_category(key) else {
+            return false;
+        };
+        if let Some(urn) = f(urn) {
+            self.insert_category(key.clone(), urn);
+        }
+        true
+    }
+
+    /// Randomly samples a category (with probability proportional to its total
+    /// weight) and then an element of it using `rng`, returning (references
+    /// to) the category's key and the element (or `None` if there are
+    /// no categories)
+    pub fn sample_nested<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<(&K, &T)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (i, key) = self.categories.sample(rng);
+        Some((key, self.urns[i].sample_ref(rng)))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn nested_urn_example() {
+        let mut loot = NestedUrn::new();
+        let common = from_list(vec![(5, "stick"), (3, "stone")]).unwrap();
+        assert_eq!(loot.insert_category("common", common), None);
+        loot.insert("rare", 1, "sword");
+        loot.insert("rare", 1, "shield");
+        assert_eq!(loot.len(), 2);
+        assert_eq!(loot.weight(), 10);
+
+        // Emptying a category removes it
+        assert!(loot.update_category(&"common", |urn| urn.remove_position(0).1));
+        assert!(loot.update_category(&"common", |urn| urn.remove_position(0).1));
+        assert_eq!(loot.category(&"common"), None);
+        assert!(!loot.update_category(&"common", Some));
+        assert_eq!(loot.weight(), 2);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let (key, item) = loot.sample_nested(&mut rng).unwrap();
+            assert_eq!(key, &"rare");
+            assert!(["sword", "shield"].contains(item));
+        }
+        assert_eq!(loot.remove_category(&"rare").map(|u| u.size()), Some(2));
+        assert!(loot.is_empty());
+        assert_eq!(loot.sample_nested(&mut rng), None);
+    }
+
+    #[test]
+    fn category_weights_stay_in_sync() {
+        let mut urn = NestedUrn::new();
+        urn.insert('a', 1, 0);
+        urn.insert('b', 0, 1);
+        urn.insert('b', 0, 2);
+        urn.update_category(&'a', |u| Some(u.insert(0, 3)));
+        urn.update_category(&'a', |u| u.remove_position(0).1);
+        // Every remaining element has weight 0
+        urn.insert('c', 4, 4);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            assert_eq!(urn.sample_nested(&mut rng), Some((&'c', &4)));
+        }
+    }
+}
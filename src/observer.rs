@@ -0,0 +1,176 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{Index, Urn, Weight},
+    urn::singleton,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                               Observer hooks                               */
+/* -------------------------------------------------------------------------- */
+
+/// Callbacks invoked by an `ObservedUrn` on each insertion, removal and
+/// sample, with the weight and element involved (e.g. to maintain a reverse
+/// map or metrics counters in lockstep with the urn).
+/// Every method does nothing by default, so observers only need to
+/// implement the events they care about.
+pub trait UrnObserver<T> {
+    /// Called when element `a` is inserted with weight `w`
+    /// (just before it is moved into the urn)
+    fn on_insert(&mut self, _w: Weight, _a: &T) {}
+
+    /// Called after element `a` with weight `w` is removed
+    fn on_remove(&mut self, _w: Weight, _a: &T) {}
+
+    /// Called after element `a` with weight `w` is sampled
+    fn on_sample(&mut self, _w: Weight, _a: &T) {}
+}
+
+/// The trivial observer, which ignores every event
+impl<T> UrnObserver<T> for () {}
+
+/// An urn which notifies its observer `O` (see `UrnObserver`) of every
+/// insertion, removal and sample.
+///
+/// Time complexity: every operation takes `O(log n)`, plus the time taken
+/// by the observer.
+#[derive(Debug, Clone)]
+pub struct ObservedUrn<T: Clone, O> {
+    urn: Option<Urn<T>>,
+    observer: O,
+}
+
+impl<T: Clone, O: UrnObserver<T>> ObservedUrn<T, O> {
+    /// Creates an empty `ObservedUrn` notifying `observer`
+    pub fn new(observer: O) -> Self {
+        Self {
+            urn: None,
+            observer,
+        }
+    }
+
+    /// Wraps `urn`, notifying `observer` of subsequent events.
+    /// The observer isn't notified of the urn's existing elements.
+    pub fn with_urn(urn: Urn<T>, observer: O) -> Self {
+        Self {
+            urn: Some(urn),
+            observer,
+        }
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.urn.as_ref()
+    }
+
+    /// The observer
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// The observer, mutably
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Consumes the `ObservedUrn`, returning the underlying urn
+    /// and the observer
+    pub fn into_parts(self) -> (Option<Urn<T>>, O) {
+        (self.urn, self.observer)
+    }
+
+    /// Calls `on_insert`, then inserts element `a` with weight `w`
+    /// (see `Urn::insert`)
+    pub fn insert(&mut self, w: Weight, a: T) {
+        self.observer.on_insert(w, &a);
+        self.urn = Some(match self.urn.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        });
+    }
+
+    /// Removes the element at index `i` (see `Urn::remove_index`), then
+    /// calls `on_remove`, returning the element along with its weight
+    /// (or `None` if there are no elements)
+    pub fn remove_index(&mut self, i: Index) -> Option<(Weight, T)> {
+        let ((w, a), urn_opt) = self.urn.take()?.remove_index(i);
+        self.urn = urn_opt;
+        self.observer.on_remove(w, &a);
+        Some((w, a))
+    }
+
+    /// Removes the element at (leaf) position `pos` (see
+    /// `Urn::remove_position`), then calls `on_remove`, returning the element
+    /// along with its weight (or `None` if `pos` is out of bounds)
+    pub fn remove_position(&mut self, pos: u32) -> Option<(Weight, T)> {
+        if pos >= self.urn.as_ref()?.size() {
+            return None;
+        }
+        let ((w, a), urn_opt) = self.urn.take()?.remove_position(pos);
+        self.urn = urn_opt;
+        self.observer.on_remove(w, &a);
+        Some((w, a))
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`, then calls
+    /// `on_sample` (or returns `None` if there are no elements)
+    pub fn sample<R: RngCore + ?Sized>(&mut self, rng: &mut R) -> Option<&T> {
+        let urn = self.urn.as_ref()?;
+        let (_, w, a) = urn.sample_with_position(rng);
+        self.observer.on_sample(w, a);
+        Some(a)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::widen;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashMap;
+
+    /// Counts the samples of each element, and tracks the total weight
+    #[derive(Default)]
+    struct Counter {
+        samples: HashMap<char, u32>,
+        weight: Index,
+    }
+
+    impl UrnObserver<char> for Counter {
+        fn on_insert(&mut self, w: Weight, _: &char) {
+            self.weight += widen(w);
+        }
+
+        fn on_remove(&mut self, w: Weight, _: &char) {
+            self.weight -= widen(w);
+        }
+
+        fn on_sample(&mut self, _: Weight, a: &char) {
+            *self.samples.entry(*a).or_default() += 1;
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_event() {
+        let mut urn = ObservedUrn::new(Counter::default());
+        for (w, a) in [(2, 'R'), (0, 'G'), (3, 'B')] {
+            urn.insert(w, a);
+        }
+        assert_eq!(urn.observer().weight, 5);
+        assert_eq!(urn.remove_position(0), Some((2, 'R')));
+        assert_eq!(urn.remove_position(5), None);
+        assert_eq!(urn.observer().weight, 3);
+
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..10 {
+            assert_eq!(urn.sample(&mut rng), Some(&'B'));
+        }
+        let (rest, counter) = urn.into_parts();
+        assert_eq!(rest.map(|u| u.weight()), Some(counter.weight));
+        assert_eq!(counter.samples, HashMap::from([('B', 10)]));
+    }
+}
@@ -0,0 +1,70 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::types::Urn;
+
+/* -------------------------------------------------------------------------- */
+/*                          Parallel batch sampling                           */
+/* -------------------------------------------------------------------------- */
+
+/// The no. of samples drawn by each parallel task of `Urn::sample_n_par`
+const SAMPLES_PER_CHUNK: usize = 4096;
+
+/// The RNG used for the `chunk`-th chunk of samples drawn by
+/// `Urn::sample_n_par` with the given `seed`
+fn chunk_rng(seed: u64, chunk: u64) -> StdRng {
+    let mut bytes = [0; 32];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    bytes[8..16].copy_from_slice(&chunk.to_le_bytes());
+    StdRng::from_seed(bytes)
+}
+
+impl<T: Clone + Send + Sync> Urn<T> {
+    /// Draws `n` independent samples (with replacement) in parallel using
+    /// `rayon`, returning them in a `Vec`.
+    ///
+    /// The samples are split into fixed-size chunks, and the `i`-th chunk is
+    /// drawn using its own RNG, seeded with both `seed` and `i`. Since the
+    /// chunks don't depend on how they are scheduled, the output is
+    /// deterministic for a fixed `seed` (regardless of the no. of threads),
+    /// as long as the version of `rand` (whose `StdRng` algorithm may change
+    /// between versions) stays the same.
+    /// Time complexity: `O(n log size)` work, spread over all threads.
+    pub fn sample_n_par(&self, n: usize, seed: u64) -> Vec<T> {
+        let chunks = n.div_ceil(SAMPLES_PER_CHUNK);
+        (0..chunks)
+            .into_par_iter()
+            .flat_map_iter(|chunk| {
+                let mut rng = chunk_rng(seed, chunk as u64);
+                let len = SAMPLES_PER_CHUNK.min(n - chunk * SAMPLES_PER_CHUNK);
+                (0..len).map(move |_| self.sample_ref(&mut rng).clone())
+            })
+            .collect()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+
+    #[test]
+    fn sample_n_par_is_deterministic() {
+        let urn = from_list(vec![(1, 'a'), (0, 'b'), (3, 'c')]).unwrap();
+        let n = 10_000;
+        let samples = urn.sample_n_par(n, 42);
+        assert_eq!(samples.len(), n);
+        assert!(!samples.contains(&'b'));
+        assert_eq!(urn.sample_n_par(n, 42), samples);
+        assert_ne!(urn.sample_n_par(n, 43), samples);
+        assert!(urn.sample_n_par(0, 42).is_empty());
+
+        // The output doesn't depend on the no. of threads
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build();
+        let sequential = pool.unwrap().install(|| urn.sample_n_par(n, 42));
+        assert_eq!(sequential, samples);
+    }
+}
@@ -0,0 +1,67 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::from_iter_exact,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                     Sampling positions (index-only urns)                   */
+/* -------------------------------------------------------------------------- */
+
+impl Urn<()> {
+    /// Builds an *index-only* urn from a slice of weights, for when the
+    /// values being sampled live elsewhere (e.g. in an array owned by the
+    /// caller) and only their indexes are needed. The urn's elements are
+    /// all `()`, so no values are cloned or stored: the `i`-th leaf has
+    /// weight `weights[i]`, and `sample_position` returns indexes into
+    /// `weights`.
+    /// (This correspondence holds as long as the urn is only reweighted in
+    /// place, e.g. via `weight_mut`. `insert` and `remove` may move leaves.)
+    /// Returns `None` if `weights` is empty.
+    /// Time complexity: `O(n)`.
+    pub fn index_only_from_weights(weights: &[Weight]) -> Option<Self> {
+        from_iter_exact(weights.iter().map(|&w| (w, ())))
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Randomly samples an element using `rng`, returning its (leaf)
+    /// position rather than the element itself.
+    /// (Singleton urns are sampled without drawing from `rng`.)
+    /// Time complexity: `O(log n)`.
+    pub fn sample_position<R: RngCore + ?Sized>(&self, rng: &mut R) -> u32 {
+        if self.size() == 1 {
+            return 0;
+        }
+        self.position_of_index(self.draw_index(rng))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Urn;
+    use rand::thread_rng;
+
+    #[test]
+    fn index_only_positions_match_slice() {
+        let weights = [0, 3, 0, 1, 0];
+        let mut urn = Urn::index_only_from_weights(&weights).unwrap();
+        assert_eq!(urn.size(), 5);
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let pos = urn.sample_position(&mut rng);
+            assert!(weights[pos as usize] > 0);
+        }
+
+        *urn.weight_mut(1) = 0;
+        for _ in 0..100 {
+            assert_eq!(urn.sample_position(&mut rng), 3);
+        }
+        assert!(Urn::index_only_from_weights(&[]).is_none());
+    }
+}
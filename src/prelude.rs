@@ -0,0 +1,9 @@
+//! The most commonly used items of the crate, for glob-importing:
+//! `use urn::prelude::*;`
+
+pub use crate::sampler::WeightedSampler;
+pub use crate::slice::UrnSliceExt;
+pub use crate::types::{Index, IndexDraw, Urn, Weight, ZeroWeightPolicy};
+// The `urn!` macro (the `urn` module itself is private)
+pub use crate::urn;
+pub use crate::urn::{from_iter_exact, from_list, singleton};
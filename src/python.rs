@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use pyo3::{exceptions::PyIndexError, prelude::*};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    types::{Index, Urn, Weight},
+    urn::{from_list, singleton},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                              PyO3 bindings                                 */
+/* -------------------------------------------------------------------------- */
+
+/// An urn of arbitrary Python objects, exported to Python as `urn.Urn`.
+/// Each urn owns its RNG, which can be seeded (by passing `seed` to the
+/// constructor or `from_list`) to make sampling reproducible.
+///
+/// The objects are stored behind `Arc`s, so that the persistent urn can share
+/// them between versions without touching their reference counts (which
+/// requires holding the GIL). Sampling returns the stored object itself,
+/// not a copy.
+#[pyclass(name = "Urn", module = "urn")]
+pub struct PyUrn {
+    urn: Option<Urn<Arc<Py<PyAny>>>>,
+    rng: StdRng,
+}
+
+/// Seeds an RNG with `seed`, or from the OS's entropy source if it is `None`
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+}
+
+/// The error raised when sampling from or removing from an empty urn
+fn empty_urn_error() -> PyErr {
+    PyIndexError::new_err("the urn is empty")
+}
+
+#[pymethods]
+impl PyUrn {
+    /// Creates an empty urn, whose RNG is seeded with `seed` (or from the
+    /// OS's entropy source if `seed` is `None`)
+    #[new]
+    #[pyo3(signature = (seed = None))]
+    fn new(seed: Option<u64>) -> Self {
+        Self {
+            urn: None,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    /// Builds an urn from a list of `(weight, object)` pairs.
+    /// Time complexity: `O(n)`.
+    #[staticmethod]
+    #[pyo3(signature = (elems, seed = None))]
+    fn from_list(elems: Vec<(Weight, Py<PyAny>)>, seed: Option<u64>) -> Self {
+        let elems = elems.into_iter().map(|(w, a)| (w, Arc::new(a))).collect();
+        Self {
+            urn: from_list(elems),
+            rng: seeded_rng(seed),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.urn.as_ref().map_or(0, |urn| urn.size() as usize)
+    }
+
+    /// The total weight of all the objects
+    #[getter]
+    fn weight(&self) -> Index {
+        self.urn.as_ref().map_or(0, |urn| urn.weight())
+    }
+
+    /// Inserts `obj` with weight `weight`.
+    /// Time complexity: `O(log n)`.
+    fn insert(&mut self, weight: Weight, obj: Py<PyAny>) {
+        let obj = Arc::new(obj);
+        self.urn = Some(match self.urn.take() {
+            None => singleton(weight, obj),
+            Some(urn) => urn.insert(weight, obj),
+        });
+    }
+
+    /// Randomly samples an object (without removing it).
+    /// Raises `IndexError` if the urn is empty.
+    /// Time complexity: `O(log n)`.
+    fn sample(&mut self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let urn = self.urn.as_ref().ok_or_else(empty_urn_error)?;
+        Ok(urn.sample_ref(&mut self.rng).clone_ref(py))
+    }
+
+    /// Randomly removes an object, returning it along with its weight.
+    /// Raises `IndexError` if the urn is empty.
+    /// Time complexity: `O(log n)`.
+    fn remove(&mut self, py: Python<'_>) -> PyResult<(Weight, Py<PyAny>)> {
+        let urn = self.urn.take().ok_or_else(empty_urn_error)?;
+        let i = urn.draw_index(&mut self.rng);
+        let ((w, a), rest) = urn.remove_index(i);
+        self.urn = rest;
+        Ok((w, a.clone_ref(py)))
+    }
+
+    /// Randomly samples an object `a` with weight `w`, and replaces it by
+    /// `(w_new, a_new) = f(w, a)`, returning `((w, a), (w_new, a_new))`.
+    /// If `f` raises an exception (or doesn't return a `(weight, object)`
+    /// pair), the urn is left unchanged.
+    /// Time complexity: `O(log n)`, plus the time taken by `f`.
+    #[allow(clippy::type_complexity)]
+    fn update(
+        &mut self,
+        py: Python<'_>,
+        f: &Bound<'_, PyAny>,
+    ) -> PyResult<((Weight, Py<PyAny>), (Weight, Py<PyAny>))> {
+        let urn = self.urn.take().ok_or_else(empty_urn_error)?;
+        let i = urn.draw_index(&mut self.rng);
+        // The sampled object is removed and the new object inserted (rather
+        // than replaced in place), since `f` may fail
+        let ((w, a), rest) = urn.remove_index(i);
+        let result = f
+            .call1((w, a.clone_ref(py)))
+            .and_then(|new| new.extract::<(Weight, Py<PyAny>)>());
+        let (w_new, a_new) = match result {
+            Ok(new) => new,
+            Err(err) => {
+                self.urn = Some(match rest {
+                    None => singleton(w, a),
+                    Some(rest) => rest.insert(w, a),
+                });
+                return Err(err);
+            }
+        };
+        let new = (w_new, a_new.clone_ref(py));
+        let a_new = Arc::new(a_new);
+        self.urn = Some(match rest {
+            None => singleton(w_new, a_new),
+            Some(rest) => rest.insert(w_new, a_new),
+        });
+        Ok(((w, a.clone_ref(py)), new))
+    }
+}
+
+/// The Python module `urn`
+#[pymodule]
+#[pyo3(name = "urn")]
+fn urn_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyUrn>()
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyString;
+
+    #[test]
+    fn python_urn_round_trip() {
+        Python::initialize();
+        Python::attach(|py| {
+            let a = PyString::new(py, "a").into_any().unbind();
+            let b = PyString::new(py, "b").into_any().unbind();
+            let mut urn = PyUrn::from_list(vec![(0, a), (3, b)], Some(0));
+            assert_eq!((urn.__len__(), urn.weight()), (2, 3));
+            let s = urn.sample(py).unwrap();
+            assert_eq!(s.extract::<String>(py).unwrap(), "b");
+
+            // A failing update leaves the urn unchanged
+            let len = py.eval(c"len", None, None).unwrap();
+            assert!(urn.update(py, &len).is_err());
+            assert_eq!((urn.__len__(), urn.weight()), (2, 3));
+            let inc = py.eval(c"lambda w, a: (w + 1, a)", None, None).unwrap();
+            let ((w, _), (w_new, _)) = urn.update(py, &inc).unwrap();
+            assert_eq!((w, w_new, urn.weight()), (3, 4, 4));
+
+            let (w, obj) = urn.remove(py).unwrap();
+            assert_eq!(
+                (w, obj.extract::<String>(py).unwrap()),
+                (4, "b".into())
+            );
+            urn.insert(1, PyString::new(py, "c").into_any().unbind());
+            assert_eq!((urn.__len__(), urn.weight()), (2, 1));
+        });
+    }
+}
@@ -1,4 +1,6 @@
-use crate::types::{Tree, Tree::*, Urn, Weight};
+use crate::fenwick::FenwickUrn;
+use crate::handle::HandleUrn;
+use crate::types::{widen, Index, Tree, Tree::*, Urn, Weight};
 use crate::urn;
 use quickcheck::*;
 
@@ -22,32 +24,55 @@ impl Arbitrary for Urn<char> {
 // Properties are adapted from the Coq lemmas in
 // https://github.com/antalsz/urn-random/blob/master/coq/urn.v
 
+// The checks below recompute the cached data of a tree from scratch,
+// independently of `Urn::validate` (which is itself tested against them)
+
 impl<T: Clone> Tree<T> {
-    /// Counts the no. of nodes in the tree
+    /// Counts the no. of leaves in the tree
     fn tree_count(&self) -> u32 {
         match self {
             Leaf(_, _) => 1,
-            Node(_, l, r) => l.tree_count().wrapping_add(r.tree_count()),
+            Node(_, _, _, l, r) => l.tree_count().wrapping_add(r.tree_count()),
         }
     }
 
     /// Sums the weights at all the leaves
-    fn sum_leaf_weights(&self) -> Weight {
+    fn sum_leaf_weights(&self) -> Index {
         match self {
-            Leaf(w, _) => *w,
-            Node(_, l, r) => {
+            Leaf(w, _) => widen(*w),
+            Node(_, _, _, l, r) => {
                 l.sum_leaf_weights().wrapping_add(r.sum_leaf_weights())
             }
         }
     }
 
+    /// Computes the maximum weight of any leaf
+    fn max_leaf_weight(&self) -> Weight {
+        match self {
+            Leaf(w, _) => *w,
+            Node(_, _, _, l, r) => l.max_leaf_weight().max(r.max_leaf_weight()),
+        }
+    }
+
+    /// Computes the minimum weight of any leaf
+    fn min_leaf_weight(&self) -> Weight {
+        match self {
+            Leaf(w, _) => *w,
+            Node(_, _, _, l, r) => l.min_leaf_weight().min(r.min_leaf_weight()),
+        }
+    }
+
     /// Checks whether the weight at each node matches the sum of
-    /// the subtrees' leaf weights
+    /// the subtrees' leaf weights, and whether the maximum and minimum
+    /// weights cached at each node match the maximum and minimum of the
+    /// subtrees' leaf weights
     fn weights_match(&self) -> bool {
         match self {
             Leaf(_, _) => true,
-            Node(w, l, r) => {
+            Node(w, m, k, l, r) => {
                 *w == l.sum_leaf_weights().wrapping_add(r.sum_leaf_weights())
+                    && *m == l.max_leaf_weight().max(r.max_leaf_weight())
+                    && *k == l.min_leaf_weight().min(r.min_leaf_weight())
                     && l.weights_match()
                     && r.weights_match()
             }
@@ -60,17 +85,25 @@ impl<T: Clone> Urn<T> {
     fn is_wf(&self) -> bool {
         self.tree.tree_count() == self.size() && self.tree.weights_match()
     }
+
+    /// Checks whether the total weight of the urn fits in a `Weight`
+    /// (i.e. whether none of the weights in the tree have wrapped around)
+    fn weight_fits(&self) -> bool {
+        self.headroom().is_some()
+    }
 }
 
 #[cfg(test)]
 mod qc_tests {
     use super::*;
+    use crate::types::{IndexDraw, ZeroWeightPolicy};
     use quickcheck_macros::quickcheck;
+    use rand::SeedableRng;
 
     // Ensure that all urns produced using `from_list` are well-formed
     #[quickcheck]
     fn from_list_produces_wf_urns(elems: Vec<(Weight, char)>) -> bool {
-        urn::from_list(elems).map_or(true, |urn| urn.is_wf())
+        urn::from_list(elems).is_none_or(|urn| urn.is_wf())
     }
 
     // Ensure that `from_list` produces equivalent urns as `from_list_naive`
@@ -86,6 +119,25 @@ mod qc_tests {
         urn.size() == naive_urn.size() && urn.weight() == naive_urn.weight()
     }
 
+    // `from_iter_exact` builds exactly the same tree as `from_list`
+    #[quickcheck]
+    fn from_iter_exact_equals_from_list(elems: Vec<(Weight, char)>) -> bool {
+        urn::from_iter_exact(elems.clone().into_iter()) == urn::from_list(elems)
+    }
+
+    // `validate` accepts exactly the urns whose cached data matches a
+    // recomputation from scratch, including urns whose tree was modified
+    // directly (which may break the urn's invariants)
+    #[quickcheck]
+    fn validate_agrees_with_recomputation(urn: Urn<char>, w: Weight) -> bool {
+        let mut broken = urn.clone();
+        if let Node(total, _, _, _, _) = &mut broken.tree {
+            *total = total.wrapping_add(widen(w));
+        }
+        urn.validate().is_ok() == urn.is_wf()
+            && broken.validate().is_ok() == broken.is_wf()
+    }
+
     #[quickcheck]
     fn insert_preserves_wf(urn: Urn<char>, w: Weight, a: char) -> bool {
         urn.is_wf() && urn.insert(w, a).is_wf()
@@ -99,7 +151,7 @@ mod qc_tests {
     #[quickcheck]
     fn uninsert_preserves_wf(urn: Urn<char>) -> bool {
         let (_, _, new_urn) = urn.clone().uninsert();
-        urn.is_wf() && new_urn.map_or(true, |u| u.is_wf())
+        urn.is_wf() && new_urn.is_none_or(|u| u.is_wf())
     }
 
     #[quickcheck]
@@ -109,24 +161,28 @@ mod qc_tests {
     }
 
     #[quickcheck]
+    #[cfg(feature = "std-rng")]
     fn replace_preserves_wf(urn: Urn<char>, w: Weight, a: char) -> bool {
         let (_, new_urn) = urn.replace(w, &a);
         urn.is_wf() && new_urn.is_wf()
     }
 
     #[quickcheck]
+    #[cfg(feature = "std-rng")]
     fn replace_preserves_size(urn: Urn<char>, w: Weight, a: char) -> bool {
         let (_, new_urn) = urn.replace(w, &a);
         urn.size() == new_urn.size()
     }
 
     #[quickcheck]
+    #[cfg(feature = "std-rng")]
     fn remove_preserves_wf(urn: Urn<char>) -> bool {
         let (_, new_urn) = urn.clone().remove();
-        urn.is_wf() && new_urn.map_or(true, |u| u.is_wf())
+        urn.is_wf() && new_urn.is_none_or(|u| u.is_wf())
     }
 
     #[quickcheck]
+    #[cfg(feature = "std-rng")]
     fn remove_decrements_size(urn: Urn<char>) -> bool {
         let ((_, _), new_urn) = urn.clone().remove();
         urn.size() - 1 == new_urn.map_or(0, |u| u.size())
@@ -139,4 +195,505 @@ mod qc_tests {
         let ((w_new, a_new), _, u_opt) = new_urn.uninsert();
         urn.is_wf() && (w_new, a_new, u_opt) == (w, a, Some(urn))
     }
+
+    // `remove_index` behaves identically to the naive composition of
+    // `uninsert` and `replace_index`
+    #[quickcheck]
+    fn remove_index_equivalent_to_remove_index_naive(
+        urn: Urn<char>,
+        i: Index,
+    ) -> bool {
+        let i = i % urn.weight().max(1);
+        urn.clone().remove_index(i) == urn.remove_index_naive(i)
+    }
+
+    #[quickcheck]
+    fn remove_index_preserves_wf(urn: Urn<char>, i: Index) -> bool {
+        let i = i % urn.weight().max(1);
+        let (_, new_urn) = urn.remove_index(i);
+        new_urn.is_none_or(|u| u.is_wf())
+    }
+
+    #[quickcheck]
+    fn adjust_index_preserves_wf(
+        urn: Urn<char>,
+        delta: i8,
+        i: Index,
+        remove: bool,
+    ) -> bool {
+        let i = i % urn.weight().max(1);
+        let on_zero = if remove {
+            ZeroWeightPolicy::Remove
+        } else {
+            ZeroWeightPolicy::Keep
+        };
+        match urn.adjust_index(i64::from(delta), i, on_zero) {
+            Ok(new_urn) => new_urn.is_none_or(|u| u.is_wf()),
+            Err(_) => true,
+        }
+    }
+
+    // Adjusting by 0 (while keeping zero-weight elements) is the identity
+    #[quickcheck]
+    fn adjust_index_zero_is_identity(urn: Urn<char>, i: Index) -> bool {
+        let i = i % urn.weight().max(1);
+        urn.clone().adjust_index(0, i, ZeroWeightPolicy::Keep) == Ok(Some(urn))
+    }
+
+    // `into_random_order` yields exactly `size` elements
+    #[quickcheck]
+    fn into_random_order_yields_size_elements(urn: Urn<char>) -> bool {
+        let n = urn.size() as usize;
+        let order = urn.into_random_order(rand::thread_rng());
+        order.len() == n && order.count() == n
+    }
+
+    // `from_list` is a left inverse of `to_vec`. In fact, since the shape of
+    // an urn's tree only depends on its size, rebuilding an urn from its
+    // leaves gives back exactly the same tree (not just the same distribution)
+    #[quickcheck]
+    fn from_list_to_vec(urn: Urn<char>, elems: Vec<(Weight, char)>) -> bool {
+        let urn = elems.into_iter().fold(urn, |u, (w, a)| u.insert(w, a));
+        urn::from_list(urn.to_vec()) == Some(urn)
+    }
+
+    #[quickcheck]
+    fn to_vec_has_size_elements(urn: Urn<char>) -> bool {
+        urn.to_vec().len() == urn.size() as usize
+    }
+
+    // `leaves_at_least` agrees with filtering the leaves by weight
+    #[quickcheck]
+    fn leaves_at_least_is_filter(urn: Urn<char>, min_w: Weight) -> bool {
+        let pruned: Vec<_> = urn.tree.leaves_at_least(min_w).collect();
+        let filtered: Vec<_> =
+            urn.iter().filter(|(w, _)| *w >= min_w).collect();
+        pruned == filtered
+    }
+
+    #[quickcheck]
+    fn sample_index_ref_agrees_with_sample_index(
+        urn: Urn<char>,
+        i: Index,
+    ) -> bool {
+        *urn.sample_index_ref(i) == urn.tree.sample_index(i)
+    }
+
+    // The rounded expected counts always sum to the no. of draws
+    #[quickcheck]
+    fn simulate_draw_distribution_sums_to_n(urn: Urn<char>, n: u16) -> bool {
+        let dist = urn.simulate_draw_distribution(u64::from(n));
+        let sum: u64 = dist.counts.iter().map(|c| c.rounded).sum();
+        let total: u128 = urn.iter().map(|(w, _)| u128::from(w)).sum();
+        (total == 0 || sum == u64::from(n)) && dist.max_rounding_error < 1.0
+    }
+
+    // The cumulative weight before each leaf is the sum of the weights
+    // of the leaves to its left
+    #[quickcheck]
+    fn cumulative_weight_before_is_prefix_sum(urn: Urn<char>) -> bool {
+        let mut lb: Index = 0;
+        urn.iter().enumerate().all(|(pos, (w, _))| {
+            let ok = urn.cumulative_weight_before(pos as u32) == lb;
+            lb = lb.wrapping_add(widen(w));
+            ok
+        })
+    }
+
+    // Writing through a `WeightGuard` leaves the urn well-formed
+    #[quickcheck]
+    fn weight_mut_preserves_wf(urn: Urn<char>, pos: u32, w: Weight) -> bool {
+        let mut urn = urn;
+        let pos = pos % urn.size();
+        let old = urn.to_vec()[pos as usize].0;
+        let mut guard = urn.weight_mut(pos);
+        let seen = *guard;
+        *guard = w;
+        drop(guard);
+        seen == old && urn.is_wf() && urn.to_vec()[pos as usize].0 == w
+    }
+
+    // `remove_position` removes exactly the element at that position
+    #[quickcheck]
+    fn remove_position_removes_element(urn: Urn<char>, pos: u32) -> bool {
+        let pos = pos % urn.size();
+        let mut elems = urn.to_vec();
+        let expected = elems.remove(pos as usize);
+        let (removed, new_urn) = urn.remove_position(pos);
+        let wf = new_urn.as_ref().is_none_or(|u| u.is_wf());
+        let mut rest = new_urn.map_or(vec![], |u| u.to_vec());
+        elems.sort();
+        rest.sort();
+        wf && removed == expected && elems == rest
+    }
+
+    // The special cases for urns of size 1 and 2 in `remove_index` agree
+    // with the general case
+    #[quickcheck]
+    fn remove_index_small_urns(
+        (w1, a1): (Weight, char),
+        second: Option<(Weight, char)>,
+        i: Index,
+    ) -> bool {
+        let mut urn = urn::singleton(w1, a1);
+        if let Some((w2, a2)) = second {
+            urn = urn.insert(w2, a2);
+        }
+        urn.clone().remove_index(i) == urn.remove_index_naive(i)
+    }
+
+    // The special cases for urns of size 1 and 2 in `sample_ref` sample
+    // the same elements as looking up the drawn index in the tree
+    #[quickcheck]
+    fn sample_ref_small_urns(
+        (w1, a1): (Weight, char),
+        second: Option<(Weight, char)>,
+        seed: u64,
+    ) -> bool {
+        let mut urn = urn::singleton(w1, a1);
+        if let Some((w2, a2)) = second {
+            urn = urn.insert(w2, a2);
+        }
+        let mut rng1 = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut rng2 = rand::rngs::StdRng::seed_from_u64(seed);
+        let expected = if urn.size() == 1 {
+            &a1
+        } else {
+            urn.sample_index_ref(urn.draw_index(&mut rng2))
+        };
+        urn.sample_ref(&mut rng1) == expected
+    }
+
+    // A `FenwickUrn` maps indexes to the same elements as an `Urn`
+    // built from the same list (as long as the total weight doesn't overflow)
+    #[quickcheck]
+    fn fenwick_agrees_with_urn(elems: Vec<(Weight, char)>, i: Index) -> bool {
+        let total: u128 = elems.iter().map(|(w, _)| u128::from(*w)).sum();
+        if total > u128::from(Index::MAX) {
+            return true;
+        }
+        let fenwick = FenwickUrn::from_list(elems.clone());
+        urn::from_list(elems).is_none_or(|urn| {
+            let i = i % urn.weight().max(1);
+            fenwick.weight() == urn.weight()
+                && fenwick.get(fenwick.position_of_index(i)).map(|(_, a)| a)
+                    == Some(urn.sample_index_ref(i))
+        })
+    }
+
+    // Building a `FenwickUrn` by `push`ing gives the same partial sums
+    // as `from_list`, and `swap_remove` keeps them consistent
+    #[quickcheck]
+    fn fenwick_push_remove(elems: Vec<(Weight, char)>, i: usize) -> bool {
+        let mut pushed = FenwickUrn::new();
+        for (w, a) in elems.clone() {
+            pushed.push(w, a);
+        }
+        let mut elems = elems;
+        if pushed != FenwickUrn::from_list(elems.clone()) {
+            return false;
+        }
+        if elems.is_empty() {
+            return true;
+        }
+        let i = i % elems.len();
+        pushed.swap_remove(i) == elems.swap_remove(i)
+            && pushed == FenwickUrn::from_list(elems)
+    }
+
+    // After removing some elements by handle, every remaining handle still
+    // refers to the element it was created for, and the urn is well-formed
+    #[quickcheck]
+    fn handles_track_elements(
+        elems: Vec<(Weight, char)>,
+        removals: Vec<usize>,
+    ) -> bool {
+        let mut urn = HandleUrn::new();
+        let mut live: Vec<_> = elems
+            .into_iter()
+            .map(|(w, a)| (urn.insert(w, a), (w, a)))
+            .collect();
+        for i in removals {
+            if live.is_empty() {
+                break;
+            }
+            let (h, elem) = live.swap_remove(i % live.len());
+            if urn.remove_handle(h) != Some(elem) {
+                return false;
+            }
+        }
+        urn.len() == live.len()
+            && urn.urn().is_none_or(|u| u.is_wf())
+            && live.iter().all(|(h, (w, a))| urn.get(*h) == Some((*w, a)))
+    }
+
+    // `insert_many` (whether it rebuilds the urn or not) produces a
+    // well-formed urn with the same elements as repeated `insert`s
+    #[quickcheck]
+    fn insert_many_equivalent_to_insert(
+        urn: Urn<char>,
+        elems: Vec<(Weight, char)>,
+        rebuild_factor: u8,
+    ) -> bool {
+        let inserted =
+            elems.iter().fold(urn.clone(), |u, (w, a)| u.insert(*w, *a));
+        let batched = urn.insert_many_with(elems, u32::from(rebuild_factor));
+        let (mut expected, mut actual) =
+            (inserted.into_vec(), batched.to_vec());
+        expected.sort();
+        actual.sort();
+        batched.is_wf() && expected == actual
+    }
+
+    // Resolving sorted indexes in one pass agrees with `sample_index_ref`
+    #[quickcheck]
+    fn positions_of_sorted_indexes_agrees_with_sample_index(
+        urn: Urn<char>,
+        indexes: Vec<Index>,
+    ) -> TestResult {
+        if !urn.weight_fits() {
+            return TestResult::discard();
+        }
+        let mut indexes = indexes;
+        indexes.sort();
+        let n = urn.size();
+        let positions = urn.positions_of_sorted_indexes(&indexes);
+        TestResult::from_bool(indexes.iter().zip(positions).all(|(&i, pos)| {
+            std::ptr::eq(
+                urn.sample_index_ref(i),
+                urn.tree.lookup_position(n, pos).1,
+            )
+        }))
+    }
+
+    // `partition` produces well-formed urns which together contain
+    // all the elements of the original urn
+    #[quickcheck]
+    fn partition_preserves_elements(urn: Urn<char>, min_w: Weight) -> bool {
+        let mut expected = urn.to_vec();
+        let (heavy, light) = urn.partition(|w, _| w >= min_w);
+        let heavy_ok = heavy
+            .as_ref()
+            .is_none_or(|u| u.is_wf() && u.iter().all(|(w, _)| w >= min_w));
+        let light_ok = light
+            .as_ref()
+            .is_none_or(|u| u.is_wf() && u.iter().all(|(w, _)| w < min_w));
+        let mut actual: Vec<_> = [heavy, light]
+            .into_iter()
+            .flatten()
+            .flat_map(|u| u.into_vec())
+            .collect();
+        expected.sort();
+        actual.sort();
+        heavy_ok && light_ok && expected == actual
+    }
+
+    // The `i`-th outcome of `iter_buckets_expanded` is the element
+    // whose bucket contains index `i`
+    #[quickcheck]
+    fn iter_buckets_expanded_agrees_with_sample_index(
+        elems: Vec<(u8, char)>,
+    ) -> TestResult {
+        let elems = elems.into_iter().map(|(w, a)| (Weight::from(w), a));
+        let Some(urn) = urn::from_list(elems.collect()) else {
+            return TestResult::discard();
+        };
+        if !urn.weight_fits() {
+            return TestResult::discard();
+        }
+        let expanded: Vec<&char> = urn.iter_buckets_expanded().collect();
+        TestResult::from_bool(
+            expanded.len() == urn.weight() as usize
+                && expanded
+                    .iter()
+                    .enumerate()
+                    .all(|(i, a)| *a == urn.sample_index_ref(i as Index)),
+        )
+    }
+
+    // Decaying an urn in place keeps it well-formed, and scales every
+    // weight independently
+    #[quickcheck]
+    fn decay_keep_is_wf(urn: Urn<char>, factor: u8, min_weight: u8) -> bool {
+        let factor = f64::from(factor) / 100.0;
+        let min_weight = Weight::from(min_weight);
+        let before = urn.to_vec();
+        let decayed = urn
+            .decay(factor, min_weight, ZeroWeightPolicy::Keep)
+            .unwrap();
+        decayed.is_wf()
+            && decayed.size() as usize == before.len()
+            && decayed.iter().zip(before.iter()).all(|((w, a), (w0, a0))| {
+                a == a0
+                    && w >= min_weight
+                    && (w == min_weight
+                        || w == (*w0 as f64 * factor).round() as Weight)
+            })
+    }
+
+    // The probabilities of all elements sum to 1, and the entropy
+    // is bounded by `log2(n)`
+    #[quickcheck]
+    fn probabilities_sum_to_one(urn: Urn<char>) -> TestResult {
+        if !urn.weight_fits() || urn.weight() == 0 {
+            return TestResult::discard();
+        }
+        let total: f64 = urn.probabilities().map(|(p, _)| p).sum();
+        let entropy = urn.entropy();
+        TestResult::from_bool(
+            (total - 1.0).abs() < 1e-9
+                && entropy >= 0.0
+                && entropy <= f64::from(urn.size()).log2() + 1e-9,
+        )
+    }
+
+    // Every index-drawing strategy only draws indexes within the
+    // urn's total weight
+    #[quickcheck]
+    fn draw_index_in_range(urn: Urn<char>, seed: u64) -> TestResult {
+        if urn.weight() == 0 {
+            return TestResult::discard();
+        }
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        TestResult::from_bool(
+            [IndexDraw::Uniform, IndexDraw::Lemire, IndexDraw::Bitmask]
+                .into_iter()
+                .all(|draw| {
+                    let urn = urn.clone().with_index_draw(draw);
+                    (0..20).all(|_| urn.draw_index(&mut rng) < urn.weight())
+                }),
+        )
+    }
+
+    // Locating the bucket of a single index agrees with the
+    // single-pass batch lookup
+    #[quickcheck]
+    fn position_of_index_agrees_with_batch(urn: Urn<char>) -> TestResult {
+        if !urn.weight_fits() {
+            return TestResult::discard();
+        }
+        // (Only check a bounded no. of indexes for wide weights)
+        let step = u128::from(urn.weight()) / 256 + 1;
+        let indexes: Vec<Index> =
+            (0..=urn.weight()).step_by(step as usize).collect();
+        let positions = urn.positions_of_sorted_indexes(&indexes);
+        TestResult::from_bool(
+            indexes
+                .iter()
+                .zip(positions)
+                .all(|(&i, pos)| urn.position_of_index(i) == pos),
+        )
+    }
+
+    // `from_chunks` builds a well-formed urn containing exactly the elements
+    // that `from_list_naive` inserts one at a time
+    #[quickcheck]
+    fn from_chunks_equivalent_to_from_list_naive(
+        chunks: Vec<Vec<(Weight, char)>>,
+    ) -> bool {
+        let elems: Vec<(Weight, char)> = chunks.concat();
+        let chunked = urn::from_chunks(chunks);
+        let naive = urn::from_list_naive(elems);
+        match (chunked, naive) {
+            (None, None) => true,
+            (Some(chunked), Some(naive)) => {
+                let (mut xs, mut ys) = (chunked.to_vec(), naive.to_vec());
+                xs.sort();
+                ys.sort();
+                chunked.is_wf()
+                    && chunked.size() == naive.size()
+                    && chunked.weight() == naive.weight()
+                    && xs == ys
+            }
+            _ => false,
+        }
+    }
+
+    // The sums cached in an `FUrn` stay close to the compensated sums of
+    // its weights after any sequence of updates
+    #[quickcheck]
+    fn furn_updates_preserve_sums(ops: Vec<(u8, u16, u32)>) -> bool {
+        let mut urn = crate::furn::FUrn::new();
+        for (op, i, w) in ops {
+            let w = f64::from(w) / f64::from(u16::MAX);
+            let i = usize::from(i) % urn.len().max(1);
+            match op % 3 {
+                0 => {
+                    urn.push(w, i).unwrap();
+                }
+                1 if !urn.is_empty() => {
+                    urn.set_weight(i, w).unwrap();
+                }
+                2 if !urn.is_empty() => {
+                    urn.swap_remove(i);
+                }
+                _ => (),
+            }
+        }
+        urn.check_sums(1e-9)
+    }
+
+    // `sample_k_distinct` splits the urn's elements between the drawn
+    // elements and the remaining urn
+    #[quickcheck]
+    fn sample_k_distinct_partitions_elements(urn: Urn<char>, k: u32) -> bool {
+        let k = k % (urn.size() + 1);
+        let mut elems = urn.to_vec();
+        let mut rng = rand::thread_rng();
+        let (mut drawn, rest) = urn.sample_k_distinct(k, &mut rng).unwrap();
+        let wf = rest.as_ref().is_none_or(|u| u.is_wf());
+        drawn.extend(rest.map_or(vec![], |u| u.into_vec()));
+        elems.sort();
+        drawn.sort();
+        wf && elems == drawn
+    }
+
+    // Reservoir sampling returns `min(k, m)` distinct items with positive
+    // weight, where `m` is the no. of items with positive weight
+    #[quickcheck]
+    fn weighted_sample_k_size(weights: Vec<Weight>, k: u8) -> bool {
+        let stream: Vec<(Weight, usize)> =
+            weights.iter().copied().zip(0..).collect();
+        let m = weights.iter().filter(|w| **w > 0).count();
+        let mut rng = rand::thread_rng();
+        let sample =
+            crate::reservoir::weighted_sample_k(stream, k.into(), &mut rng);
+        let mut items: Vec<usize> = sample.iter().map(|(_, i)| *i).collect();
+        items.sort();
+        items.dedup();
+        items.len() == m.min(k.into())
+            && sample.iter().all(|&(w, i)| w > 0 && weights[i] == w)
+    }
+
+    // `update_all` preserves well-formedness, and agrees with mapping
+    // over the urn's elements
+    #[quickcheck]
+    fn update_all_is_wf(urn: Urn<char>, ws: Vec<Weight>) -> bool {
+        let f = |k: usize, w: Weight| ws.get(k).copied().unwrap_or(w);
+        let expected: Vec<(Weight, usize)> = urn
+            .iter()
+            .enumerate()
+            .map(|(k, (w, _))| (f(k, w), k))
+            .collect();
+        let mut k = 0;
+        let new_urn = urn.update_all(|w, _| {
+            k += 1;
+            (f(k - 1, w), k - 1)
+        });
+        new_urn.is_wf() && new_urn.to_vec() == expected
+    }
+
+    // Urns built from permutations of the same elements have the same
+    // distribution and the same canonical form
+    #[quickcheck]
+    fn canonicalize_ignores_order(elems: Vec<(Weight, char)>) -> bool {
+        let reversed: Vec<_> = elems.iter().rev().cloned().collect();
+        match (urn::from_list(elems), urn::from_list_naive(reversed)) {
+            (Some(u1), Some(u2)) => {
+                u1.same_distribution(&u2)
+                    && u1.clone().canonicalize() == u2.canonicalize()
+            }
+            (u1, u2) => u1.is_none() && u2.is_none(),
+        }
+    }
 }
@@ -0,0 +1,136 @@
+use rand_core::RngCore;
+
+/* -------------------------------------------------------------------------- */
+/*                     Uniform sampling on top of `RngCore`                   */
+/* -------------------------------------------------------------------------- */
+
+// The crate only requires `rand_core::RngCore` from random number
+// generators, so it doesn't need `rand` itself (see the `std-rng` feature).
+// The functions below reimplement the algorithms `rand` 0.8 uses for the
+// corresponding `Rng` methods, so that they consume the same random numbers
+// and produce the same values (e.g. a seeded `StdRng` yields the same
+// samples whether or not `rand` is used).
+
+/// The high and low halves of the 256-bit product of `a` and `b`
+/// (a translation of `__mulddi3` from LLVM's compiler-rt, as in `rand`)
+fn wmul_u128(a: u128, b: u128) -> (u128, u128) {
+    const LOWER_MASK: u128 = !0 >> 64;
+    let mut low = (a & LOWER_MASK).wrapping_mul(b & LOWER_MASK);
+    let mut t = low >> 64;
+    low &= LOWER_MASK;
+    t += (a >> 64).wrapping_mul(b & LOWER_MASK);
+    low += (t & LOWER_MASK) << 64;
+    let mut high = t >> 64;
+    t = low >> 64;
+    low &= LOWER_MASK;
+    t += (b >> 64).wrapping_mul(a & LOWER_MASK);
+    low += (t & LOWER_MASK) << 64;
+    high += t >> 64;
+    high += (a >> 64).wrapping_mul(b >> 64);
+    (high, low)
+}
+
+/// Draws a `u64` uniformly at random from the range `[0, n)`
+/// (like `rng.gen_range(0..n)`).
+/// Panics if `n` is 0.
+pub(crate) fn below_u64<R: RngCore + ?Sized>(rng: &mut R, n: u64) -> u64 {
+    assert!(n > 0, "cannot sample empty range");
+    // A conservative approximation of the largest multiple of `n`
+    let zone = (n << n.leading_zeros()).wrapping_sub(1);
+    loop {
+        let m = u128::from(rng.next_u64()) * u128::from(n);
+        if m as u64 <= zone {
+            return (m >> 64) as u64;
+        }
+    }
+}
+
+/// Draws a `u128` uniformly at random from the range `[0, n)`
+/// (like `rng.gen_range(0..n)`).
+/// Panics if `n` is 0.
+pub(crate) fn below_u128<R: RngCore + ?Sized>(rng: &mut R, n: u128) -> u128 {
+    assert!(n > 0, "cannot sample empty range");
+    let zone = (n << n.leading_zeros()).wrapping_sub(1);
+    loop {
+        let lo = u128::from(rng.next_u64());
+        let v = u128::from(rng.next_u64()) << 64 | lo;
+        let (hi, lo) = wmul_u128(v, n);
+        if lo <= zone {
+            return hi;
+        }
+    }
+}
+
+/// Builds the `f64` in `[1, 2)` whose fraction bits are the lowest 52 bits
+/// of `fraction`
+fn one_to_two(fraction: u64) -> f64 {
+    f64::from_bits(fraction | 1023 << 52)
+}
+
+/// Draws an `f64` uniformly at random from `[0, 1)`, with 53 random bits
+/// (like `rng.gen::<f64>()`)
+pub(crate) fn unit_f64<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    (rng.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Draws an `f64` uniformly at random from the open interval `(0, 1)`, with
+/// 52 random bits (like `rng.sample(rand::distributions::Open01)`)
+pub(crate) fn open01_f64<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    one_to_two(rng.next_u64() >> 12) - (1.0 - f64::EPSILON / 2.0)
+}
+
+/// Draws an `f64` uniformly at random from `[lo, hi)`
+/// (like `rng.gen_range(lo..hi)`).
+/// Panics unless `lo < hi` and both are finite.
+pub(crate) fn range_f64<R: RngCore + ?Sized>(
+    rng: &mut R,
+    lo: f64,
+    hi: f64,
+) -> f64 {
+    assert!(lo < hi, "cannot sample empty range");
+    let scale = hi - lo;
+    assert!(scale.is_finite(), "range overflow");
+    loop {
+        let u = one_to_two(rng.next_u64() >> 12) - 1.0;
+        let x = u * scale + lo;
+        if x < hi {
+            return x;
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{distributions::Open01, rngs::StdRng, Rng, SeedableRng};
+
+    // Every function agrees with the `rand` method it reimplements
+    #[test]
+    fn agrees_with_rand() {
+        let (mut ours, mut theirs) =
+            (StdRng::seed_from_u64(7), StdRng::seed_from_u64(7));
+        for n in [1, 2, 3, 10, 255, 1 << 40, u64::MAX / 3 + 1, u64::MAX] {
+            for _ in 0..50 {
+                assert_eq!(below_u64(&mut ours, n), theirs.gen_range(0..n));
+            }
+        }
+        let ns = [1, 7, 1 << 64, (1 << 64) + 3, u128::MAX / 5, u128::MAX];
+        for n in ns {
+            for _ in 0..50 {
+                assert_eq!(below_u128(&mut ours, n), theirs.gen_range(0..n));
+            }
+        }
+        for _ in 0..200 {
+            assert_eq!(unit_f64(&mut ours), theirs.gen::<f64>());
+            assert_eq!(open01_f64(&mut ours), theirs.sample::<f64, _>(Open01));
+            assert_eq!(
+                range_f64(&mut ours, 0.25, 1.0),
+                theirs.gen_range(0.25..1.0)
+            );
+        }
+    }
+}
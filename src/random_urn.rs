@@ -0,0 +1,198 @@
+#[cfg(feature = "std-rng")]
+use rand::{rngs::StdRng, SeedableRng};
+use rand_core::RngCore;
+
+use crate::{
+    types::{Index, Urn, Weight},
+    urn::singleton,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                          Urns which own their RNG                          */
+/* -------------------------------------------------------------------------- */
+
+/// An urn bundled with the random number generator used to sample from it,
+/// so that the randomized operations (`sample`, `remove`, `update`,
+/// `replace`) don't need an RNG to be passed around.
+/// Built with `Urn::with_rng` (or `Urn::with_seed`, for a reproducible
+/// `StdRng`).
+///
+/// The urn is updated in place, and may become empty by removing all of its
+/// elements (in which case the randomized operations return `None`).
+#[derive(Debug, Clone)]
+pub struct RandomUrn<T: Clone, R: RngCore> {
+    urn: Option<Urn<T>>,
+    rng: R,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Bundles the urn with the random number generator `rng`
+    /// (see `RandomUrn`)
+    pub fn with_rng<R: RngCore>(self, rng: R) -> RandomUrn<T, R> {
+        RandomUrn {
+            urn: Some(self),
+            rng,
+        }
+    }
+
+    /// Bundles the urn with a `StdRng` seeded with `seed`, so that the
+    /// same seed yields the same sequence of draws (see `RandomUrn`)
+    #[cfg(feature = "std-rng")]
+    pub fn with_seed(self, seed: u64) -> RandomUrn<T, StdRng> {
+        self.with_rng(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<T: Clone, R: RngCore> RandomUrn<T, R> {
+    /// Creates an empty urn which will sample using `rng`
+    pub fn new(rng: R) -> Self {
+        Self { urn: None, rng }
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.urn.as_ref().map_or(0, |urn| urn.size() as usize)
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.urn.is_none()
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> Index {
+        self.urn.as_ref().map_or(0, |urn| urn.weight())
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.urn.as_ref()
+    }
+
+    /// The random number generator, e.g. to draw other random values
+    /// from the same stream
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    /// Separates the urn (`None` if there are no elements) from the
+    /// random number generator
+    pub fn into_parts(self) -> (Option<Urn<T>>, R) {
+        (self.urn, self.rng)
+    }
+
+    /// Inserts element `a` with weight `w`.
+    /// Time complexity: `O(log n)`.
+    pub fn insert(&mut self, w: Weight, a: T) {
+        self.urn = Some(match self.urn.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        });
+    }
+
+    /// Removes all the elements, returning an iterator over them (and their
+    /// weights) in leaf order. The urn is left empty, but keeps its random
+    /// number generator (e.g. to refill it for the next simulation episode).
+    /// Time complexity: `O(n)`.
+    pub fn drain(&mut self) -> std::vec::IntoIter<(Weight, T)> {
+        self.urn
+            .take()
+            .map_or_else(Vec::new, Urn::into_vec)
+            .into_iter()
+    }
+
+    /// Removes all the elements, keeping the random number generator.
+    /// Time complexity: `O(n)`.
+    pub fn clear(&mut self) {
+        self.urn = None;
+    }
+
+    /// Randomly samples (a reference to) an element, or returns `None` if
+    /// there are no elements.
+    /// Time complexity: `O(log n)`.
+    pub fn sample(&mut self) -> Option<&T> {
+        let urn = self.urn.as_ref()?;
+        Some(urn.sample_ref(&mut self.rng))
+    }
+
+    /// Removes a random element, returning it along with its weight
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn remove(&mut self) -> Option<(Weight, T)> {
+        let urn = self.urn.take()?;
+        let i = urn.draw_index(&mut self.rng);
+        let (removed, urn) = urn.remove_index(i);
+        self.urn = urn;
+        Some(removed)
+    }
+
+    /// Randomly samples an element `a` with weight `w`, and replaces it by
+    /// `(w_new, a_new) = f(w, a)` (see `Urn::update`), returning both pairs
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn update<F>(&mut self, f: F) -> Option<((Weight, T), (Weight, T))>
+    where
+        F: FnOnce(Weight, T) -> (Weight, T),
+    {
+        let urn = self.urn.take()?;
+        let i = urn.draw_index(&mut self.rng);
+        let (old, new, urn) = urn.update_index(f, i);
+        self.urn = Some(urn);
+        Some((old, new))
+    }
+
+    /// Randomly samples an element and replaces it by the element `a`
+    /// with weight `w`, returning the sampled element along with its weight
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn replace(&mut self, w: Weight, a: T) -> Option<(Weight, T)> {
+        let urn = self.urn.as_ref()?;
+        let i = urn.draw_index(&mut self.rng);
+        let ((w_old, a_old), new_urn) = urn.replace_index(w, &a, i);
+        let old = (w_old, a_old.clone());
+        self.urn = Some(new_urn);
+        Some(old)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn random_urn_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let mut random = urn.clone().with_rng(StdRng::seed_from_u64(5));
+        assert!("RGB".contains(*random.sample().unwrap()));
+        assert_eq!(random.replace(0, 'Y').map(|(w, _)| w > 0), Some(true));
+        assert_eq!((random.len(), random.weight() < 9), (3, true));
+        let ((w, a), (w_new, a_new)) = random
+            .update(|w, a| (w + 1, a.to_ascii_lowercase()))
+            .unwrap();
+        assert!(w > 0 && w_new == w + 1 && a_new == a.to_ascii_lowercase());
+
+        let removed: Vec<char> =
+            std::iter::from_fn(|| random.remove().map(|(_, a)| a)).collect();
+        assert_eq!(removed.len(), 3);
+        assert!(random.is_empty() && random.sample().is_none());
+        random.insert(1, 'Z');
+        assert_eq!(random.sample(), Some(&'Z'));
+        random.insert(2, 'Y');
+        assert_eq!(random.drain().collect::<Vec<_>>(), [(1, 'Z'), (2, 'Y')]);
+        assert!(random.is_empty());
+
+        // The same seed yields the same draws
+        let draws = |seed| {
+            let mut random = urn.clone().with_rng(StdRng::seed_from_u64(seed));
+            (0..20)
+                .map(|_| *random.sample().unwrap())
+                .collect::<String>()
+        };
+        assert_eq!(draws(7), draws(7));
+    }
+}
@@ -0,0 +1,116 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{Index, Urn},
+    urn::from_iter_exact,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                       Resampling (e.g. particle filters)                   */
+/* -------------------------------------------------------------------------- */
+
+/// Schemes for resampling an urn of weighted particles (see `Urn::resample`).
+/// All three schemes draw every particle with weight `w` `n * w / W` times
+/// in expectation (where `W` is the total weight), but they differ in the
+/// variance of these counts.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ResampleScheme {
+    /// `n` independent draws (as by `Urn::sample_ref`)
+    Multinomial,
+    /// The range `[0, W)` is split into `n` strata of equal width, and one
+    /// index is drawn uniformly at random from each stratum
+    Stratified,
+    /// Like `Stratified`, but the same offset is used in every stratum, so
+    /// the `n` indexes are equally spaced (see `Urn::sample_systematic`)
+    #[default]
+    Systematic,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Resamples the urn's elements (the *particles*) using the scheme
+    /// `scheme` and `rng`, returning a new urn with `n` copies of the drawn
+    /// particles, each with weight 1 (the resampling step of sequential
+    /// Monte Carlo methods such as particle filters).
+    /// The particles are drawn with replacement, in leaf order for the
+    /// `Stratified` and `Systematic` schemes, and in the order they were drawn
+    /// for the `Multinomial` scheme.
+    /// Panics if `n` is 0 (urns are non-empty) or doesn't fit in a `u32`.
+    /// Time complexity: `O(n log m)` for the `Multinomial` scheme, and
+    /// `O(m + n log m)` for the others, where `m` is the size of the urn.
+    pub fn resample<R: RngCore + ?Sized>(
+        &self,
+        n: usize,
+        scheme: ResampleScheme,
+        rng: &mut R,
+    ) -> Urn<T> {
+        assert!(n > 0, "cannot resample 0 particles");
+        assert!(u32::try_from(n).is_ok(), "too many particles");
+        let particles: Vec<&T> = match scheme {
+            ResampleScheme::Multinomial => {
+                (0..n).map(|_| self.sample_ref(rng)).collect()
+            }
+            ResampleScheme::Stratified => {
+                // The `j`-th index is `floor((j * W + r_j) / n)` for a fresh
+                // `r_j` in `[0, W)`, which lies in `[j * W / n, (j+1) * W / n)`,
+                // so the indexes are sorted
+                let (total, k) = (u128::from(self.weight()), n as u128);
+                let indexes: Vec<Index> = (0..k)
+                    .map(|j| {
+                        let r = u128::from(self.draw_index(rng));
+                        ((j * total + r) / k) as Index
+                    })
+                    .collect();
+                self.positions_of_sorted_indexes(&indexes)
+                    .into_iter()
+                    .map(|pos| self.tree.lookup_position(self.size(), pos).1)
+                    .collect()
+            }
+            ResampleScheme::Systematic => self.sample_systematic(n, rng),
+        };
+        from_iter_exact(particles.into_iter().map(|a| (1, a.clone())))
+            .expect("n is positive")
+            .with_index_draw(self.draw)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::ResampleScheme::{self, *};
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn resample_gives_equal_weights() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (5, 'Y')]);
+        let urn = urn.unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        let schemes = [Multinomial, Stratified, Systematic];
+        for scheme in schemes {
+            let resampled = urn.resample(7, scheme, &mut rng);
+            assert_eq!((resampled.size(), resampled.weight()), (7, 7));
+            assert!(resampled.iter().all(|(w, &a)| w == 1 && a != 'G'));
+        }
+    }
+
+    #[test]
+    fn low_variance_schemes_are_proportional() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (5, 'Y')]);
+        let urn = urn.unwrap();
+        let mut rng = StdRng::seed_from_u64(6);
+        for _ in 0..20 {
+            // With 10 particles (the total weight), the stratum of each
+            // particle lies within a single bucket
+            for scheme in [Stratified, Systematic] {
+                let resampled = urn.resample(10, scheme, &mut rng);
+                let particles: String =
+                    resampled.iter().map(|(_, &a)| a).collect();
+                assert_eq!(particles, "RRBBBYYYYY");
+            }
+        }
+        assert_eq!(ResampleScheme::default(), Systematic);
+    }
+}
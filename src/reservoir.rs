@@ -0,0 +1,185 @@
+//! Weighted reservoir sampling, i.e. weighted sampling without replacement
+//! from a stream of `(weight, item)` pairs whose length isn't known in
+//! advance, using `O(k)` memory (where `k` is the no. of items to sample).
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use rand_core::RngCore;
+
+use crate::{
+    random::range_f64,
+    types::{Urn, Weight},
+    urn::from_list,
+};
+
+/// An item in the reservoir, along with the logarithm of its key
+/// (items with larger keys are more likely to be kept)
+struct Keyed<T> {
+    log_key: f64,
+    w: Weight,
+    a: T,
+}
+
+// `BinaryHeap` is a max-heap, so items are ordered by *decreasing* key,
+// in order to keep the item with the smallest key at the top
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.log_key.total_cmp(&self.log_key)
+    }
+}
+
+/// Draws a number uniformly at random from the open interval `(lo, 1)`
+fn open_uniform<R: RngCore + ?Sized>(rng: &mut R, lo: f64) -> f64 {
+    loop {
+        let u = range_f64(rng, lo, 1.0);
+        if u > lo {
+            return u;
+        }
+    }
+}
+
+/// Samples (up to) `k` items without replacement from the stream `iter`
+/// using `rng`, where each item is chosen with probability proportional to
+/// its weight among the items that haven't been chosen yet. The stream is
+/// consumed in one pass, without being materialized.
+///
+/// This is Efraimidis and Spirakis' algorithm A-ExpJ: each item conceptually
+/// gets the key `u^(1/w)` (where `u` is uniform in `(0, 1)`), and the `k`
+/// items with the largest keys are kept. Instead of drawing a key for every
+/// item, the algorithm draws how much weight to skip until the next item
+/// which enters the reservoir, so it only draws `O(k log(n / k))` random
+/// numbers in expectation.
+///
+/// Items with weight 0 are never chosen, so fewer than `k` items are returned
+/// if the stream contains fewer than `k` items with positive weight.
+/// The items are returned in decreasing order of their keys, which is
+/// distributed like the order in which `k` successive weighted draws without
+/// replacement would choose them.
+/// Time complexity: `O(n + k log k log(n / k))` for a stream of `n` items.
+pub fn weighted_sample_k<I, T, R>(
+    iter: I,
+    k: usize,
+    rng: &mut R,
+) -> Vec<(Weight, T)>
+where
+    I: IntoIterator<Item = (Weight, T)>,
+    R: RngCore + ?Sized,
+{
+    if k == 0 {
+        return vec![];
+    }
+    let mut reservoir: BinaryHeap<Keyed<T>> = BinaryHeap::new();
+    let mut items = iter.into_iter().filter(|(w, _)| *w > 0);
+
+    // Fill the reservoir with the first `k` items
+    for (w, a) in items.by_ref().take(k) {
+        let log_key = open_uniform(rng, 0.0).ln() / w as f64;
+        reservoir.push(Keyed { log_key, w, a });
+    }
+
+    // `skip` is the amount of weight to skip before the next item enters
+    // the reservoir, i.e. `ln(r) / ln(t)` where `t` is the smallest key
+    let next_skip = |reservoir: &BinaryHeap<Keyed<T>>, rng: &mut R| {
+        let log_t = reservoir.peek().map_or(0.0, |min| min.log_key);
+        open_uniform(rng, 0.0).ln() / log_t
+    };
+    if reservoir.len() == k {
+        let mut skip = next_skip(&reservoir, rng);
+        for (w, a) in items {
+            let w_f = w as f64;
+            if skip > w_f {
+                skip -= w_f;
+                continue;
+            }
+            // The item replaces the item with the smallest key `t`, and
+            // gets a key drawn uniformly from `(t^w, 1)`, raised to `1/w`
+            let log_t = reservoir.peek().map_or(0.0, |min| min.log_key);
+            // (`t^w` may round to 1 if the smallest key is very close to 1)
+            let t_w = (log_t * w_f).exp().min(1.0 - f64::EPSILON);
+            let log_key = open_uniform(rng, t_w).ln() / w_f;
+            reservoir.pop();
+            reservoir.push(Keyed { log_key, w, a });
+            skip = next_skip(&reservoir, rng);
+        }
+    }
+    // Popping yields the items in increasing order of their keys
+    let mut sampled: Vec<(Weight, T)> = std::iter::from_fn(|| reservoir.pop())
+        .map(|item| (item.w, item.a))
+        .collect();
+    sampled.reverse();
+    sampled
+}
+
+/// Like `weighted_sample_k`, but collects the sampled items (with their
+/// weights) into an urn, which is `None` if no item was sampled
+/// (i.e. if `k == 0`, or if no item has a positive weight).
+/// Time complexity: `O(n + k log k log(n / k))`.
+pub fn weighted_sample_k_urn<I, T, R>(
+    iter: I,
+    k: usize,
+    rng: &mut R,
+) -> Option<Urn<T>>
+where
+    I: IntoIterator<Item = (Weight, T)>,
+    T: Clone,
+    R: RngCore + ?Sized,
+{
+    from_list(weighted_sample_k(iter, k, rng))
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn reservoir_keeps_positive_weights() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let stream = vec![(1, 'a'), (0, 'b'), (2, 'c'), (3, 'd')];
+        let mut all = weighted_sample_k(stream.clone(), 10, &mut rng);
+        all.sort();
+        assert_eq!(all, vec![(1, 'a'), (2, 'c'), (3, 'd')]);
+        assert!(weighted_sample_k(stream.clone(), 0, &mut rng).is_empty());
+        assert_eq!(weighted_sample_k_urn(stream, 0, &mut rng), None);
+    }
+
+    #[test]
+    fn reservoir_prefers_heavy_items() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // A long stream of light items, with a heavy item in the middle
+        let stream =
+            (0..10_000u32).map(|i| if i == 5000 { (100, i) } else { (1, i) });
+        let mut hits = 0;
+        for _ in 0..100 {
+            let sample = weighted_sample_k(stream.clone(), 10, &mut rng);
+            assert_eq!(sample.len(), 10);
+            let mut items: Vec<u32> = sample.iter().map(|(_, i)| *i).collect();
+            items.sort();
+            items.dedup();
+            assert_eq!(items.len(), 10);
+            hits += usize::from(items.contains(&5000));
+        }
+        // The heavy item is in the sample with probability about 0.1
+        assert!((2..30).contains(&hits));
+        let urn = weighted_sample_k_urn(stream, 3, &mut rng).unwrap();
+        assert_eq!(urn.size(), 3);
+    }
+}
@@ -0,0 +1,100 @@
+use rand_core::RngCore;
+
+use crate::types::{Index, Urn, Weight};
+
+/// Common interface for weighted sampling data structures, so that
+/// applications can swap between sampling backends (and benchmark them)
+/// by changing a generic parameter.      
+/// Like `Urn`, the interface is persistent: `insert` and `remove` consume
+/// the sampler and return the updated one, and `remove` returns `None`
+/// once the last element has been removed.
+pub trait WeightedSampler<T>: Sized {
+    /// Inserts element `a` with weight `w`
+    fn insert(self, w: Weight, a: T) -> Self;
+
+    /// Removes a random element (chosen using `rng`), returning it along with
+    /// its weight and the remaining sampler (if it is non-empty)
+    fn remove<R: RngCore + ?Sized>(
+        self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>);
+
+    /// Samples (a reference to) a random element using `rng`
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T;
+
+    /// The total weight of all the elements
+    fn weight(&self) -> Index;
+
+    /// The no. of elements
+    fn len(&self) -> usize;
+
+    /// Whether there are no elements
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Clone> WeightedSampler<T> for Urn<T> {
+    fn insert(self, w: Weight, a: T) -> Self {
+        Urn::insert(self, w, a)
+    }
+
+    fn remove<R: RngCore + ?Sized>(
+        self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>) {
+        let i = self.draw_index(rng);
+        self.remove_index(i)
+    }
+
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        self.sample_ref(rng)
+    }
+
+    fn weight(&self) -> Index {
+        Urn::weight(self)
+    }
+
+    fn len(&self) -> usize {
+        self.size() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{urn::singleton, AliasTable};
+    use rand::thread_rng;
+
+    /// Inserts `n` elements into a sampler, then removes all of them
+    fn churn<S: WeightedSampler<u32>>(mut sampler: S, n: u32) -> usize {
+        let mut rng = thread_rng();
+        for i in 0..n {
+            sampler = sampler.insert(1, i);
+        }
+        let mut removed = 0;
+        let mut sampler_opt = Some(sampler);
+        while let Some(sampler) = sampler_opt {
+            let _ = sampler.sample(&mut rng);
+            let (_, rest) = sampler.remove(&mut rng);
+            sampler_opt = rest;
+            removed += 1;
+        }
+        removed
+    }
+
+    #[test]
+    fn urn_implements_weighted_sampler() {
+        assert_eq!(churn(singleton(1, 0), 10), 11);
+    }
+
+    #[test]
+    fn flat_urn_implements_weighted_sampler() {
+        assert_eq!(churn(singleton(1, 0).flatten(), 10), 11);
+    }
+
+    #[test]
+    fn alias_table_implements_weighted_sampler() {
+        assert_eq!(churn(AliasTable::from_list(vec![(1, 0)]), 10), 11);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::types::{widen, Index, Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                           Lookups by value                                 */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone + PartialEq> Urn<T> {
+    /// Whether the urn contains an element equal to `a`.
+    /// Time complexity: `O(n)`.
+    pub fn contains(&self, a: &T) -> bool {
+        self.iter().any(|(_, b)| b == a)
+    }
+
+    /// The (leaf) position of the first element equal to `a`, in leaf order
+    /// (`None` if there is no such element). The position can be passed to
+    /// `remove_position`.
+    /// Time complexity: `O(n)`.
+    pub fn position(&self, a: &T) -> Option<u32> {
+        self.iter().position(|(_, b)| b == a).map(|pos| pos as u32)
+    }
+
+    /// The total weight of all the elements equal to `a`
+    /// (0 if there are none).
+    /// Time complexity: `O(n)`.
+    pub fn weight_of(&self, a: &T) -> Index {
+        self.iter()
+            .filter(|(_, b)| *b == a)
+            .fold(0, |total: Index, (w, _)| total.wrapping_add(widen(w)))
+    }
+
+    /// Removes the first element equal to `a` (see `position`), returning
+    /// it along with its weight, and the resultant urn in an `Option`
+    /// (which is `None` if the urn is empty).
+    /// Returns the urn unchanged in an `Err` if there is no such element.
+    /// Time complexity: `O(n)` to find the element, then `O(log n)`
+    /// to remove it (see `remove_position`).
+    pub fn remove_value(
+        self,
+        a: &T,
+    ) -> Result<((Weight, T), Option<Self>), Self> {
+        match self.position(a) {
+            Some(pos) => Ok(self.remove_position(pos)),
+            None => Err(self),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::from_list;
+
+    #[test]
+    fn lookups_by_value() {
+        let urn =
+            from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (4, 'R')]).unwrap();
+        assert!(urn.contains(&'G'));
+        assert!(!urn.contains(&'Y'));
+        assert_eq!(urn.position(&'B'), Some(2));
+        assert_eq!(urn.weight_of(&'R'), 6);
+        assert_eq!(urn.weight_of(&'Y'), 0);
+
+        let urn = urn.remove_value(&'Y').unwrap_err();
+        let ((w, a), urn) = urn.remove_value(&'R').unwrap();
+        assert_eq!((w, a), (2, 'R'));
+        let urn = urn.unwrap();
+        assert_eq!(urn.weight_of(&'R'), 4);
+        assert_eq!(urn.size(), 3);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    almost_perfect::almost_perfect,
+    types::{
+        Tree::{self, *},
+        Urn,
+    },
+};
+
+/* -------------------------------------------------------------------------- */
+/*                          The shapes of urns' trees                         */
+/* -------------------------------------------------------------------------- */
+
+/// Statistics about the depths of the leaves of an urn's tree
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DepthStats {
+    /// The depth of the shallowest leaf (the root has depth 0)
+    pub min: u32,
+    /// The depth of the deepest leaf
+    pub max: u32,
+    /// The average depth of a leaf
+    pub mean: f64,
+}
+
+impl<T: Clone> Tree<T> {
+    /// Computes the minimum, maximum and mean depth of the leaves of the tree
+    pub(crate) fn depth_stats(&self) -> DepthStats {
+        let (mut min, mut max, mut total, mut leaves) = (u32::MAX, 0, 0, 0u64);
+        let mut stack = vec![(self, 0)];
+        while let Some((tree, depth)) = stack.pop() {
+            match tree {
+                Leaf(_, _) => {
+                    min = min.min(depth);
+                    max = max.max(depth);
+                    total += u64::from(depth);
+                    leaves += 1;
+                }
+                Node(_, _, _, l, r) => {
+                    stack.push((r, depth + 1));
+                    stack.push((l, depth + 1));
+                }
+            }
+        }
+        DepthStats {
+            min,
+            max,
+            mean: total as f64 / leaves as f64,
+        }
+    }
+
+    /// Counts the leaves of the tree
+    fn leaf_count(&self) -> u64 {
+        let (mut leaves, mut stack) = (0, vec![self]);
+        while let Some(tree) = stack.pop() {
+            match tree {
+                Leaf(_, _) => leaves += 1,
+                Node(_, _, _, l, r) => {
+                    stack.push(r);
+                    stack.push(l);
+                }
+            }
+        }
+        leaves
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// The depth of the urn's tree, i.e. the depth of its deepest leaf
+    /// (the root has depth 0).
+    /// Since the left subtree of every node has at least as many leaves as
+    /// the right one (see `validate`), the deepest leaf is the leftmost one,
+    /// so only the leftmost path is walked. (Use `stats` to measure the
+    /// depths of all leaves, e.g. for urns whose trees were modified directly.)
+    /// Time complexity: `O(log n)`.
+    pub fn depth(&self) -> u32 {
+        let (mut tree, mut depth) = (&self.tree, 0);
+        while let Node(_, _, _, l, _) = tree {
+            tree = l;
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Counts the leaves of the urn's tree (which should be equal to `size`).
+    /// Time complexity: `O(n)`.
+    pub fn leaf_count(&self) -> u64 {
+        self.tree.leaf_count()
+    }
+
+    /// Estimates the no. of bytes the urn's tree allocates on the heap
+    /// (excluding any heap memory owned by the elements themselves, see
+    /// `heap_size_estimate_with`). Every node and leaf except the root is
+    /// boxed, and a tree with `n` leaves has `2n - 1` of them, so this is
+    /// `(2n - 2) * size_of::<Tree<T>>()`. The allocator's own overhead
+    /// (e.g. rounding up allocations) isn't included.
+    /// Time complexity: `O(1)`.
+    pub fn heap_size_estimate(&self) -> usize {
+        let boxed = 2 * (self.size as usize).saturating_sub(1);
+        boxed * std::mem::size_of::<Tree<T>>()
+    }
+
+    /// Like `heap_size_estimate`, but also adds the heap memory owned by
+    /// each element `a`, as estimated by `elem_heap_size(&a)` (e.g.
+    /// `String::capacity` for urns of `String`s).
+    /// Time complexity: `O(n)`.
+    pub fn heap_size_estimate_with<F>(&self, mut elem_heap_size: F) -> usize
+    where
+        F: FnMut(&T) -> usize,
+    {
+        self.iter()
+            .fold(self.heap_size_estimate(), |total, (_, a)| {
+                total + elem_heap_size(a)
+            })
+    }
+
+    /// Checks whether the urn's tree is *almost perfect*, i.e. whether the
+    /// depths of any two leaves differ by at most one (which makes sampling
+    /// take `O(log n)` time). This holds for all well-formed urns, no matter
+    /// which operations built them.
+    /// Time complexity: `O(n)`.
+    pub fn is_almost_perfect(&self) -> bool {
+        let DepthStats { min, max, .. } = self.stats();
+        max - min <= 1
+    }
+
+    /// Computes the minimum, maximum and mean depth of the leaves of the
+    /// urn's tree (see also `audit`).
+    /// Time complexity: `O(n)`.
+    pub fn stats(&self) -> DepthStats {
+        self.tree.depth_stats()
+    }
+
+    /// Rebuilds the urn's tree into an almost perfect tree (see `from_list`)
+    /// if it violates any of the urn's invariants (see `validate`), e.g.
+    /// after its tree was modified directly, and returns whether the tree
+    /// was rebuilt. The elements are moved into the new tree (only the first
+    /// one is cloned, as a placeholder while the old tree is taken apart),
+    /// and the urn's `size` is reset to its no. of leaves.
+    /// Note: `insert` and the various `remove`s keep the tree in the same
+    /// shape as a fresh `from_list`, however long the sequence of operations,
+    /// so well-formed urns never need to be rebalanced (and are left as is).
+    /// Time complexity: `O(n)`.
+    pub fn rebalance(&mut self) -> bool {
+        if self.validate().is_ok() {
+            return false;
+        }
+        let (_, first) = self.iter().next().expect("urns are non-empty");
+        let placeholder = Leaf(0, first.clone());
+        let mut elems = Vec::with_capacity(self.size as usize);
+        std::mem::replace(&mut self.tree, placeholder).into_leaves(&mut elems);
+        self.size = elems.len() as u32;
+        self.tree = almost_perfect(elems);
+        true
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Weight;
+    use crate::urn::{from_list, singleton};
+
+    #[test]
+    fn shape_example() {
+        let urn = singleton(1, 'a');
+        assert_eq!((urn.depth(), urn.leaf_count()), (0, 1));
+        assert!(urn.is_almost_perfect());
+
+        let urn = from_list((0..5).map(|k| (1, k)).collect()).unwrap();
+        assert_eq!((urn.depth(), urn.leaf_count()), (3, 5));
+        assert_eq!(
+            urn.stats(),
+            DepthStats {
+                min: 2,
+                max: 3,
+                mean: 12.0 / 5.0
+            }
+        );
+        assert!(urn.is_almost_perfect());
+
+        let mut urn = urn;
+        if let Node(_, _, _, l, _) = &mut urn.tree {
+            let lopsided = Tree::node(l.clone(), Box::new(Leaf(1, 5)));
+            **l = lopsided;
+        }
+        assert_eq!(urn.leaf_count(), 6);
+        assert!(!urn.is_almost_perfect());
+
+        let elems = urn.to_vec();
+        assert!(urn.rebalance());
+        assert!(urn.validate().is_ok() && urn.is_almost_perfect());
+        assert_eq!((urn.size(), urn.to_vec()), (6, elems));
+        assert!(!urn.rebalance());
+    }
+
+    // The tree stays almost perfect after long sequences of insertions and
+    // removals
+    #[test]
+    fn churn_keeps_urns_almost_perfect() {
+        let mut urn = singleton(1, 0);
+        for k in 1..1000 {
+            urn = urn.insert(1 + (k % 7) as Weight, k);
+            if k % 3 == 0 {
+                let pos = k % urn.size();
+                urn = urn.remove_position(pos).1.unwrap();
+            }
+        }
+        assert_eq!(u64::from(urn.size()), urn.leaf_count());
+        assert_eq!(urn.depth(), urn.stats().max);
+        assert!(urn.is_almost_perfect());
+        assert!(!urn.rebalance());
+    }
+
+    #[test]
+    fn heap_size_estimate_counts_boxed_trees() {
+        assert_eq!(singleton(1, 'a').heap_size_estimate(), 0);
+        let urn = from_list((0..5).map(|k| (1, k.to_string())).collect());
+        let urn = urn.unwrap();
+        let tree_size = std::mem::size_of::<Tree<String>>();
+        assert_eq!(urn.heap_size_estimate(), 8 * tree_size);
+        assert_eq!(urn.heap_size_estimate_with(String::len), 8 * tree_size + 5);
+    }
+}
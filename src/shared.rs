@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use rand_core::RngCore;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::{from_list, singleton},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                          Urns of shared payloads                           */
+/* -------------------------------------------------------------------------- */
+
+/// An urn whose elements are reference-counted (`Arc`) payloads.
+///
+/// Cloning an `Arc` only bumps its reference count, so every operation that
+/// clones elements (e.g. `sample`, or the copies made by the borrowing
+/// methods `replace` and `adjust`, and by cloning whole urns) is cheap no
+/// matter how large the payloads are, and all copies share one payload.
+pub type SharedUrn<T> = Urn<Arc<T>>;
+
+/// Creates a singleton `SharedUrn` containing `a` (or the payload it
+/// points to) with weight `w`.
+/// Time complexity: `O(1)`.
+pub fn singleton_shared<T>(w: Weight, a: impl Into<Arc<T>>) -> SharedUrn<T> {
+    singleton(w, a.into())
+}
+
+/// Builds a `SharedUrn` from (weight, element) pairs, moving each element
+/// into its own `Arc` (see `from_list`).
+/// Returns `None` if `elems` is empty.
+/// Time complexity: `O(n)`.
+pub fn from_list_shared<T>(elems: Vec<(Weight, T)>) -> Option<SharedUrn<T>> {
+    from_list(elems.into_iter().map(|(w, a)| (w, Arc::new(a))).collect())
+}
+
+impl<T> Urn<Arc<T>> {
+    /// Inserts an element with weight `w` (see `insert`). `a` may either be
+    /// a plain element, which is moved into a new `Arc`, or an existing
+    /// `Arc`, whose payload is then shared with the urn.
+    /// Time complexity: `O(log n)`.
+    pub fn insert_shared(self, w: Weight, a: impl Into<Arc<T>>) -> Self {
+        self.insert(w, a.into())
+    }
+
+    /// Randomly samples an element using `rng`, returning a new reference to
+    /// its payload (without cloning the payload itself).
+    /// Time complexity: `O(log n)`.
+    pub fn sample_shared<R: RngCore + ?Sized>(&self, rng: &mut R) -> Arc<T> {
+        Arc::clone(self.sample_ref(rng))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn shared_urn_example() {
+        let big = Arc::new(vec![0u8; 1 << 16]);
+        let urn = from_list_shared(vec![(1, vec![1u8])])
+            .unwrap()
+            .insert_shared(3, Arc::clone(&big))
+            .insert_shared(0, vec![2u8]);
+        assert_eq!(urn.size(), 3);
+
+        // Cloning the urn and sampling from it only share the payload
+        let copy = urn.clone();
+        assert_eq!(Arc::strong_count(&big), 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        let sampled: Vec<Arc<Vec<u8>>> =
+            (0..100).map(|_| copy.sample_shared(&mut rng)).collect();
+        assert!(sampled
+            .iter()
+            .all(|a| a.len() != 1 << 16 || Arc::ptr_eq(a, &big)));
+        assert!(sampled.iter().all(|a| **a != vec![2u8]));
+
+        let ((w, removed), rest) = urn.remove_position(2);
+        assert!(w == 3 && Arc::ptr_eq(&removed, &big));
+        assert!(rest.unwrap().iter().all(|(_, a)| !Arc::ptr_eq(a, &big)));
+        assert_eq!(
+            singleton_shared(1, 'a').sample_shared(&mut rng),
+            Arc::new('a')
+        );
+    }
+}
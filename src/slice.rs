@@ -0,0 +1,131 @@
+use rand_core::RngCore;
+
+use crate::{
+    random::below_u128,
+    types::{Urn, Weight},
+    urn::{from_iter_exact, from_list},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                          Slice extension methods                           */
+/* -------------------------------------------------------------------------- */
+
+/// Extension methods for slices of `(weight, element)` pairs, mirroring
+/// `rand::seq::SliceRandom::choose_weighted`, so that code which starts out
+/// choosing from a fixed slice can later switch to an `Urn` (via `to_urn`)
+/// once it needs to update the distribution.
+pub trait UrnSliceExt<T> {
+    /// Chooses (a reference to) one of the pairs using `rng`, with
+    /// probability proportional to its weight (without building an urn).
+    /// Returns `None` if the slice is empty or all its weights are 0.
+    /// Time complexity: `O(n)`.
+    fn choose_weighted_urn<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<&(Weight, T)>;
+
+    /// Builds an urn containing (clones of) the pairs in the slice,
+    /// or `None` if the slice is empty.
+    /// Time complexity: `O(n)`.
+    fn to_urn(&self) -> Option<Urn<T>>
+    where
+        T: Clone;
+}
+
+impl<T> UrnSliceExt<T> for [(Weight, T)] {
+    fn choose_weighted_urn<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<&(Weight, T)> {
+        // The total is a `u128`, since it may exceed `Index::MAX`
+        let total: u128 = self.iter().map(|(w, _)| u128::from(*w)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut i = below_u128(rng, total);
+        self.iter().find(|(w, _)| {
+            let w = u128::from(*w);
+            if i < w {
+                return true;
+            }
+            i -= w;
+            false
+        })
+    }
+
+    fn to_urn(&self) -> Option<Urn<T>>
+    where
+        T: Clone,
+    {
+        from_list(self.to_vec())
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                          Urns of borrowed elements                         */
+/* -------------------------------------------------------------------------- */
+
+impl<'a, T> Urn<&'a T> {
+    /// Builds an urn of references to the elements of `elems`, or `None` if
+    /// the slice is empty. The elements are never cloned (only the references
+    /// are copied), so this suits read-mostly sampling from large static
+    /// tables, e.g. a `static` word-frequency table baked into the binary.
+    /// Time complexity: `O(n)`.
+    pub fn from_slice(elems: &'a [(Weight, T)]) -> Option<Self> {
+        from_iter_exact(elems.iter().map(|(w, a)| (*w, a)))
+    }
+
+    /// Clones the referenced elements into an urn which owns them,
+    /// keeping the shape of the tree (see `update_all`).
+    /// Time complexity: `O(n)`.
+    pub fn cloned(self) -> Urn<T>
+    where
+        T: Clone,
+    {
+        self.update_all(|w, a| (w, a.clone()))
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn choose_weighted_urn_skips_zero_weights() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let elems = [(0, 'a'), (2, 'b'), (0, 'c'), (1, 'd')];
+        let mut seen = vec![];
+        for _ in 0..100 {
+            let (w, a) = elems.choose_weighted_urn(&mut rng).unwrap();
+            assert!(*w > 0);
+            seen.push(*a);
+        }
+        assert!(seen.contains(&'b') && seen.contains(&'d'));
+        assert_eq!([(0, 'a')].choose_weighted_urn(&mut rng), None);
+        assert_eq!(
+            <[(Weight, char)]>::choose_weighted_urn(&[], &mut rng),
+            None
+        );
+
+        let urn = elems.to_urn().unwrap();
+        assert_eq!(urn.to_vec(), elems.to_vec());
+        assert_eq!(elems[..0].to_urn(), None);
+    }
+
+    #[test]
+    fn from_slice_borrows_elements() {
+        static WORDS: [(Weight, &str); 3] = [(5, "the"), (3, "of"), (2, "urn")];
+        let urn = Urn::from_slice(&WORDS).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let word: &&str = urn.sample_ref(&mut rng);
+        assert!(WORDS.iter().any(|(_, a)| std::ptr::eq(a, word)));
+        assert_eq!(urn.weight(), 10);
+        assert_eq!(urn.cloned().to_vec(), WORDS.to_vec());
+        assert_eq!(Urn::<&char>::from_slice(&[]), None);
+    }
+}
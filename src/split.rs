@@ -0,0 +1,121 @@
+use rand_core::RngCore;
+
+use crate::{
+    random::below_u64,
+    types::{Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                           Splitting urns apart                             */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone> Urn<T> {
+    /// Splits the urn into two urns: the first contains the elements `a`
+    /// (with weight `w`) for which `pred(w, &a)` holds, and the second
+    /// contains the remaining elements. Either urn is `None` if it would
+    /// be empty.      
+    /// Both urns are rebuilt as almost perfect trees, and no element
+    /// is cloned.      
+    /// Time complexity: `O(n)`.
+    pub fn partition<F>(self, mut pred: F) -> (Option<Self>, Option<Self>)
+    where
+        F: FnMut(Weight, &T) -> bool,
+    {
+        let (yes, no): (Vec<_>, Vec<_>) =
+            self.into_vec().into_iter().partition(|(w, a)| pred(*w, a));
+        let (yes, no) = (from_list(yes), from_list(no));
+        for urn in [&yes, &no].into_iter().flatten() {
+            urn.debug_check_invariants();
+        }
+        (yes, no)
+    }
+
+    /// Splits the urn into the elements whose weight is at least `min_w`
+    /// (the heavy elements) and the remaining (light) elements,
+    /// e.g. to sample hot and cold entries differently.
+    /// Either urn is `None` if it would be empty (see `partition`).
+    /// Time complexity: `O(n)`.
+    pub fn split_off_heavy(
+        self,
+        min_w: Weight,
+    ) -> (Option<Self>, Option<Self>) {
+        self.partition(|w, _| w >= min_w)
+    }
+
+    /// Randomly partitions the elements into `k` urns (e.g. the folds of a
+    /// cross-validation split): each element is sent to one of the `k` urns,
+    /// drawn uniformly at random using `rng`, independently of the other
+    /// elements. Elements keep their weights, so every urn receives
+    /// `1 / k` of the total weight in expectation.
+    /// The `j`-th urn is `None` if no element was sent to it.
+    /// The urns are rebuilt as almost perfect trees, and no element
+    /// is cloned.
+    /// Panics if `k` is 0.
+    /// Time complexity: `O(n + k)`.
+    pub fn split_k<R: RngCore + ?Sized>(
+        self,
+        k: usize,
+        rng: &mut R,
+    ) -> Vec<Option<Self>> {
+        assert!(k > 0, "cannot split an urn into 0 urns");
+        let mut folds: Vec<Vec<(Weight, T)>> = vec![Vec::new(); k];
+        for (w, a) in self.into_vec() {
+            folds[below_u64(rng, k as u64) as usize].push((w, a));
+        }
+        folds.into_iter().map(from_list).collect()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::{types::Weight, urn::from_list};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn partition_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let (warm, cool) = urn.clone().partition(|_, a| *a == 'R');
+        assert_eq!(warm.map(|u| u.to_vec()), Some(vec![(2, 'R')]));
+        assert_eq!(cool.map(|u| u.to_vec()), Some(vec![(4, 'G'), (3, 'B')]));
+
+        let (heavy, light) = urn.partition(|w, _| w > 10);
+        assert!(heavy.is_none());
+        assert_eq!(light.map(|u| u.size()), Some(3));
+    }
+
+    #[test]
+    fn split_off_heavy_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let (heavy, light) = urn.clone().split_off_heavy(3);
+        assert_eq!(heavy.map(|u| u.to_vec()), Some(vec![(4, 'G'), (3, 'B')]));
+        assert_eq!(light.map(|u| u.to_vec()), Some(vec![(2, 'R')]));
+        assert_eq!(urn.clone().split_off_heavy(0).1, None);
+        assert_eq!(urn.split_off_heavy(5).0, None);
+    }
+
+    #[test]
+    fn split_k_keeps_every_element_once() {
+        let elems: Vec<(Weight, u32)> =
+            (0..100).map(|k| (k as Weight % 5, k)).collect();
+        let urn = from_list(elems.clone()).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let folds = urn.clone().split_k(4, &mut rng);
+        assert_eq!(folds.len(), 4);
+        assert!(folds.iter().all(|fold| fold.is_some()));
+        let mut found: Vec<(Weight, u32)> = folds
+            .into_iter()
+            .flatten()
+            .flat_map(|u| u.into_vec())
+            .collect();
+        found.sort_by_key(|&(_, a)| a);
+        assert_eq!(found, elems);
+
+        let folds = urn.split_k(1, &mut rng);
+        assert_eq!(folds[0].as_ref().map(|u| u.size()), Some(100));
+    }
+}
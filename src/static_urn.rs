@@ -0,0 +1,132 @@
+use rand_core::RngCore;
+
+use crate::{
+    types::{Index, Urn, Weight},
+    urn::{from_iter_exact, sample_weight_with},
+};
+
+/// A fixed weighted table of `N` elements which can be built at compile
+/// time, e.g. as a `static` (see the `static_urn!` macro), so that firmware
+/// can sample from a ROM-resident table with no startup cost.
+///
+/// The table is flat: the elements are stored in an array alongside the
+/// cumulative sums of their weights, which `new` computes in a `const fn`,
+/// and sampling binary-searches the cumulative sums. Nothing is allocated,
+/// and the table can't be updated (use `to_urn` to get an updatable `Urn`).
+///
+/// Time complexity: `new` takes `O(N)` (at compile time, in `const`s and
+/// `static`s), and `sample` takes `O(log N)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StaticUrn<T, const N: usize> {
+    elems: [(Weight, T); N],
+    /// `cumulative[k]` is the total weight of the elements at indexes
+    /// `0..=k`
+    cumulative: [Index; N],
+}
+
+impl<T, const N: usize> StaticUrn<T, N> {
+    /// Builds a table of the (weight, element) pairs in `elems`.
+    /// Panics (or fails to compile, when evaluated at compile time) if `N`
+    /// is 0 or if the total weight exceeds `Index::MAX`.
+    pub const fn new(elems: [(Weight, T); N]) -> Self {
+        assert!(N > 0, "a `StaticUrn` needs at least one element");
+        let mut cumulative = [0; N];
+        let (mut k, mut total): (usize, Index) = (0, 0);
+        while k < N {
+            // `as` is lossless, since `Index` is at least as wide as `Weight`
+            total = match total.checked_add(elems[k].0 as Index) {
+                Some(total) => total,
+                None => panic!("the total weight of a `StaticUrn` overflows"),
+            };
+            cumulative[k] = total;
+            k += 1;
+        }
+        Self { elems, cumulative }
+    }
+
+    /// The no. of elements
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Whether the table contains no elements (always `false`)
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// The total weight of all the elements
+    pub const fn weight(&self) -> Index {
+        self.cumulative[N - 1]
+    }
+
+    /// The (weight, element) pairs of the table, in order
+    pub const fn elems(&self) -> &[(Weight, T)] {
+        &self.elems
+    }
+
+    /// Retrieves the weight and (a reference to) the element whose bucket
+    /// contains the index `i`.
+    /// Panics if `i >= self.weight()`.
+    pub fn lookup_index(&self, i: Index) -> (Weight, &T) {
+        assert!(i < self.weight(), "index out of bounds");
+        let k = self.cumulative.partition_point(|&c| c <= i);
+        let (w, a) = &self.elems[k];
+        (*w, a)
+    }
+
+    /// Randomly samples (a reference to) an element using `rng`, with
+    /// probability proportional to its weight.
+    /// Panics if all weights are 0.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        assert!(self.weight() > 0, "cannot sample when all weights are 0");
+        self.lookup_index(sample_weight_with(rng, self.weight())).1
+    }
+
+    /// Builds an `Urn` containing (clones of) the table's elements.
+    /// Time complexity: `O(N)`.
+    pub fn to_urn(&self) -> Urn<T>
+    where
+        T: Clone,
+    {
+        from_iter_exact(self.elems.iter().cloned())
+            .expect("`StaticUrn`s are non-empty")
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    static COLORS: StaticUrn<&str, 3> =
+        StaticUrn::new([(3, "red"), (1, "blue"), (6, "green")]);
+
+    #[test]
+    fn static_urn_example() {
+        assert_eq!((COLORS.len(), COLORS.weight()), (3, 10));
+        let buckets: Vec<&str> =
+            (0..10).map(|i| *COLORS.lookup_index(i).1).collect();
+        assert_eq!(
+            buckets,
+            vec![
+                "red", "red", "red", "blue", "green", "green", "green",
+                "green", "green", "green"
+            ]
+        );
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(["red", "blue", "green"].contains(COLORS.sample(&mut rng)));
+        assert_eq!(COLORS.to_urn().to_vec(), COLORS.elems().to_vec());
+    }
+
+    #[test]
+    fn static_urn_skips_zero_weights() {
+        const URN: StaticUrn<char, 4> =
+            StaticUrn::new([(0, 'a'), (2, 'b'), (0, 'c'), (1, 'd')]);
+        let found: Vec<char> = (0..3).map(|i| *URN.lookup_index(i).1).collect();
+        assert_eq!(found, vec!['b', 'b', 'd']);
+    }
+}
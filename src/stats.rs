@@ -0,0 +1,557 @@
+use rand_core::RngCore;
+
+use crate::types::{Index, Tree::*, Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                          Exact draw distributions                          */
+/* -------------------------------------------------------------------------- */
+
+/// The expected no. of times an element is drawn in `n` independent draws
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpectedCount<'a, T> {
+    /// The element's weight
+    pub weight: Weight,
+    /// The element itself
+    pub elem: &'a T,
+    /// The exact expected count `n * weight / total_weight`
+    pub expected: f64,
+    /// The expected count rounded to an integer, such that the rounded
+    /// counts of all elements sum to `n`
+    pub rounded: u64,
+}
+
+/// The exact outcome distribution of `draws` independent draws from an urn,
+/// created by `Urn::simulate_draw_distribution`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawDistribution<'a, T> {
+    /// The no. of draws
+    pub draws: u64,
+    /// The expected count of each element (in leaf order)
+    pub counts: Vec<ExpectedCount<'a, T>>,
+    /// The largest difference `|rounded - expected|` over all elements
+    /// (always less than 1)
+    pub max_rounding_error: f64,
+}
+
+impl<T: Clone> Urn<T> {
+    /// Computes the exact expected no. of times each element is drawn in
+    /// `n` independent draws (i.e. `n * p_i` for each element `i`) without
+    /// sampling at all.     
+    /// The expected counts are also rounded to integers using the
+    /// largest remainder method, so that the rounded counts sum to `n`
+    /// (unless the urn's total weight is 0, in which case all counts are 0).
+    /// Time complexity: `O(n log n)`.
+    pub fn simulate_draw_distribution(
+        &self,
+        n: u64,
+    ) -> DrawDistribution<'_, T> {
+        // We do all the arithmetic on `u128`s so that it is exact
+        let total = self.exact_weight();
+        let mut counts = Vec::with_capacity(self.size() as usize);
+        let mut remainders = Vec::with_capacity(self.size() as usize);
+        let mut allocated: u128 = 0;
+        for (i, (w, elem)) in self.iter().enumerate() {
+            let scaled = u128::from(n) * u128::from(w);
+            let floor = scaled.checked_div(total).unwrap_or(0);
+            let rem = scaled.checked_rem(total).unwrap_or(0);
+            allocated += floor;
+            remainders.push((rem, i));
+            counts.push(ExpectedCount {
+                weight: w,
+                elem,
+                expected: if total == 0 {
+                    0.0
+                } else {
+                    scaled as f64 / total as f64
+                },
+                rounded: floor as u64,
+            });
+        }
+
+        // Hand out the remaining draws to the elements with the
+        // largest remainders (breaking ties by leaf order)
+        if total != 0 {
+            let leftover = (u128::from(n) - allocated) as usize;
+            remainders
+                .sort_by(|(r1, i1), (r2, i2)| r2.cmp(r1).then(i1.cmp(i2)));
+            for &(_, i) in remainders.iter().take(leftover) {
+                counts[i].rounded += 1;
+            }
+        }
+
+        let max_rounding_error = counts
+            .iter()
+            .map(|c| (c.rounded as f64 - c.expected).abs())
+            .fold(0.0, f64::max);
+        DrawDistribution {
+            draws: n,
+            counts,
+            max_rounding_error,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                            CDF & quantile queries                          */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone> Urn<T> {
+    /// Computes the total weight of the elements that come before the
+    /// element at (leaf) position `pos`, i.e. the lower bound of the bucket
+    /// of indexes that map to that element. Together with `quantile`, this
+    /// allows one to inspect the CDF of the distribution.     
+    /// Since the shape of an urn's tree only depends on its size, the left
+    /// subtree of a node with `n` leaves always has `(n + 1) / 2` leaves,
+    /// so we can descend directly to the `pos`-th leaf.       
+    /// Panics if `pos >= self.size()`.     
+    /// Time complexity: `O(log n)`.
+    pub fn cumulative_weight_before(&self, pos: u32) -> Index {
+        assert!(
+            pos < self.size(),
+            "position {} out of bounds for urn of size {}",
+            pos,
+            self.size()
+        );
+        let (mut tree, mut n, mut pos) = (&self.tree, self.size(), pos);
+        let mut lb: Index = 0;
+        while let Node(_, _, _, l, r) = tree {
+            let nl = n.div_ceil(2);
+            if pos < nl {
+                (tree, n) = (l, nl);
+            } else {
+                lb = lb.wrapping_add(l.weight());
+                (tree, n, pos) = (r, n - nl, pos - nl);
+            }
+        }
+        lb
+    }
+
+    /// Computes the (leaf) position of the element whose bucket contains
+    /// the index `i` (indexes beyond the urn's total weight map to the
+    /// last leaf, as with `sample_index`).      
+    /// Time complexity: `O(log n)`.
+    pub(crate) fn position_of_index(&self, i: Index) -> u32 {
+        let (mut tree, mut n, mut i) = (&self.tree, self.size(), i);
+        let mut pos = 0;
+        while let Node(_, _, _, l, r) = tree {
+            let nl = n.div_ceil(2);
+            let wl = l.weight();
+            if i < wl {
+                (tree, n) = (l, nl);
+            } else {
+                pos += nl;
+                (tree, n, i) = (r, n - nl, i - wl);
+            }
+        }
+        pos
+    }
+
+    /// Returns the element which covers the cumulative probability `p`,
+    /// i.e. the element whose bucket contains the index `floor(p * W)`
+    /// (where `W` is the urn's total weight).     
+    /// Panics if `p` is not in the range `[0, 1]`.      
+    /// Time complexity: `O(log n)`.
+    pub fn quantile(&self, p: f64) -> &T {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "quantile {} is not in the range [0, 1]",
+            p
+        );
+        let w = self.weight();
+        // Clamp the index so that `p = 1` maps to the last non-empty bucket
+        let i = ((p * w as f64) as Index).min(w.saturating_sub(1));
+        self.sample_index_ref(i)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                Probabilities                               */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone> Urn<T> {
+    /// Iterates over the elements of the urn (in leaf order), yielding
+    /// the exact probability `w / W` with which each element is sampled,
+    /// where `w` is its weight and `W` is the urn's exact total weight
+    /// (see `exact_weight`, so the probabilities sum to 1 even if `weight`
+    /// has wrapped around).      
+    /// (If the total weight is 0, every probability is NaN.)      
+    /// Time complexity: `O(n)`.
+    pub fn probabilities(&self) -> impl Iterator<Item = (f64, &T)> {
+        let total = self.exact_weight() as f64;
+        self.iter().map(move |(w, a)| (w as f64 / total, a))
+    }
+
+    /// Returns the probability of sampling the element whose bucket
+    /// contains the index `i`, relative to the urn's exact total weight
+    /// (see `exact_weight`).      
+    /// Panics if `i >= self.exact_weight()`.      
+    /// Time complexity: `O(n)` (to compute the exact total weight).
+    pub fn prob_of_index(&self, i: Index) -> f64 {
+        let total = self.exact_weight();
+        assert!(
+            u128::from(i) < total,
+            "index {} out of bounds for urn of weight {}",
+            i,
+            total
+        );
+        let (w, _) = self.tree.lookup_index(i);
+        w as f64 / total as f64
+    }
+
+    /// Computes the Shannon entropy (in bits) of the distribution represented
+    /// by the urn, i.e. `-sum(p * log2(p))` over the probabilities `p` of
+    /// all elements (elements with weight 0 contribute nothing).      
+    /// Time complexity: `O(n)`.
+    pub fn entropy(&self) -> f64 {
+        self.probabilities()
+            .filter(|(p, _)| *p > 0.0)
+            .map(|(p, _)| -p * p.log2())
+            .sum()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                              Goodness of fit                               */
+/* -------------------------------------------------------------------------- */
+
+/// The result of a chi-square goodness-of-fit test,
+/// created by `Urn::chi_square`
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChiSquare {
+    /// The test statistic `sum((observed - expected)^2 / expected)`
+    pub statistic: f64,
+    /// The no. of degrees of freedom (one less than the no. of elements
+    /// with a positive weight)
+    pub degrees_of_freedom: u32,
+    /// The probability of a statistic at least this large if the observed
+    /// counts really were drawn from the urn (small p-values, e.g. below
+    /// 0.001, indicate that the counts are biased)
+    pub p_value: f64,
+}
+
+/// Computes `ln(Gamma(x))` for `x > 0` using the Lanczos approximation
+/// (with the coefficients from Numerical Recipes, accurate to about 1e-10)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |acc, (j, c)| {
+            acc + c / (x + 1.0 + j as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Computes the regularized upper incomplete gamma function `Q(a, x)`
+/// for `a > 0` and `x >= 0`, using its series expansion when `x < a + 1`
+/// and its continued fraction expansion otherwise
+/// (see section 6.2 of Numerical Recipes)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const MAX_ITERS: usize = 1000;
+    const EPS: f64 = 1e-14;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut ap) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..MAX_ITERS {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * EPS {
+                break;
+            }
+        }
+        1.0 - sum * prefactor
+    } else {
+        // Modified Lentz's method
+        let tiny = f64::MIN_POSITIVE / EPS;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_ITERS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPS {
+                break;
+            }
+        }
+        prefactor * h
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Draws `n` samples from the urn using `rng`, and counts how many times
+    /// each element was drawn. The `i`-th count belongs to the element at
+    /// (leaf) position `i` (i.e. the `i`-th element yielded by `iter`).      
+    /// Time complexity: `O(size + n log size)`.
+    pub fn empirical_counts<R: RngCore + ?Sized>(
+        &self,
+        n: u64,
+        rng: &mut R,
+    ) -> Vec<u64> {
+        let mut counts = vec![0; self.size() as usize];
+        for _ in 0..n {
+            let pos = self.position_of_index(self.draw_index(rng));
+            counts[pos as usize] += 1;
+        }
+        counts
+    }
+
+    /// Performs a chi-square goodness-of-fit test comparing the `observed`
+    /// counts (where the `i`-th count belongs to the element at leaf
+    /// position `i`, as returned by `empirical_counts`) against the counts
+    /// expected from the urn's exact probabilities.      
+    /// Elements with weight 0 are excluded from the test (if any of them
+    /// were observed, the statistic is infinite and the p-value is 0).      
+    /// Panics if `observed.len() != self.size()`.      
+    /// Time complexity: `O(n)`.
+    pub fn chi_square(&self, observed: &[u64]) -> ChiSquare {
+        assert_eq!(
+            observed.len(),
+            self.size() as usize,
+            "expected one observed count per element"
+        );
+        let total = self.exact_weight() as f64;
+        let draws: u64 = observed.iter().sum();
+        let mut statistic = 0.0;
+        let mut categories = 0;
+        for ((w, _), &o) in self.iter().zip(observed) {
+            if w == 0 {
+                if o > 0 {
+                    statistic = f64::INFINITY;
+                }
+                continue;
+            }
+            categories += 1;
+            let expected = draws as f64 * w as f64 / total;
+            statistic += (o as f64 - expected).powi(2) / expected;
+        }
+        let degrees_of_freedom = categories.max(1) - 1;
+        let p_value = if statistic.is_infinite() {
+            0.0
+        } else if degrees_of_freedom == 0 {
+            1.0
+        } else {
+            gamma_q(f64::from(degrees_of_freedom) / 2.0, statistic / 2.0)
+                .clamp(0.0, 1.0)
+        };
+        ChiSquare {
+            statistic,
+            degrees_of_freedom,
+            p_value,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                             Capacity planning                              */
+/* -------------------------------------------------------------------------- */
+
+/// The outcome of inserting a planned batch of weights into an urn,
+/// created by `Urn::plan_insert`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CapacityPlan {
+    /// The urn's headroom before the batch is inserted
+    /// (`None` if the urn's total weight has already overflowed)
+    pub headroom_before: Option<Index>,
+    /// The sum of the planned weights
+    pub planned_weight: u128,
+    /// The urn's headroom after the batch is inserted
+    /// (`None` if inserting the batch would overflow the total weight)
+    pub headroom_after: Option<Index>,
+    /// By how much the total weight would exceed `Index::MAX`
+    /// after inserting the batch (0 if it fits)
+    pub overflow: u128,
+}
+
+impl<T: Clone> Urn<T> {
+    /// The exact total weight of the urn, which unlike `weight` doesn't wrap
+    /// around when the total doesn't fit in an `Index`.
+    /// Time complexity: `O(n)`.
+    pub fn exact_weight(&self) -> u128 {
+        self.iter().map(|(w, _)| u128::from(w)).sum()
+    }
+
+    /// How much additional total weight can be inserted into the urn before
+    /// its total weight overflows `Index` (after which the weights cached in
+    /// the tree wrap around, and sampling is no longer proportional).
+    /// Returns `None` if the total weight has already overflowed.
+    /// Time complexity: `O(n)`.
+    pub fn headroom(&self) -> Option<Index> {
+        let total = self.exact_weight();
+        Index::try_from(total).ok().map(|total| Index::MAX - total)
+    }
+
+    /// Estimates the effect of inserting elements with the `planned` weights
+    /// on the urn's headroom (see `headroom`), so that weights can be
+    /// renormalized before they overflow.
+    /// Time complexity: `O(n + k)`, where `k` is the no. of planned weights.
+    pub fn plan_insert<I>(&self, planned: I) -> CapacityPlan
+    where
+        I: IntoIterator<Item = Weight>,
+    {
+        let total = self.exact_weight();
+        let planned_weight: u128 = planned.into_iter().map(u128::from).sum();
+        let after = total + planned_weight;
+        let max = u128::from(Index::MAX);
+        let headroom =
+            |t: u128| Index::try_from(t).ok().map(|t| Index::MAX - t);
+        CapacityPlan {
+            headroom_before: headroom(total),
+            planned_weight,
+            headroom_after: headroom(after),
+            overflow: after.saturating_sub(max),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        types::{widen, Index, Weight},
+        urn::from_list,
+    };
+    use rand::SeedableRng;
+
+    #[test]
+    fn simulate_draw_distribution_example() {
+        let urn = from_list(vec![(1, 'a'), (1, 'b'), (1, 'c')]).unwrap();
+        let dist = urn.simulate_draw_distribution(10);
+        let rounded: Vec<u64> = dist.counts.iter().map(|c| c.rounded).collect();
+        assert_eq!(rounded, vec![4, 3, 3]);
+        assert!((dist.counts[0].expected - 10.0 / 3.0).abs() < 1e-9);
+        assert!((dist.max_rounding_error - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn simulate_draw_distribution_exact() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (2, 'B')]).unwrap();
+        let dist = urn.simulate_draw_distribution(8);
+        let rounded: Vec<u64> = dist.counts.iter().map(|c| c.rounded).collect();
+        assert_eq!(rounded, vec![2, 4, 2]);
+        assert_eq!(dist.max_rounding_error, 0.0);
+    }
+
+    #[test]
+    fn quantile_and_cdf_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (2, 'B')]).unwrap();
+        assert_eq!(urn.quantile(0.0), &'R');
+        assert_eq!(urn.quantile(0.25), &'G');
+        assert_eq!(urn.quantile(0.7), &'G');
+        assert_eq!(urn.quantile(0.95), &'B');
+        assert_eq!(urn.quantile(1.0), &'B');
+        let cdf: Vec<_> = (0..3)
+            .map(|pos| urn.cumulative_weight_before(pos))
+            .collect();
+        assert_eq!(cdf, vec![0, 2, 6]);
+    }
+
+    #[test]
+    fn headroom_example() {
+        let urn = from_list(vec![(Weight::MAX - 10, 'a'), (4, 'b')]).unwrap();
+        let total = widen(Weight::MAX) - 6;
+        assert_eq!(urn.headroom(), Some(Index::MAX - total));
+        let plan = urn.plan_insert(vec![2, 3]);
+        assert_eq!(plan.planned_weight, 5);
+        assert_eq!(plan.headroom_after, Some(Index::MAX - total - 5));
+        assert_eq!(plan.overflow, 0);
+    }
+
+    /// Only 64-bit weights can overflow the (64-bit) total weight
+    #[cfg(feature = "weight-u64")]
+    #[test]
+    fn headroom_overflow_example() {
+        let urn = from_list(vec![(Weight::MAX - 10, 'a'), (4, 'b')]).unwrap();
+        assert_eq!(urn.headroom(), Some(6));
+        let plan = urn.plan_insert(vec![5, 5]);
+        assert_eq!(plan.headroom_after, None);
+        assert_eq!(plan.overflow, 4);
+        assert_eq!(urn.insert(7, 'c').headroom(), None);
+    }
+
+    /// Probabilities are relative to the exact total weight,
+    /// even once `weight` has wrapped around
+    #[cfg(feature = "weight-u64")]
+    #[test]
+    fn probabilities_of_overflowed_urn() {
+        let urn = from_list(vec![(Weight::MAX, 'a'), (Weight::MAX, 'b')]);
+        let urn = urn.unwrap();
+        assert_eq!(urn.headroom(), None);
+        let probs: Vec<_> = urn.probabilities().collect();
+        assert_eq!(probs, vec![(0.5, &'a'), (0.5, &'b')]);
+        assert_eq!(urn.prob_of_index(0), 0.5);
+        assert_eq!(urn.entropy(), 1.0);
+    }
+
+    #[test]
+    fn probabilities_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (0, 'Y'), (2, 'B')]);
+        let urn = urn.unwrap();
+        let probs: Vec<_> = urn.probabilities().collect();
+        assert_eq!(
+            probs,
+            vec![(0.25, &'R'), (0.5, &'G'), (0.0, &'Y'), (0.25, &'B')]
+        );
+        assert_eq!(urn.prob_of_index(1), 0.25);
+        assert_eq!(urn.prob_of_index(2), 0.5);
+        assert_eq!(urn.entropy(), 1.5);
+        assert_eq!(from_list(vec![(3, 'a')]).unwrap().entropy(), 0.0);
+    }
+
+    #[test]
+    fn gamma_q_matches_known_values() {
+        // Chi-square survival function values (df = 2k, x = 2y)
+        assert!((super::gamma_q(1.0, 1.0) - (-1.0f64).exp()).abs() < 1e-10);
+        // P(chi2_1 >= 3.841) = 0.05
+        assert!((super::gamma_q(0.5, 3.841459 / 2.0) - 0.05).abs() < 1e-6);
+        // P(chi2_10 >= 18.307) = 0.05
+        assert!((super::gamma_q(5.0, 18.307038 / 2.0) - 0.05).abs() < 1e-6);
+    }
+
+    #[test]
+    fn chi_square_example() {
+        let urn = from_list(vec![(1, 'a'), (0, 'z'), (3, 'b')]).unwrap();
+        let fit = urn.chi_square(&[25, 0, 75]);
+        assert_eq!(fit.statistic, 0.0);
+        assert_eq!(fit.degrees_of_freedom, 1);
+        assert!((fit.p_value - 1.0).abs() < 1e-9);
+
+        let biased = urn.chi_square(&[50, 0, 50]);
+        assert!(biased.p_value < 1e-6);
+        assert_eq!(urn.chi_square(&[25, 1, 74]).p_value, 0.0);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let counts = urn.empirical_counts(4000, &mut rng);
+        assert_eq!(counts[1], 0);
+        assert_eq!(counts.iter().sum::<u64>(), 4000);
+        assert!(urn.chi_square(&counts).p_value > 1e-4);
+    }
+}
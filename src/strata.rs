@@ -0,0 +1,183 @@
+use rand_core::RngCore;
+
+use crate::types::Urn;
+
+/* -------------------------------------------------------------------------- */
+/*                             Stratified sampling                            */
+/* -------------------------------------------------------------------------- */
+
+/// A collection of labeled urns (the *strata*), for stratified sampling:
+/// `sample(n, rng)` draws a total of `n` samples, allocated among the strata
+/// in proportion to their total weights (see `allocate`), so each stratum
+/// is represented by (almost exactly) its share of the samples,
+/// instead of by a random no. of samples.
+///
+/// The strata are kept in insertion order, which is used to break ties
+/// when allocating samples, so the allocation is deterministic.
+#[derive(Debug, Clone)]
+pub struct Strata<K, T: Clone> {
+    strata: Vec<(K, Urn<T>)>,
+}
+
+impl<K, T: Clone> Default for Strata<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T: Clone> Strata<K, T> {
+    /// Creates a `Strata` with no strata
+    pub fn new() -> Self {
+        Self { strata: Vec::new() }
+    }
+
+    /// Adds the stratum `urn` with label `label` (after all existing strata)
+    pub fn push(&mut self, label: K, urn: Urn<T>) -> &mut Self {
+        self.strata.push((label, urn));
+        self
+    }
+
+    /// The no. of strata
+    pub fn len(&self) -> usize {
+        self.strata.len()
+    }
+
+    /// Whether there are no strata
+    pub fn is_empty(&self) -> bool {
+        self.strata.is_empty()
+    }
+
+    /// Iterates over the strata (in insertion order),
+    /// yielding each stratum's label and urn
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Urn<T>)> {
+        self.strata.iter().map(|(label, urn)| (label, urn))
+    }
+
+    /// The total weight of all the strata
+    /// (as a `u128`, since it may not fit in an `Index`)
+    pub fn weight(&self) -> u128 {
+        self.strata
+            .iter()
+            .map(|(_, urn)| u128::from(urn.weight()))
+            .sum()
+    }
+
+    /// Allocates `n` samples among the strata in proportion to their total
+    /// weights, returning the no. of samples of each stratum (in insertion
+    /// order), by the largest remainder method: a stratum with total weight
+    /// `w` first receives `floor(n * w / W)` samples, where `W` is the total
+    /// weight of all the strata, and the samples left over are given one
+    /// each to the strata with the largest remainders `n * w mod W`
+    /// (breaking ties in insertion order).
+    /// All of this uses exact integer arithmetic, so every stratum receives
+    /// `floor(n * w / W)` or `ceil(n * w / W)` samples, and the counts always
+    /// add up to `n`. Strata with weight 0 receive no samples, and if `W` is 0,
+    /// no stratum receives any samples.
+    /// Time complexity: `O(s log s)`, where `s` is the no. of strata.
+    pub fn allocate(&self, n: usize) -> Vec<usize> {
+        let total = self.weight();
+        if total == 0 {
+            return vec![0; self.strata.len()];
+        }
+        // `n * w` fits in a `u128`, since `n` and `w` both fit in a `u64`
+        let n = n as u128;
+        let (mut counts, mut remainders): (Vec<usize>, Vec<(u128, usize)>) =
+            self.strata
+                .iter()
+                .enumerate()
+                .map(|(j, (_, urn))| {
+                    let share = n * u128::from(urn.weight());
+                    ((share / total) as usize, (share % total, j))
+                })
+                .unzip();
+        let allocated: usize = counts.iter().sum();
+        // Largest remainders first, ties in insertion order
+        remainders.sort_by(|(r1, j1), (r2, j2)| r2.cmp(r1).then(j1.cmp(j2)));
+        for &(_, j) in remainders.iter().take(n as usize - allocated) {
+            counts[j] += 1;
+        }
+        counts
+    }
+
+    /// Draws a total of `n` samples with replacement using `rng`, allocated
+    /// among the strata by `allocate`, and returns each stratum's label along
+    /// with its samples (in insertion order). Within a stratum, the samples
+    /// are drawn independently (as by `Urn::sample_ref`).
+    /// Time complexity: `O(s log s + n log m)`, where `s` is the no. of
+    /// strata and `m` the size of the largest stratum.
+    pub fn sample<R: RngCore + ?Sized>(
+        &self,
+        n: usize,
+        rng: &mut R,
+    ) -> Vec<(&K, Vec<&T>)> {
+        self.strata
+            .iter()
+            .zip(self.allocate(n))
+            .map(|((label, urn), k)| {
+                (label, (0..k).map(|_| urn.sample_ref(rng)).collect())
+            })
+            .collect()
+    }
+}
+
+impl<K, T: Clone> FromIterator<(K, Urn<T>)> for Strata<K, T> {
+    fn from_iter<I: IntoIterator<Item = (K, Urn<T>)>>(iter: I) -> Self {
+        Self {
+            strata: iter.into_iter().collect(),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::Strata;
+    use crate::urn::{from_list, singleton};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn allocate_uses_largest_remainders() {
+        let strata: Strata<_, _> = vec![
+            ("a", from_list(vec![(1, 'x'), (2, 'y')]).unwrap()),
+            ("b", singleton(3, 'z')),
+            ("c", singleton(0, 'w')),
+            ("d", singleton(4, 'v')),
+        ]
+        .into_iter()
+        .collect();
+        // Quotas of 10 * (3, 3, 0, 4) / 10
+        assert_eq!(strata.allocate(10), vec![3, 3, 0, 4]);
+        // Quotas 1.2, 1.2, 0, 1.6: the remainder goes to "d"
+        assert_eq!(strata.allocate(4), vec![1, 1, 0, 2]);
+        // Quotas 0.6, 0.6, 0, 0.8: ties go to the earlier stratum
+        assert_eq!(strata.allocate(2), vec![1, 0, 0, 1]);
+        assert_eq!(strata.allocate(0), vec![0, 0, 0, 0]);
+        for n in 0..50 {
+            assert_eq!(strata.allocate(n).iter().sum::<usize>(), n);
+        }
+
+        let zero: Strata<_, _> =
+            vec![((), singleton(0, 'w'))].into_iter().collect();
+        assert_eq!(zero.allocate(5), vec![0]);
+        assert!(Strata::<(), char>::new().allocate(5).is_empty());
+    }
+
+    #[test]
+    fn sample_draws_from_each_stratum() {
+        let mut strata = Strata::new();
+        strata
+            .push("low", from_list(vec![(1, 1), (1, 2)]).unwrap())
+            .push("high", from_list(vec![(2, 10), (4, 20)]).unwrap());
+        let mut rng = StdRng::seed_from_u64(3);
+        let samples = strata.sample(8, &mut rng);
+        assert_eq!(samples.len(), 2);
+        let (low, high) = (&samples[0], &samples[1]);
+        assert_eq!((*low.0, low.1.len()), ("low", 2));
+        assert_eq!((*high.0, high.1.len()), ("high", 6));
+        assert!(low.1.iter().all(|&&a| a < 10));
+        assert!(high.1.iter().all(|&&a| a >= 10));
+    }
+}
@@ -0,0 +1,172 @@
+use proptest::{
+    strategy::{NewTree, Strategy, ValueTree},
+    test_runner::TestRunner,
+};
+
+use crate::types::{Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                         `proptest` strategy integration                    */
+/* -------------------------------------------------------------------------- */
+
+/// A `proptest` strategy which chooses one of several alternative strategies
+/// with probability proportional to its weight (like `prop_oneof!` with
+/// weights, but with the weights taken from an urn, which may be built at
+/// runtime). Created by `UrnStrategy::new` or `Urn::into_strategy`.
+///
+/// When shrinking, a value first tries to switch to the alternative with the
+/// next higher weight (the alternatives are ordered by decreasing weight, and
+/// ties keep the urn's leaf order), and then shrinks within its alternative.
+#[derive(Debug, Clone)]
+pub struct UrnStrategy<S: Strategy + Clone> {
+    /// The alternatives, ordered by decreasing weight
+    alternatives: Vec<S>,
+    /// An index-only urn whose `k`-th leaf has the weight
+    /// of `alternatives[k]`
+    weights: Urn<()>,
+}
+
+impl<S: Strategy + Clone> UrnStrategy<S> {
+    /// Creates a strategy which chooses between the strategies in `urn`
+    /// in proportion to their weights.
+    /// Time complexity: `O(n log n)`.
+    pub fn new(urn: Urn<S>) -> Self {
+        let mut elems = urn.into_vec();
+        elems.sort_by(|(w1, _), (w2, _)| w2.cmp(w1));
+        let weights: Vec<Weight> = elems.iter().map(|(w, _)| *w).collect();
+        Self {
+            alternatives: elems.into_iter().map(|(_, s)| s).collect(),
+            // The urn is non-empty, so there is at least one weight
+            weights: Urn::index_only_from_weights(&weights).unwrap(),
+        }
+    }
+}
+
+impl<S: Strategy + Clone> Urn<S> {
+    /// Converts an urn of `proptest` strategies into a strategy which chooses
+    /// between them in proportion to their weights (see `UrnStrategy`)
+    pub fn into_strategy(self) -> UrnStrategy<S> {
+        UrnStrategy::new(self)
+    }
+}
+
+impl<S: Strategy + Clone> Strategy for UrnStrategy<S> {
+    type Tree = UrnValueTree<S::Tree>;
+    type Value = S::Value;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        // The index is drawn by `proptest`'s own strategy for ranges,
+        // since `proptest` may use a different version of `rand`
+        let w = self.weights.weight();
+        let i = if w == 0 {
+            0
+        } else {
+            (0..w).new_tree(runner)?.current()
+        };
+        let pick = self.weights.position_of_index(i) as usize;
+        // Also generate a value for every heavier alternative,
+        // so that shrinking can switch to them
+        let trees = self.alternatives[..=pick]
+            .iter()
+            .map(|s| s.new_tree(runner))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(UrnValueTree {
+            trees,
+            pick,
+            min_pick: 0,
+            prev_pick: None,
+        })
+    }
+}
+
+/// The `ValueTree` of an `UrnStrategy`
+#[derive(Debug, Clone)]
+pub struct UrnValueTree<T> {
+    /// The values generated by the chosen alternative and every
+    /// heavier alternative
+    trees: Vec<T>,
+    /// The alternative whose value is currently used
+    pick: usize,
+    /// The heaviest alternative which may still be switched to
+    min_pick: usize,
+    /// The alternative used before the last call to `simplify`, if that call
+    /// switched alternatives
+    prev_pick: Option<usize>,
+}
+
+impl<T: ValueTree> ValueTree for UrnValueTree<T> {
+    type Value = T::Value;
+
+    fn current(&self) -> T::Value {
+        self.trees[self.pick].current()
+    }
+
+    fn simplify(&mut self) -> bool {
+        if self.pick > self.min_pick {
+            self.prev_pick = Some(self.pick);
+            self.pick -= 1;
+            return true;
+        }
+        self.prev_pick = None;
+        self.trees[self.pick].simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        match self.prev_pick.take() {
+            // The heavier alternative no longer fails, so go back and
+            // only shrink within the previous alternative from now on
+            Some(pick) => {
+                self.pick = pick;
+                self.min_pick = pick;
+                true
+            }
+            None => self.trees[self.pick].complicate(),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use proptest::strategy::Just;
+
+    #[test]
+    fn urn_strategy_respects_weights() {
+        let urn =
+            from_list(vec![(1, Just('a')), (0, Just('b')), (3, Just('c'))]);
+        let strategy = urn.unwrap().into_strategy();
+        let mut runner = TestRunner::deterministic();
+        let mut seen = vec![];
+        for _ in 0..100 {
+            let a = strategy.new_tree(&mut runner).unwrap().current();
+            assert_ne!(a, 'b');
+            seen.push(a);
+        }
+        assert!(seen.contains(&'a') && seen.contains(&'c'));
+    }
+
+    #[test]
+    fn urn_strategy_shrinks_towards_heavier_alternatives() {
+        let urn =
+            from_list(vec![(1, Just('a')), (2, Just('b')), (3, Just('c'))]);
+        let strategy = urn.unwrap().into_strategy();
+        let mut runner = TestRunner::deterministic();
+        let mut tree = loop {
+            let tree = strategy.new_tree(&mut runner).unwrap();
+            if tree.current() == 'a' {
+                break tree;
+            }
+        };
+        assert!(tree.simplify());
+        assert_eq!(tree.current(), 'b');
+        // Suppose 'b' doesn't fail the test: we go back to 'a' for good
+        assert!(tree.complicate());
+        assert_eq!(tree.current(), 'a');
+        assert!(!tree.simplify());
+    }
+}
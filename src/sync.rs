@@ -0,0 +1,413 @@
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+use rand_core::RngCore;
+
+use crate::{
+    random::below_u128,
+    types::{Index, Urn, Weight},
+    urn::singleton,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                              Thread-safe urns                              */
+/* -------------------------------------------------------------------------- */
+
+/// Acquires the read lock of `lock`, recovering it if it is poisoned
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires the write lock of `lock`, recovering it if it is poisoned
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A thread-safe urn, which can be shared between threads by cloning it
+/// (clones share the same underlying urn), e.g. as the weighted queue of a
+/// multi-threaded job scheduler.
+///
+/// Consistency model: the urn is guarded by a single `RwLock`, so every
+/// operation is atomic (linearizable). Any number of threads may `sample`
+/// concurrently (under the read lock), while `insert`, `remove` and `update`
+/// take the write lock, so they are serialized, and exclude all readers.
+/// A `sample` therefore always sees the urn as it was between two writes,
+/// never a partially updated urn.
+///
+/// If a thread panics while holding the lock, later operations still succeed
+/// (the poisoned lock is recovered). Writers move the urn out of the lock
+/// while computing the updated urn, so if the closure passed to `update`
+/// panics, the `SyncUrn` is left empty (but never partially updated).
+#[derive(Debug, Clone)]
+pub struct SyncUrn<T: Clone> {
+    urn: Arc<RwLock<Option<Urn<T>>>>,
+}
+
+impl<T: Clone> Default for SyncUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> From<Urn<T>> for SyncUrn<T> {
+    fn from(urn: Urn<T>) -> Self {
+        Self {
+            urn: Arc::new(RwLock::new(Some(urn))),
+        }
+    }
+}
+
+impl<T: Clone> SyncUrn<T> {
+    /// Creates an empty `SyncUrn`
+    pub fn new() -> Self {
+        Self {
+            urn: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Acquires the read lock (recovering it if it is poisoned)
+    fn read(&self) -> RwLockReadGuard<'_, Option<Urn<T>>> {
+        read(&self.urn)
+    }
+
+    /// Acquires the write lock (recovering it if it is poisoned)
+    fn write(&self) -> RwLockWriteGuard<'_, Option<Urn<T>>> {
+        write(&self.urn)
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.read().as_ref().map_or(0, |urn| urn.size() as usize)
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.read().is_none()
+    }
+
+    /// The total weight of all the elements
+    pub fn weight(&self) -> Index {
+        self.read().as_ref().map_or(0, |urn| urn.weight())
+    }
+
+    /// A copy of the current urn (`None` if there are no elements).
+    /// Time complexity: `O(n)`.
+    pub fn snapshot(&self) -> Option<Urn<T>> {
+        self.read().clone()
+    }
+
+    /// Runs `f` on the current urn (`None` if there are no elements)
+    /// while holding the read lock, e.g. to take several samples
+    /// from the same version of the urn
+    pub fn with_urn<F, B>(&self, f: F) -> B
+    where
+        F: FnOnce(Option<&Urn<T>>) -> B,
+    {
+        f(self.read().as_ref())
+    }
+
+    /// Inserts element `a` with weight `w`.
+    /// Time complexity: `O(log n)`.
+    pub fn insert(&self, w: Weight, a: T) {
+        let mut guard = self.write();
+        let urn = match guard.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        };
+        *guard = Some(urn);
+    }
+
+    /// Randomly samples an element using `rng`, returning a clone of it
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<T> {
+        self.read().as_ref().map(|urn| urn.sample_ref(rng).clone())
+    }
+
+    /// Removes a random element using `rng`, returning it along with
+    /// its weight (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn remove<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<(Weight, T)> {
+        let mut guard = self.write();
+        let urn = guard.take()?;
+        let i = urn.draw_index(rng);
+        let (elem, rest) = urn.remove_index(i);
+        *guard = rest;
+        Some(elem)
+    }
+
+    /// Replaces the urn by `f(urn)` atomically (with respect to all other
+    /// operations), returning whatever `f` returns alongside the new urn
+    pub fn update<F, B>(&self, f: F) -> B
+    where
+        F: FnOnce(Option<Urn<T>>) -> (Option<Urn<T>>, B),
+    {
+        let mut guard = self.write();
+        let (urn, b) = f(guard.take());
+        *guard = urn;
+        b
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                Sharded urns                                */
+/* -------------------------------------------------------------------------- */
+
+/// One shard of a `ShardedUrn`. The size and weight of the shard's urn are
+/// mirrored in atomics (which are only updated while holding the shard's
+/// write lock), so that a shard can be chosen without taking any locks.
+#[derive(Debug)]
+struct Shard<T: Clone> {
+    urn: RwLock<Option<Urn<T>>>,
+    size: AtomicU32,
+    weight: AtomicU64,
+}
+
+impl<T: Clone> Shard<T> {
+    /// Stores `urn` in the shard (whose write lock is held by `guard`),
+    /// updating the shard's size and weight
+    fn store(&self, guard: &mut Option<Urn<T>>, urn: Option<Urn<T>>) {
+        let (size, weight) =
+            urn.as_ref().map_or((0, 0), |u| (u.size(), u.weight()));
+        *guard = urn;
+        self.size.store(size, Ordering::Release);
+        self.weight.store(weight, Ordering::Release);
+    }
+}
+
+/// A thread-safe urn split into several shards, each with its own lock,
+/// for workloads with many concurrent `insert`s and `remove`s (for which the
+/// single lock of a `SyncUrn` is a bottleneck). To share a `ShardedUrn`
+/// between threads, wrap it in an `Arc`.
+///
+/// `insert` spreads the elements over the shards in round-robin order.
+/// `sample` and `remove` first choose a shard with probability proportional
+/// to its total weight (read from atomics, without taking any locks), and
+/// then sample from that shard's urn under the shard's lock.
+///
+/// Consistency model: every operation on a single shard is atomic, but
+/// operations are not linearizable across shards. In particular, a shard is
+/// chosen based on the weights of the shards at the time of the call, which
+/// may change before the shard's lock is acquired, so an element is only
+/// sampled with probability exactly proportional to its weight when there are
+/// no concurrent writers. (If the chosen shard has become empty in the
+/// meantime, the shard is chosen again.) Likewise, `len` and `weight` sum
+/// the shards' sizes and weights one shard at a time.
+#[derive(Debug)]
+pub struct ShardedUrn<T: Clone> {
+    shards: Vec<Shard<T>>,
+    /// The shard which receives the next inserted element (modulo the no.
+    /// of shards)
+    next: AtomicUsize,
+}
+
+impl<T: Clone> ShardedUrn<T> {
+    /// Creates an empty urn with `shards` shards.
+    /// Panics if `shards == 0`.
+    pub fn new(shards: usize) -> Self {
+        assert!(shards > 0, "a ShardedUrn needs at least one shard");
+        Self {
+            shards: (0..shards)
+                .map(|_| Shard {
+                    urn: RwLock::new(None),
+                    size: AtomicU32::new(0),
+                    weight: AtomicU64::new(0),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The no. of shards
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The no. of elements
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.size.load(Ordering::Acquire) as usize)
+            .sum()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total weight of all the elements
+    /// (as a `u128`, since it may exceed `Index::MAX`)
+    pub fn weight(&self) -> u128 {
+        self.shards
+            .iter()
+            .map(|shard| u128::from(shard.weight.load(Ordering::Acquire)))
+            .sum()
+    }
+
+    /// Inserts element `a` with weight `w` into the next shard
+    /// (in round-robin order).
+    /// Time complexity: `O(log n)`.
+    pub fn insert(&self, w: Weight, a: T) {
+        let k = self.next.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let shard = &self.shards[k];
+        let mut guard = write(&shard.urn);
+        let urn = match guard.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        };
+        shard.store(&mut guard, Some(urn));
+    }
+
+    /// Chooses a shard with probability proportional to its weight (or, if
+    /// every shard has weight 0, the first non-empty shard), using `rng`.
+    /// Returns `None` if every shard is empty.
+    fn choose_shard<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<&Shard<T>> {
+        let weights: Vec<u128> = self
+            .shards
+            .iter()
+            .map(|shard| u128::from(shard.weight.load(Ordering::Acquire)))
+            .collect();
+        let total: u128 = weights.iter().sum();
+        if total == 0 {
+            return self
+                .shards
+                .iter()
+                .find(|shard| shard.size.load(Ordering::Acquire) > 0);
+        }
+        let mut i = below_u128(rng, total);
+        for (shard, w) in self.shards.iter().zip(weights) {
+            if i < w {
+                return Some(shard);
+            }
+            i -= w;
+        }
+        unreachable!("the index is less than the total weight")
+    }
+
+    /// Randomly samples an element using `rng`, returning a clone of it
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(s + log n)`, where `s` is the no. of shards.
+    pub fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<T> {
+        loop {
+            let shard = self.choose_shard(rng)?;
+            // The shard may have been emptied since it was chosen
+            if let Some(urn) = read(&shard.urn).as_ref() {
+                return Some(urn.sample_ref(rng).clone());
+            }
+        }
+    }
+
+    /// Removes a random element using `rng`, returning it along with
+    /// its weight (or `None` if there are no elements).
+    /// Time complexity: `O(s + log n)`, where `s` is the no. of shards.
+    pub fn remove<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Option<(Weight, T)> {
+        loop {
+            let shard = self.choose_shard(rng)?;
+            let mut guard = write(&shard.urn);
+            if let Some(urn) = guard.take() {
+                let i = urn.draw_index(rng);
+                let (elem, rest) = urn.remove_index(i);
+                shard.store(&mut guard, rest);
+                return Some(elem);
+            }
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use std::thread;
+
+    #[test]
+    fn concurrent_inserts_and_removals() {
+        let urn = SyncUrn::new();
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let urn = urn.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        urn.insert(1, t * 100 + i);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(urn.len(), 400);
+        assert_eq!(urn.weight(), 400);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let urn = urn.clone();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    (0..100).filter_map(|_| urn.remove(&mut rng)).count()
+                })
+            })
+            .collect();
+        let removed: usize =
+            readers.into_iter().map(|r| r.join().unwrap()).sum();
+        assert_eq!(removed, 400);
+        assert!(urn.is_empty());
+        assert_eq!(urn.sample(&mut thread_rng()), None);
+    }
+
+    #[test]
+    fn update_is_atomic() {
+        let urn: SyncUrn<char> = singleton(2, 'a').into();
+        let old = urn.update(|u| (u.map(|u| u.insert(3, 'b')), 'x'));
+        assert_eq!(old, 'x');
+        assert_eq!(urn.with_urn(|u| u.map(|u| u.size())), Some(2));
+        assert_eq!(urn.snapshot().map(|u| u.weight()), Some(5));
+    }
+
+    #[test]
+    fn sharded_urn_spreads_elements() {
+        let urn = Arc::new(ShardedUrn::new(4));
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let urn = Arc::clone(&urn);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        urn.insert(if i % 2 == 0 { 0 } else { 1 }, t * 100 + i);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!((urn.len(), urn.weight()), (400, 200));
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            assert_eq!(urn.sample(&mut rng).map(|i| i % 2), Some(1));
+        }
+        let mut removed = vec![];
+        while let Some((_, i)) = urn.remove(&mut rng) {
+            removed.push(i);
+        }
+        removed.sort();
+        assert_eq!(removed, (0..400).collect::<Vec<_>>());
+        assert!(urn.is_empty());
+        assert_eq!(urn.sample(&mut rng), None);
+    }
+}
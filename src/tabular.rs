@@ -0,0 +1,373 @@
+use std::{fmt, io};
+
+#[cfg(feature = "csv")]
+use std::io::BufRead;
+
+use crate::{
+    types::{widen, Index, Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                      CSV & JSON import and export                          */
+/* -------------------------------------------------------------------------- */
+
+/// Errors arising when importing an urn with `Urn::from_csv` or
+/// `Urn::from_json`
+#[derive(Debug)]
+pub enum ImportError {
+    /// The input couldn't be read
+    Io(io::Error),
+    /// The input isn't valid JSON (at the given 1-based line and column)
+    Syntax { line: usize, column: usize },
+    /// A row is invalid. Rows are numbered from 1: for CSV, `row` is the
+    /// line number, and for JSON, the position of the row in the array.
+    Row { row: usize, error: RowError },
+    /// The input has no rows (urns always contain at least one element)
+    Empty,
+}
+
+/// The ways in which a row of an imported distribution can be invalid
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RowError {
+    /// The row doesn't have exactly two fields (`weight,value`)
+    FieldCount(usize),
+    /// A quoted field isn't closed by the end of the line
+    UnterminatedQuote,
+    /// The row lacks the given field (`"weight"` or `"value"`)
+    MissingField(&'static str),
+    /// The weight isn't an integer between 0 and the maximum `Weight`
+    InvalidWeight(String),
+    /// The value isn't a string
+    InvalidValue,
+    /// The total weight of the rows so far doesn't fit in an `Index`
+    TotalWeightOverflow,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "I/O error: {}", err),
+            ImportError::Syntax { line, column } => {
+                write!(f, "invalid JSON at line {}, column {}", line, column)
+            }
+            ImportError::Row { row, error } => {
+                write!(f, "row {}: {}", row, error)
+            }
+            ImportError::Empty => write!(f, "cannot import an empty urn"),
+        }
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowError::FieldCount(n) => {
+                write!(f, "expected 2 fields (weight,value), found {}", n)
+            }
+            RowError::UnterminatedQuote => write!(f, "unterminated quote"),
+            RowError::MissingField(field) => {
+                write!(f, "missing field `{}`", field)
+            }
+            RowError::InvalidWeight(w) => {
+                write!(f, "invalid weight `{}` (maximum {})", w, Weight::MAX)
+            }
+            RowError::InvalidValue => write!(f, "the value isn't a string"),
+            RowError::TotalWeightOverflow => {
+                write!(f, "the total weight overflows")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for RowError {}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+/// Accumulates validated rows, checking that the total weight doesn't
+/// overflow, and builds the urn
+#[derive(Default)]
+struct Rows {
+    elems: Vec<(Weight, String)>,
+    total: Index,
+}
+
+impl Rows {
+    /// Validates the weight `w` (as written in the input) of row `row`,
+    /// and adds the row
+    fn push(
+        &mut self,
+        row: usize,
+        w: &str,
+        a: String,
+    ) -> Result<(), ImportError> {
+        let err = |error| ImportError::Row { row, error };
+        let w: Weight = w
+            .parse()
+            .map_err(|_| err(RowError::InvalidWeight(w.to_string())))?;
+        self.total = self
+            .total
+            .checked_add(widen(w))
+            .ok_or_else(|| err(RowError::TotalWeightOverflow))?;
+        self.elems.push((w, a));
+        Ok(())
+    }
+
+    fn build(self) -> Result<Urn<String>, ImportError> {
+        from_list(self.elems).ok_or(ImportError::Empty)
+    }
+}
+
+/// Splits a CSV line into its fields, unquoting quoted fields
+/// (in which `""` stands for a literal quote)
+#[cfg(feature = "csv")]
+fn split_csv_line(line: &str) -> Result<Vec<String>, RowError> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => return Err(RowError::UnterminatedQuote),
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                }
+            }
+            // Anything between the closing quote and the next comma is kept
+            // verbatim, as spreadsheets do
+        }
+        let mut at_comma = false;
+        for c in chars.by_ref() {
+            if c == ',' {
+                at_comma = true;
+                break;
+            }
+            field.push(c);
+        }
+        fields.push(field);
+        if !at_comma {
+            return Ok(fields);
+        }
+    }
+}
+
+/// Quotes a CSV field if it contains a comma, a quote, a line break,
+/// or leading or trailing whitespace
+#[cfg(feature = "csv")]
+fn quote_csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    let needs_quotes =
+        field.contains([',', '"', '\n', '\r']) || field.trim() != field;
+    if needs_quotes {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+#[cfg(feature = "csv")]
+impl Urn<String> {
+    /// Imports an urn from CSV rows of the form `weight,value` (e.g. a
+    /// distribution authored in a spreadsheet), in order.
+    /// Values containing commas, quotes or surrounding whitespace must be
+    /// quoted (with `""` for a literal quote), and weights may be surrounded
+    /// by whitespace (quoted values can't span several lines). Blank lines
+    /// are skipped, as is a first row of `weight,value` (a header).
+    /// Fails with the 1-based line number of the first invalid row, or if
+    /// there are no rows.
+    /// Time complexity: `O(n)`.
+    pub fn from_csv<R: io::Read>(reader: R) -> Result<Self, ImportError> {
+        let mut rows = Rows::default();
+        for (j, line) in io::BufReader::new(reader).lines().enumerate() {
+            let (row, line) = (j + 1, line?);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let err = |error| ImportError::Row { row, error };
+            let fields = split_csv_line(&line).map_err(err)?;
+            let [w, a]: [String; 2] = fields
+                .try_into()
+                .map_err(|f: Vec<_>| err(RowError::FieldCount(f.len())))?;
+            let w = w.trim();
+            if row == 1 && w.eq_ignore_ascii_case("weight") {
+                continue;
+            }
+            rows.push(row, w, a)?;
+        }
+        rows.build()
+    }
+
+    /// Exports the urn as CSV rows of the form `weight,value`, in leaf order,
+    /// after a `weight,value` header (so `from_csv` reads them back).
+    /// Time complexity: `O(n)`.
+    pub fn to_csv<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "weight,value")?;
+        for (w, a) in self.iter() {
+            writeln!(writer, "{},{}", w, quote_csv_field(a))?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(feature = "json")]
+impl Urn<String> {
+    /// Imports an urn from a JSON array of rows of the form
+    /// `{"weight": 3, "value": "..."}`, in order (other fields are ignored).
+    /// Fails with the 1-based position of the first invalid row, if the
+    /// input isn't a JSON array, or if the array is empty.
+    /// Time complexity: `O(n)`.
+    pub fn from_json<R: io::Read>(reader: R) -> Result<Self, ImportError> {
+        use serde_json::Value;
+
+        let json: Vec<Value> =
+            serde_json::from_reader(reader).map_err(|err| {
+                if err.is_io() {
+                    ImportError::Io(err.into())
+                } else {
+                    ImportError::Syntax {
+                        line: err.line(),
+                        column: err.column(),
+                    }
+                }
+            })?;
+        let mut rows = Rows::default();
+        for (j, value) in json.into_iter().enumerate() {
+            let row = j + 1;
+            let err = |error| ImportError::Row { row, error };
+            let field =
+                |name| value.get(name).ok_or(err(RowError::MissingField(name)));
+            let w = match field("weight")? {
+                Value::Number(w) => w.to_string(),
+                w => return Err(err(RowError::InvalidWeight(w.to_string()))),
+            };
+            let a = field("value")?
+                .as_str()
+                .ok_or(err(RowError::InvalidValue))?;
+            rows.push(row, &w, a.to_string())?;
+        }
+        rows.build()
+    }
+
+    /// Exports the urn as a (pretty-printed) JSON array of rows of the form
+    /// `{"weight": 3, "value": "..."}`, in leaf order
+    /// (so `from_json` reads them back).
+    /// Time complexity: `O(n)`.
+    pub fn to_json<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        let rows: Vec<serde_json::Value> = self
+            .iter()
+            .map(|(w, a)| serde_json::json!({ "weight": w, "value": a }))
+            .collect();
+        serde_json::to_writer_pretty(writer, &rows).map_err(io::Error::from)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_round_trip() {
+        let input = "weight,value\n3,common\n\n 1 ,\"rare, but \"\"shiny\"\"\"\r\n0,never\n";
+        let urn = Urn::from_csv(input.as_bytes()).unwrap();
+        assert_eq!(
+            urn.to_vec(),
+            vec![
+                (3, "common".to_string()),
+                (1, "rare, but \"shiny\"".into()),
+                (0, "never".into()),
+            ]
+        );
+        let mut output = Vec::new();
+        urn.to_csv(&mut output).unwrap();
+        assert_eq!(Urn::from_csv(&output[..]).unwrap(), urn);
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_reports_the_invalid_row() {
+        let row_error = |input: &str| match Urn::from_csv(input.as_bytes()) {
+            Err(ImportError::Row { row, error }) => Some((row, error)),
+            _ => None,
+        };
+        assert_eq!(
+            row_error("1,a\n\nx,b\n"),
+            Some((3, RowError::InvalidWeight("x".into())))
+        );
+        assert_eq!(
+            row_error("1,a\n-1,b\n"),
+            Some((2, RowError::InvalidWeight("-1".into())))
+        );
+        assert_eq!(row_error("1,a,b\n"), Some((1, RowError::FieldCount(3))));
+        assert_eq!(row_error("1\n"), Some((1, RowError::FieldCount(1))));
+        assert_eq!(
+            row_error("1,\"a\n"),
+            Some((1, RowError::UnterminatedQuote))
+        );
+        assert!(matches!(
+            Urn::from_csv("weight,value\n".as_bytes()),
+            Err(ImportError::Empty)
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip_and_errors() {
+        let input =
+            r#"[{"weight": 2, "value": "a"}, {"value": "b", "weight": 0}]"#;
+        let urn = Urn::from_json(input.as_bytes()).unwrap();
+        assert_eq!(urn.to_vec(), vec![(2, "a".to_string()), (0, "b".into())]);
+        let mut output = Vec::new();
+        urn.to_json(&mut output).unwrap();
+        assert_eq!(Urn::from_json(&output[..]).unwrap(), urn);
+
+        let row_error = |input: &str| match Urn::from_json(input.as_bytes()) {
+            Err(ImportError::Row { row, error }) => Some((row, error)),
+            _ => None,
+        };
+        assert_eq!(
+            row_error(
+                r#"[{"weight": 1, "value": "a"}, {"weight": 1.5, "value": "b"}]"#
+            ),
+            Some((2, RowError::InvalidWeight("1.5".into())))
+        );
+        assert_eq!(
+            row_error(r#"[{"value": "a"}]"#),
+            Some((1, RowError::MissingField("weight")))
+        );
+        assert_eq!(
+            row_error(r#"[{"weight": 1, "value": 7}]"#),
+            Some((1, RowError::InvalidValue))
+        );
+        assert!(matches!(
+            Urn::from_json("[{".as_bytes()),
+            Err(ImportError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            Urn::from_json("[]".as_bytes()),
+            Err(ImportError::Empty)
+        ));
+    }
+}
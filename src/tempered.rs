@@ -0,0 +1,95 @@
+use rand_core::RngCore;
+
+use crate::{random::unit_f64, types::Urn};
+
+/* -------------------------------------------------------------------------- */
+/*                        Tempered (softmax) sampling                         */
+/* -------------------------------------------------------------------------- */
+
+impl<T: Clone> Urn<T> {
+    /// Randomly samples (a reference to) an element using `rng`, with
+    /// probability proportional to `w^beta` (where `w` is its weight) rather
+    /// than `w`, without modifying the urn. The inverse temperature `beta`
+    /// interpolates between uniform sampling among the elements with a
+    /// positive weight (`beta = 0`), sampling proportionally to the weights
+    /// (`beta = 1`, as by `sample_ref`), and always sampling the heaviest
+    /// elements (as `beta` grows), e.g. to adjust the exploration of
+    /// a bandit policy without rebuilding the urn at every step.
+    /// Elements with weight 0 are never sampled, and `None` is returned if
+    /// every element has weight 0.
+    /// The transformed weights are computed on the fly as `f64`s, relative
+    /// to the maximum weight of the urn (so they don't overflow).
+    /// Panics if `beta` is negative or NaN.
+    /// Time complexity: `O(n)` (or `O(log n)` if `beta` is 1).
+    pub fn sample_tempered<R: RngCore + ?Sized>(
+        &self,
+        beta: f64,
+        rng: &mut R,
+    ) -> Option<&T> {
+        assert!(beta >= 0.0, "the inverse temperature must be non-negative");
+        let m = self.tree.max_weight();
+        if m == 0 {
+            return None;
+        }
+        if beta == 1.0 {
+            return Some(self.sample_ref(rng));
+        }
+        let tempered = |w| {
+            if w == 0 {
+                0.0
+            } else {
+                (w as f64 / m as f64).powf(beta)
+            }
+        };
+        // At least 1, since the heaviest element contributes `1^beta`
+        let total: f64 = self.iter().map(|(w, _)| tempered(w)).sum();
+        let u = unit_f64(rng) * total;
+        let mut acc = 0.0;
+        let mut last = None;
+        for (w, a) in self.iter().filter(|&(w, _)| w > 0) {
+            acc += tempered(w);
+            if u < acc {
+                return Some(a);
+            }
+            last = Some(a);
+        }
+        // Only reached if `u` exceeds the running sum due to rounding
+        last
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use crate::urn::{from_list, singleton};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn tempered_sampling_extremes() {
+        let urn = from_list(vec![(1, 'R'), (0, 'G'), (9, 'B'), (3, 'Y')]);
+        let urn = urn.unwrap();
+        let mut rng = StdRng::seed_from_u64(8);
+
+        // With `beta = 0`, every element with a positive weight is equally
+        // likely (so `'R'` is drawn about 1000 times out of 3000)
+        let mut counts = [0; 3];
+        for _ in 0..3000 {
+            match urn.sample_tempered(0.0, &mut rng) {
+                Some('R') => counts[0] += 1,
+                Some('B') => counts[1] += 1,
+                Some('Y') => counts[2] += 1,
+                other => panic!("unexpected sample {other:?}"),
+            }
+        }
+        assert!(counts.iter().all(|&c| (850..1150).contains(&c)));
+
+        // With a large `beta`, only the heaviest element is drawn
+        for _ in 0..100 {
+            assert_eq!(urn.sample_tempered(100.0, &mut rng), Some(&'B'));
+        }
+        assert_eq!(singleton(0, 'G').sample_tempered(0.5, &mut rng), None);
+    }
+}
@@ -0,0 +1,205 @@
+use std::slice;
+
+use rand_core::RngCore;
+
+use crate::types::{Index, Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                        Recording and replaying draws                       */
+/* -------------------------------------------------------------------------- */
+
+/// A record of the indexes drawn by a sequence of sampling operations
+/// (see `Urn::sample_traced` and `Urn::remove_traced`), which can be
+/// replayed to reproduce the exact same draws on an equal urn without any
+/// randomness (see `SampleTrace::replay`).
+///
+/// The indexes can be extracted (`into_indexes`) and turned back into a
+/// trace (`From<Vec<Index>>`), e.g. to store a trace in a log. With the
+/// `serde` feature enabled, traces also implement `serde::Serialize`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SampleTrace {
+    indexes: Vec<Index>,
+}
+
+impl SampleTrace {
+    /// Creates an empty trace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The no. of recorded draws
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// Whether no draws have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// The recorded indexes, in the order they were drawn
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    /// Consumes the trace, returning the recorded indexes
+    pub fn into_indexes(self) -> Vec<Index> {
+        self.indexes
+    }
+
+    /// Starts replaying the trace from its first draw
+    pub fn replay(&self) -> Replay<'_> {
+        Replay {
+            indexes: self.indexes.iter(),
+        }
+    }
+}
+
+impl From<Vec<Index>> for SampleTrace {
+    fn from(indexes: Vec<Index>) -> Self {
+        Self { indexes }
+    }
+}
+
+/// A cursor over the draws of a `SampleTrace`, which `Urn::sample_replayed`
+/// and `Urn::remove_replayed` consume in order instead of drawing indexes
+/// from a random number generator
+#[derive(Debug, Clone)]
+pub struct Replay<'a> {
+    indexes: slice::Iter<'a, Index>,
+}
+
+impl Replay<'_> {
+    /// The no. of draws which haven't been replayed yet
+    pub fn remaining(&self) -> usize {
+        self.indexes.len()
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Randomly samples (a reference to) an element using `rng`
+    /// (like `sample_ref`), recording the drawn index in `trace`.
+    /// Time complexity: `O(log n)`.
+    pub fn sample_traced<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+        trace: &mut SampleTrace,
+    ) -> &T {
+        let i = self.draw_index(rng);
+        trace.indexes.push(i);
+        self.sample_index_ref(i)
+    }
+
+    /// Removes a random element drawn using `rng` (like
+    /// `WeightedSampler::remove`), recording the drawn index in `trace`.
+    /// Time complexity: `O(log n)`.
+    pub fn remove_traced<R: RngCore + ?Sized>(
+        self,
+        rng: &mut R,
+        trace: &mut SampleTrace,
+    ) -> ((Weight, T), Option<Self>) {
+        let i = self.draw_index(rng);
+        trace.indexes.push(i);
+        self.remove_index(i)
+    }
+
+    /// The next index of `replay`, checking that it is a valid index of the
+    /// urn (which it is if the urn is equal to the one the draw was
+    /// recorded on)
+    fn next_replayed_index(&self, replay: &mut Replay) -> Option<Index> {
+        let i = *replay.indexes.next()?;
+        // Urns whose weights are all 0 draw the index 0
+        assert!(
+            i < self.weight().max(1),
+            "replayed index {} is out of range for an urn of weight {}",
+            i,
+            self.weight()
+        );
+        Some(i)
+    }
+
+    /// Samples (a reference to) the element at the next index of `replay`,
+    /// reproducing the draw recorded by `sample_traced` (or
+    /// `remove_traced`). Returns `None` once the whole trace has been
+    /// replayed.
+    /// Panics if the index is out of range for this urn.
+    /// Time complexity: `O(log n)`.
+    pub fn sample_replayed(&self, replay: &mut Replay) -> Option<&T> {
+        let i = self.next_replayed_index(replay)?;
+        Some(self.sample_index_ref(i))
+    }
+
+    /// Removes the element at the next index of `replay`, reproducing the
+    /// draw recorded by `remove_traced` (or `sample_traced`). Gives the urn
+    /// back in `Err` once the whole trace has been replayed.
+    /// Panics if the index is out of range for this urn.
+    /// Time complexity: `O(log n)`.
+    pub fn remove_replayed(
+        self,
+        replay: &mut Replay,
+    ) -> Result<((Weight, T), Option<Self>), Self> {
+        match self.next_replayed_index(replay) {
+            Some(i) => Ok(self.remove_index(i)),
+            None => Err(self),
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn replay_reproduces_draws() {
+        let urn: Urn<u32> =
+            from_list((1..=20).map(|k| (k as Weight, k)).collect()).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut trace = SampleTrace::new();
+        let sampled: Vec<u32> = (0..50)
+            .map(|_| *urn.sample_traced(&mut rng, &mut trace))
+            .collect();
+        let (mut removed, mut rest) = (vec![], Some(urn.clone()));
+        while let Some(urn) = rest {
+            let (elem, urn) = urn.remove_traced(&mut rng, &mut trace);
+            removed.push(elem);
+            rest = urn;
+        }
+        assert_eq!(trace.len(), 70);
+
+        // Replaying the (stored and restored) trace needs no RNG
+        let trace = SampleTrace::from(trace.into_indexes());
+        let mut replay = trace.replay();
+        let replayed: Vec<u32> = (0..50)
+            .map(|_| *urn.sample_replayed(&mut replay).unwrap())
+            .collect();
+        assert_eq!(replayed, sampled);
+        let mut rest = Some(urn);
+        for elem in removed {
+            let (replayed, urn) =
+                rest.unwrap().remove_replayed(&mut replay).unwrap();
+            assert_eq!(replayed, elem);
+            rest = urn;
+        }
+        assert_eq!(replay.remaining(), 0);
+        let urn = from_list(vec![(1, 0)]).unwrap();
+        assert_eq!(urn.sample_replayed(&mut replay), None);
+        assert!(urn.remove_replayed(&mut replay).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn replay_rejects_other_urns() {
+        let trace = SampleTrace::from(vec![5]);
+        let urn = from_list(vec![(2, 'a'), (4, 'b')]).unwrap();
+        assert_eq!(urn.sample_replayed(&mut trace.replay()), Some(&'b'));
+        let urn = from_list(vec![(2, 'a'), (1, 'b')]).unwrap();
+        urn.sample_replayed(&mut trace.replay());
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::{
+    types::{widen, Index, Urn, Weight},
+    urn::from_list,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                               Transactions                                 */
+/* -------------------------------------------------------------------------- */
+
+/// Errors arising from `Urn::transaction`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TransactionError<E> {
+    /// The transaction was aborted with the error returned by its closure
+    Aborted(E),
+    /// The transaction removed every element
+    /// (and urns can't be empty)
+    WouldEmpty,
+}
+
+impl<E: fmt::Display> fmt::Display for TransactionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Aborted(err) => {
+                write!(f, "transaction aborted: {}", err)
+            }
+            TransactionError::WouldEmpty => {
+                write!(f, "the transaction would empty the urn")
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for TransactionError<E> {}
+
+/// The staged mutations of a transaction, created by `Urn::transaction`.
+/// Elements are addressed by their position among the staged elements,
+/// which start out in the urn's leaf order.
+/// Mutations only edit a flat list of elements (no cached weight is
+/// updated), and the urn is rebuilt once when the transaction commits.
+#[derive(Debug, Clone)]
+pub struct Transaction<T> {
+    elems: Vec<(Weight, T)>,
+    weight: Index,
+}
+
+impl<T> Transaction<T> {
+    /// The no. of staged elements
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether every element has been removed
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// The total weight of the staged elements
+    pub fn weight(&self) -> Index {
+        self.weight
+    }
+
+    /// Retrieves the weight and (a reference to) the staged element at
+    /// position `pos` (`None` if `pos` is out of bounds)
+    pub fn get(&self, pos: usize) -> Option<(Weight, &T)> {
+        self.elems.get(pos).map(|(w, a)| (*w, a))
+    }
+
+    /// Stages the insertion of element `a` with weight `w`
+    /// (after all the staged elements).
+    /// Time complexity: `O(1)` (amortized).
+    pub fn insert(&mut self, w: Weight, a: T) {
+        self.weight = self.weight.wrapping_add(widen(w));
+        self.elems.push((w, a));
+    }
+
+    /// Stages the removal of the element at position `pos`, returning it
+    /// along with its weight (`None` if `pos` is out of bounds).
+    /// As with `Urn::remove_position`, the last staged element is moved
+    /// into position `pos`.
+    /// Time complexity: `O(1)`.
+    pub fn remove_position(&mut self, pos: usize) -> Option<(Weight, T)> {
+        if pos >= self.elems.len() {
+            return None;
+        }
+        let (w, a) = self.elems.swap_remove(pos);
+        self.weight = self.weight.wrapping_sub(widen(w));
+        Some((w, a))
+    }
+
+    /// Stages the removal of the element whose bucket contains index `i`
+    /// (see `Urn::remove_index`), returning it along with its weight
+    /// (`None` if `i` is not less than the total weight).
+    /// Time complexity: `O(n)`.
+    pub fn remove_index(&mut self, i: Index) -> Option<(Weight, T)> {
+        let mut ub: Index = 0;
+        let pos = self.elems.iter().position(|&(w, _)| {
+            ub = ub.wrapping_add(widen(w));
+            i < ub
+        })?;
+        self.remove_position(pos)
+    }
+
+    /// Stages setting the weight of the element at position `pos` to `w`,
+    /// returning its old weight (`None` if `pos` is out of bounds).
+    /// Time complexity: `O(1)`.
+    pub fn set_weight(&mut self, pos: usize, w: Weight) -> Option<Weight> {
+        let (w_old, _) = self.elems.get_mut(pos)?;
+        let w_old = std::mem::replace(w_old, w);
+        self.weight = self
+            .weight
+            .wrapping_sub(widen(w_old))
+            .wrapping_add(widen(w));
+        Some(w_old)
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Runs the transaction `f`, which stages mutations of the urn (see
+    /// `Transaction`), and applies them all at once if `f` returns `Ok`,
+    /// returning `f`'s result.
+    /// If `f` returns an error (or panics), or would remove every element,
+    /// the staged mutations are discarded and the urn is left untouched.
+    /// The committed urn is rebuilt as an almost perfect tree (see
+    /// `from_list`), so the cached weights are recomputed once per
+    /// transaction rather than once per mutation. It keeps the urn's
+    /// index-drawing strategy.
+    /// Time complexity: `O(n)` plus the time taken by `f`
+    /// (since the elements are cloned into the transaction).
+    pub fn transaction<F, R, E>(
+        &mut self,
+        f: F,
+    ) -> Result<R, TransactionError<E>>
+    where
+        F: FnOnce(&mut Transaction<T>) -> Result<R, E>,
+    {
+        let mut txn = Transaction {
+            elems: self.to_vec(),
+            weight: self.weight(),
+        };
+        let result = f(&mut txn).map_err(TransactionError::Aborted)?;
+        let urn = from_list(txn.elems).ok_or(TransactionError::WouldEmpty)?;
+        *self = urn.with_index_draw(self.draw);
+        self.debug_check_invariants();
+        Ok(result)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::IndexDraw, urn::from_list};
+
+    #[test]
+    fn transaction_commits_or_discards() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let mut urn = urn.with_index_draw(IndexDraw::Bitmask);
+
+        let removed = urn.transaction(|txn| {
+            txn.insert(5, 'Y');
+            assert_eq!(txn.set_weight(0, 1), Some(2));
+            let removed = txn.remove_index(2).ok_or("no such index")?;
+            assert_eq!(txn.weight(), 9);
+            Ok::<_, &str>(removed)
+        });
+        assert_eq!(removed, Ok((4, 'G')));
+        assert_eq!(urn.to_vec(), vec![(1, 'R'), (5, 'Y'), (3, 'B')]);
+        assert_eq!(urn.draw, IndexDraw::Bitmask);
+
+        // Neither an aborted transaction nor one emptying the urn
+        // modifies it
+        let before = urn.clone();
+        let aborted = urn.transaction(|txn| {
+            txn.insert(7, 'P');
+            txn.remove_index(100).ok_or("no such index")
+        });
+        assert_eq!(aborted, Err(TransactionError::Aborted("no such index")));
+        let emptied = urn.transaction(|txn| {
+            while txn.remove_position(0).is_some() {}
+            Ok::<_, ()>(())
+        });
+        assert_eq!(emptied, Err(TransactionError::WouldEmpty));
+        assert_eq!(urn, before);
+    }
+}
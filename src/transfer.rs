@@ -0,0 +1,118 @@
+use crate::{types::Urn, urn::singleton};
+
+/// Errors arising from `move_element`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MoveError {
+    /// The position of the element to move is out of bounds
+    /// for the source urn
+    OutOfBounds,
+    /// The source urn only contains the element to move
+    /// (and urns can't be empty)
+    WouldEmptySource,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::OutOfBounds => write!(f, "position out of bounds"),
+            MoveError::WouldEmptySource => {
+                write!(f, "moving the element would empty the source urn")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Moves the element at (leaf) position `pos` of the urn `from`
+/// into the urn `to`, keeping its weight.     
+/// The move is done in two phases: first, everything that may panic
+/// (cloning elements to use as placeholders) is done while neither urn
+/// has been modified, and then the element is removed from `from`
+/// (see `remove_position`) and inserted into `to`, which neither clones
+/// nor panics. Hence if anything panics during the move, both urns are left
+/// untouched, so an element can never be lost (or duplicated).    
+/// Time complexity: `O(log n)`.
+pub fn move_element<T: Clone>(
+    from: &mut Urn<T>,
+    to: &mut Urn<T>,
+    pos: u32,
+) -> Result<(), MoveError> {
+    if pos >= from.size() {
+        return Err(MoveError::OutOfBounds);
+    }
+    if from.size() == 1 {
+        return Err(MoveError::WouldEmptySource);
+    }
+
+    // Phase 1: prepare (neither `from` nor `to` is modified).
+    // The urns are moved out of `from` and `to` to be updated, and the
+    // placeholders fill in for them in the meantime.
+    let placeholder = |urn: &Urn<T>| {
+        let (_, a) = urn.iter().next().expect("urns are non-empty");
+        singleton(0, a.clone())
+    };
+    let (from_placeholder, to_placeholder) =
+        (placeholder(from), placeholder(to));
+
+    // Phase 2: commit
+    let ((w, a), new_from) =
+        std::mem::replace(from, from_placeholder).remove_position(pos);
+    *from = new_from.expect("source urn has at least 2 elements");
+    *to = std::mem::replace(to, to_placeholder).insert(w, a);
+    from.debug_check_invariants();
+    to.debug_check_invariants();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+
+    #[test]
+    fn move_element_example() {
+        let mut from = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let mut to = singleton(1, 'Y');
+        assert_eq!(move_element(&mut from, &mut to, 1), Ok(()));
+        assert_eq!((from.size(), from.weight()), (2, 5));
+        assert_eq!(to.to_vec(), vec![(1, 'Y'), (4, 'G')]);
+
+        let mut single = singleton(1, 'Z');
+        assert_eq!(
+            move_element(&mut single, &mut to, 0),
+            Err(MoveError::WouldEmptySource)
+        );
+        assert_eq!(
+            move_element(&mut from, &mut to, 2),
+            Err(MoveError::OutOfBounds)
+        );
+        assert_eq!(single, singleton(1, 'Z'));
+    }
+
+    // An element whose `clone` panics is never lost: the panic happens
+    // before either urn is modified
+    #[test]
+    fn panicking_clone_leaves_both_urns_untouched() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        #[derive(Debug, PartialEq)]
+        struct Fragile(u32);
+        impl Clone for Fragile {
+            fn clone(&self) -> Self {
+                panic!("cannot clone Fragile({})", self.0)
+            }
+        }
+
+        let mut from = singleton(2, Fragile(0)).insert(4, Fragile(1));
+        let mut to = singleton(1, Fragile(2));
+        let contents =
+            |urn: &Urn<Fragile>| urn.iter().map(|(w, a)| (w, a.0)).collect();
+        let before: (Vec<_>, Vec<_>) = (contents(&from), contents(&to));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            move_element(&mut from, &mut to, 0)
+        }));
+        assert!(result.is_err());
+        assert_eq!((contents(&from), contents(&to)), before);
+    }
+}
@@ -2,95 +2,531 @@
 /*                              Type Definitions                              */
 /* -------------------------------------------------------------------------- */
 
-/// Weights are guaranteed to be non-negative
+/// Weights are guaranteed to be non-negative.    
+/// The width of weights is selected at compile time using the
+/// `weight-u16`, `weight-u32` and `weight-u64` Cargo features
+/// (defaulting to `u8` if none are enabled). If several of these features
+/// are enabled, the widest one wins.
+#[cfg(not(any(
+    feature = "weight-u16",
+    feature = "weight-u32",
+    feature = "weight-u64"
+)))]
 pub type Weight = u8;
 
-/// Datatype for indexes (same as weights)
-pub type Index = Weight;
+#[cfg(all(
+    feature = "weight-u16",
+    not(any(feature = "weight-u32", feature = "weight-u64"))
+))]
+pub type Weight = u16;
+
+#[cfg(all(feature = "weight-u32", not(feature = "weight-u64")))]
+pub type Weight = u32;
+
+#[cfg(feature = "weight-u64")]
+pub type Weight = u64;
+
+/// Datatype for indexes into the range `[0, w)` of an urn's total weight `w`,
+/// and for total weights themselves (i.e. the weights cached in `Node`s).
+/// This is always `u64` (regardless of the width of `Weight`), so the total
+/// weight of many narrow weights doesn't wrap around.
+pub type Index = u64;
+
+/// Widens a `Weight` into an `Index`
+#[allow(clippy::useless_conversion)] // `Weight` may be `u64` too
+pub(crate) fn widen(w: Weight) -> Index {
+    Index::from(w)
+}
 
 /// Polymorphic binary trees, with a weight at each node/leaf.      
-/// Invariant: `Node(w, l, r).weight() == l.weight() + r.weight()`
-#[derive(Debug, PartialEq, Clone)]
+/// Each node caches the total weight of its leaves (as an `Index`, which is
+/// at least as wide as `Weight`), the maximum weight of any leaf below it
+/// (used to prune subtrees when sampling, see `Urn::sample_at_least`), and
+/// the minimum weight of any leaf below it (used to find the lightest
+/// element, see `Urn::min_weight_element`).     
+/// Invariants:
+/// - `Node(w, m, k, l, r).weight() == l.weight() + r.weight()`
+/// - `Node(w, m, k, l, r).max_weight() == max(l.max_weight(), r.max_weight())`
+/// - `Node(w, m, k, l, r).min_weight() == min(l.min_weight(), r.min_weight())`
+///
+/// Use the smart constructor `Tree::node` to build `Node`s,
+/// which establishes all three invariants.
+/// (With the `metrics` feature, `Clone` is implemented in `metrics.rs`,
+/// so that clones are counted.)
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "metrics"), derive(Clone))]
 pub enum Tree<T: Clone> {
     Leaf(Weight, T),
-    Node(Weight, Box<Tree<T>>, Box<Tree<T>>),
+    Node(Index, Weight, Weight, Box<Tree<T>>, Box<Tree<T>>),
 }
 
 /// An `Urn` is a `Tree`, along with its `size`.         
 /// Note: the same distribution can have multiple tree representations
 /// (see Fig. 4 in the paper), and the order of values in an urn doesn't
 /// matter (see section 3.4).
-#[derive(Debug, PartialEq, Clone)]
+/// (`Debug` shows the urn's size, depth and elements rather than its
+/// nested tree, see `display.rs`. Use `{:?}` on `urn.tree` to see the tree.)
+///
+/// `==` compares urns *structurally*: two urns are equal iff their trees have
+/// the same shape and the same (weight, element) pairs at the same leaves,
+/// and they use the same index-drawing strategy. Hence urns representing the
+/// same distribution may compare unequal; use `Urn::same_distribution`
+/// to compare their contents (or compare their `Urn::canonicalize`d forms).
+/// Hashing an urn (see `canonical.rs`) only depends on its contents, so
+/// canonicalized urns can be used as keys of a `HashMap` to memoize
+/// computations on distributions.
+#[derive(PartialEq, Eq, Clone)]
 pub struct Urn<T: Clone> {
     pub size: u32,
     pub tree: Tree<T>,
+    /// How indexes are drawn when sampling from the urn
+    pub draw: IndexDraw,
+}
+
+/// Strategies for drawing a uniformly random index from the range `[0, w)`
+/// (where `w` is an urn's total weight) when sampling from an urn.     
+/// All strategies are unbiased, and only consume `RngCore::next_u64`, so
+/// they produce the same indexes on every platform (and with every version
+/// of `rand`) for a given stream of random numbers.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub enum IndexDraw {
+    /// Use the uniform range sampling algorithm of `rand` 0.8
+    /// (`Rng::gen_range`)
+    #[default]
+    Uniform,
+    /// Lemire's nearly divisionless method: multiply a random `u64` by `w`,
+    /// keep the high 64 bits, and reject the (rare) draws
+    /// which fall into the biased low range
+    Lemire,
+    /// Mask a random `u64` down to the bit width of `w - 1`, rejecting draws
+    /// that are at least `w` (at most half of all draws are rejected)
+    Bitmask,
+}
+
+/// What to do with an element whose weight is adjusted down to zero
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ZeroWeightPolicy {
+    /// Keep the element in the urn with weight 0
+    /// (it can no longer be sampled)
+    Keep,
+    /// Remove the element from the urn
+    Remove,
+}
+
+/// Errors arising from checked arithmetic on weights
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WeightError {
+    /// The new weight of an element would be negative
+    Underflow,
+    /// The new weight of an element would exceed `Weight::MAX`
+    /// (or the total weight of the urn would exceed `Index::MAX`)
+    Overflow,
 }
 
+impl std::fmt::Display for WeightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightError::Underflow => write!(f, "weight underflow"),
+            WeightError::Overflow => write!(f, "weight overflow"),
+        }
+    }
+}
+
+impl std::error::Error for WeightError {}
+
 /* -------------------------------------------------------------------------- */
 /*                             Methods for Tree<T>                            */
 /* -------------------------------------------------------------------------- */
 
+use crate::metrics::record_node_allocated;
 use Tree::*;
 
 impl<T: Clone> Tree<T> {
-    /// Retrieves the weight of a tree
-    pub fn weight(&self) -> Weight {
+    /// Smart constructor for `Node`s, which computes the node's weight and
+    /// maximum and minimum leaf weights from its two subtrees
+    pub fn node(l: Box<Tree<T>>, r: Box<Tree<T>>) -> Tree<T> {
+        let w = l.weight().wrapping_add(r.weight());
+        let m = l.max_weight().max(r.max_weight());
+        let k = l.min_weight().min(r.min_weight());
+        record_node_allocated();
+        Node(w, m, k, l, r)
+    }
+
+    /// Retrieves the (total) weight of a tree
+    pub fn weight(&self) -> Index {
+        match self {
+            Leaf(w, _) => widen(*w),
+            Node(w, _, _, _, _) => *w,
+        }
+    }
+
+    /// Retrieves the maximum weight of any leaf in the tree
+    pub fn max_weight(&self) -> Weight {
         match self {
             Leaf(w, _) => *w,
-            Node(w, _, _) => *w,
+            Node(_, m, _, _, _) => *m,
         }
     }
 
-    /// Samples the value at index `i` from a `tree`
-    pub fn sample_index(&self, i: Index) -> T {
+    /// Retrieves the minimum weight of any leaf in the tree
+    pub fn min_weight(&self) -> Weight {
         match self {
-            Leaf(_, a) => a.clone(),
-            Node(_, l, r) => {
+            Leaf(w, _) => *w,
+            Node(_, _, k, _, _) => *k,
+        }
+    }
+
+    /// Iterates over the leaves of the tree from left to right,
+    /// yielding each leaf's weight and (a reference to) its value
+    pub fn leaves(&self) -> Leaves<'_, T> {
+        self.leaves_at_least(0)
+    }
+
+    /// Consumes the tree, collecting its leaves (from left to right) into
+    /// `out` without cloning them. Like `leaves`, this uses an explicit stack.
+    pub fn into_leaves(self, out: &mut Vec<(Weight, T)>) {
+        let mut stack = vec![self];
+        while let Some(tree) = stack.pop() {
+            match tree {
+                Leaf(w, a) => out.push((w, a)),
+                Node(_, _, _, l, r) => {
+                    stack.push(*r);
+                    stack.push(*l);
+                }
+            }
+        }
+    }
+
+    /// Iterates over the leaves whose weight is at least `min_w`
+    /// (from left to right). Subtrees whose maximum leaf weight is
+    /// less than `min_w` are skipped without being traversed.
+    pub fn leaves_at_least(&self, min_w: Weight) -> Leaves<'_, T> {
+        Leaves {
+            stack: vec![self],
+            min_w,
+        }
+    }
+
+    /// Retrieves the weight and (a reference to) the value at index `i`
+    pub fn lookup_index(&self, i: Index) -> (Weight, &T) {
+        match self {
+            Leaf(w, a) => (*w, a),
+            Node(_, _, _, l, r) => {
                 let wl = l.weight();
                 if i < wl {
-                    l.sample_index(i)
+                    l.lookup_index(i)
+                } else {
+                    r.lookup_index(i - wl)
+                }
+            }
+        }
+    }
+
+    /// Sets the weight of the element at index `i` to `w_new`, returning
+    /// the element's old weight along with the new tree
+    pub fn reweight_index(self, w_new: Weight, i: Index) -> (Weight, Self) {
+        match self {
+            Leaf(w, a) => (w, Leaf(w_new, a)),
+            Node(_, _, _, l, r) => {
+                let wl = l.weight();
+                if i < wl {
+                    let (w_old, l_new) = l.reweight_index(w_new, i);
+                    (w_old, Tree::node(Box::new(l_new), r))
+                } else {
+                    let (w_old, r_new) = r.reweight_index(w_new, i - wl);
+                    (w_old, Tree::node(l, Box::new(r_new)))
+                }
+            }
+        }
+    }
+
+    /// Retrieves the weight and (a reference to) the value of the `pos`-th
+    /// leaf (from the left) of a tree with `n` leaves
+    pub fn lookup_position(&self, n: u32, pos: u32) -> (Weight, &T) {
+        match self {
+            Leaf(w, a) => (*w, a),
+            Node(_, _, _, l, r) => {
+                let nl = n.div_ceil(2);
+                if pos < nl {
+                    l.lookup_position(nl, pos)
+                } else {
+                    r.lookup_position(n - nl, pos - nl)
+                }
+            }
+        }
+    }
+
+    /// Retrieves the weight and (a reference to) the value at the end of the
+    /// insertion `path` (see `Urn::insert`)
+    pub fn lookup_path(&self, path: u32) -> (Weight, &T) {
+        match self {
+            Leaf(w, a) => (*w, a),
+            Node(_, _, _, l, r) => {
+                if path & 1 == 1 {
+                    r.lookup_path(path >> 1)
+                } else {
+                    l.lookup_path(path >> 1)
+                }
+            }
+        }
+    }
+
+    /// Like `lookup_path`, but retrieves a mutable reference to the value
+    /// (the weight can't be modified this way, since the weights cached at
+    /// the leaf's ancestors would need to be repaired, see `modify_path`)
+    pub fn lookup_path_mut(&mut self, path: u32) -> (Weight, &mut T) {
+        match self {
+            Leaf(w, a) => (*w, a),
+            Node(_, _, _, l, r) => {
+                if path & 1 == 1 {
+                    r.lookup_path_mut(path >> 1)
+                } else {
+                    l.lookup_path_mut(path >> 1)
+                }
+            }
+        }
+    }
+
+    /// Finds the leaf whose bucket contains index `i`, returning its
+    /// insertion path (see `Urn::insert`), weight and value
+    pub fn locate_index(&self, i: Index) -> (u32, Weight, &T) {
+        let (mut tree, mut i, mut path, mut depth) = (self, i, 0, 0);
+        loop {
+            match tree {
+                Leaf(w, a) => return (path, *w, a),
+                Node(_, _, _, l, r) => {
+                    let wl = l.weight();
+                    if i < wl {
+                        tree = l;
+                    } else {
+                        path |= 1 << depth;
+                        (tree, i) = (r, i - wl);
+                    }
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Finds a leaf with the minimum weight (the leftmost one if there are
+    /// several), returning its insertion path (see `Urn::insert`), weight
+    /// and value, by descending into a subtree with the minimum weight
+    pub fn locate_min(&self) -> (u32, Weight, &T) {
+        self.locate_with(|l, r| l.min_weight() <= r.min_weight())
+    }
+
+    /// Finds a leaf with the maximum weight (the leftmost one if there are
+    /// several), returning its insertion path (see `Urn::insert`), weight
+    /// and value, by descending into a subtree with the maximum weight
+    pub fn locate_max(&self) -> (u32, Weight, &T) {
+        self.locate_with(|l, r| l.max_weight() >= r.max_weight())
+    }
+
+    /// Descends from the root to a leaf, going left at every node where
+    /// `go_left(l, r)` holds, returning the leaf's insertion path,
+    /// weight and value
+    fn locate_with<F>(&self, go_left: F) -> (u32, Weight, &T)
+    where
+        F: Fn(&Tree<T>, &Tree<T>) -> bool,
+    {
+        let (mut tree, mut path, mut depth) = (self, 0, 0);
+        loop {
+            match tree {
+                Leaf(w, a) => return (path, *w, a),
+                Node(_, _, _, l, r) => {
+                    if go_left(l, r) {
+                        tree = l;
+                    } else {
+                        path |= 1 << depth;
+                        tree = r;
+                    }
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Sets the weight of the leaf at the end of the insertion `path`
+    /// to `w_new` in place, repairing the cached weights of all its ancestors.
+    /// Returns the leaf's old weight.
+    pub fn reweight_path(&mut self, path: u32, w_new: Weight) -> Weight {
+        match self {
+            Leaf(w, _) => std::mem::replace(w, w_new),
+            Node(w, m, k, l, r) => {
+                let w_old = if path & 1 == 1 {
+                    r.reweight_path(path >> 1, w_new)
+                } else {
+                    l.reweight_path(path >> 1, w_new)
+                };
+                *w = l.weight().wrapping_add(r.weight());
+                *m = l.max_weight().max(r.max_weight());
+                *k = l.min_weight().min(r.min_weight());
+                w_old
+            }
+        }
+    }
+
+    /// Applies `f` to the weight and value of the leaf at the end of the
+    /// insertion `path` in place, repairing the cached weights of all its
+    /// ancestors. Returns the result of `f`.
+    pub fn modify_path<F, R>(&mut self, path: u32, f: F) -> R
+    where
+        F: FnOnce(&mut Weight, &mut T) -> R,
+    {
+        match self {
+            Leaf(w, a) => f(w, a),
+            Node(w, m, k, l, r) => {
+                let result = if path & 1 == 1 {
+                    r.modify_path(path >> 1, f)
+                } else {
+                    l.modify_path(path >> 1, f)
+                };
+                *w = l.weight().wrapping_add(r.weight());
+                *m = l.max_weight().max(r.max_weight());
+                *k = l.min_weight().min(r.min_weight());
+                result
+            }
+        }
+    }
+
+    /// Sets the weight of the `pos`-th leaf (from the left) of a tree with
+    /// `n` leaves to `w_new` in place, repairing the cached weights of all
+    /// its ancestors. Returns the leaf's old weight.     
+    /// (This relies on the fact that the left subtree of a node with `n`
+    /// leaves has `(n + 1) / 2` leaves, see `Urn::cumulative_weight_before`.)
+    pub fn reweight_position(
+        &mut self,
+        n: u32,
+        pos: u32,
+        w_new: Weight,
+    ) -> Weight {
+        match self {
+            Leaf(w, _) => std::mem::replace(w, w_new),
+            Node(w, m, k, l, r) => {
+                let nl = n.div_ceil(2);
+                let w_old = if pos < nl {
+                    l.reweight_position(nl, pos, w_new)
                 } else {
-                    r.sample_index(i - wl)
+                    r.reweight_position(n - nl, pos - nl, w_new)
+                };
+                *w = l.weight().wrapping_add(r.weight());
+                *m = l.max_weight().max(r.max_weight());
+                *k = l.min_weight().min(r.min_weight());
+                w_old
+            }
+        }
+    }
+
+    /// Replaces the weight `w` of every leaf with `f(w)` in place,
+    /// recomputing the cached weights of all internal nodes.      
+    /// Time complexity: `O(n)`.
+    pub fn map_weights<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(Weight) -> Weight,
+    {
+        self.map_weights_with(&mut |w, _| f(w))
+    }
+
+    /// Replaces the weight `w` of every leaf with value `a` with `f(w, a)`
+    /// in place (in leaf order), recomputing the cached weights of all
+    /// internal nodes.      
+    /// Time complexity: `O(n)`.
+    pub fn map_weights_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(Weight, &T) -> Weight,
+    {
+        match self {
+            Leaf(w, a) => *w = f(*w, a),
+            Node(w, m, k, l, r) => {
+                l.map_weights_with(f);
+                r.map_weights_with(f);
+                *w = l.weight().wrapping_add(r.weight());
+                *m = l.max_weight().max(r.max_weight());
+                *k = l.min_weight().min(r.min_weight());
+            }
+        }
+    }
+
+    /// Replaces every leaf `(w, a)` with `f(w, a)` (in leaf order),
+    /// consuming the tree and recomputing the cached weights of all
+    /// internal nodes in the same pass. The shape of the tree is unchanged.      
+    /// Time complexity: `O(n)`.
+    pub fn update_all_with<U, F>(self, f: &mut F) -> Tree<U>
+    where
+        U: Clone,
+        F: FnMut(Weight, T) -> (Weight, U),
+    {
+        match self {
+            Leaf(w, a) => {
+                let (w_new, a_new) = f(w, a);
+                Leaf(w_new, a_new)
+            }
+            Node(_, _, _, l, r) => {
+                let l_new = l.update_all_with(f);
+                let r_new = r.update_all_with(f);
+                Tree::node(Box::new(l_new), Box::new(r_new))
+            }
+        }
+    }
+
+    /// Samples the value at index `i` from a `tree`
+    /// (cloning it, see `sample_index_ref` for a non-cloning version)
+    pub fn sample_index(&self, i: Index) -> T {
+        self.sample_index_ref(i).clone()
+    }
+
+    /// Samples (a reference to) the value at index `i` from a `tree`
+    pub fn sample_index_ref(&self, i: Index) -> &T {
+        let (mut tree, mut i) = (self, i);
+        loop {
+            match tree {
+                Leaf(_, a) => return a,
+                Node(_, _, _, l, r) => {
+                    let wl = l.weight();
+                    if i < wl {
+                        tree = l;
+                    } else {
+                        (tree, i) = (r, i - wl);
+                    }
                 }
             }
         }
     }
 
-    /// `t.update(f, i)` samples an element from the tree `t`, then replaces the
-    /// chosen element `a` and its weight `w` by a new element `a_new`
-    /// with weight `w_new`, where `(w_new, a_new) = f(w, a)`.    
+    /// `t.update_index(f, i)` replaces the element `a` at index `i` and its
+    /// weight `w` by a new element `a_new` with weight `w_new`,
+    /// where `(w_new, a_new) = f(w, a)`.
     /// This function returns a triple `((w, a), (w_new, a_new), t_new)`,
     /// where `t_new` is the same tree as `t`,
     /// but with `(w, a)` replaced by `(w_new, a_new)`.
+    /// The tree is consumed, so its subtrees are moved rather than cloned
+    /// (only `a` and `a_new` are cloned, so that they can be returned).
     pub fn update_index<F>(
-        &self,
+        self,
         f: F,
         i: Index,
-    ) -> ((Weight, &T), (Weight, &T), Self)
+    ) -> ((Weight, T), (Weight, T), Self)
     where
-        F: FnOnce(Weight, &T) -> (Weight, &T),
+        F: FnOnce(Weight, T) -> (Weight, T),
     {
         match self {
             Leaf(w, a) => {
-                let (w_new, a_new) = f(*w, a);
-                ((*w, a), (w_new, a_new), Leaf(w_new, a_new.clone()))
+                let old = (w, a.clone());
+                let (w_new, a_new) = f(w, a);
+                (old, (w_new, a_new.clone()), Leaf(w_new, a_new))
             }
-            Node(w, l, r) => {
+            Node(_, _, _, l, r) => {
                 let wl = l.weight();
                 if i < wl {
                     let (old, new, l_new) = l.update_index(f, i);
-                    (
-                        old,
-                        new,
-                        Node(w - old.0 + new.0, Box::new(l_new), r.clone()),
-                    )
+                    (old, new, Tree::node(Box::new(l_new), r))
                 } else {
                     let (old, new, r_new) = r.update_index(f, i - wl);
-                    (
-                        old,
-                        new,
-                        Node(w - old.0 + new.0, l.clone(), Box::new(r_new)),
-                    )
+                    (old, new, Tree::node(l, Box::new(r_new)))
                 }
             }
         }
@@ -107,29 +543,101 @@ impl<T: Clone> Tree<T> {
     ) -> ((Weight, &T), Self) {
         match self {
             Leaf(w, a) => ((*w, a), Leaf(w_outer, a_outer.clone())),
-            Node(w, l, r) => {
+            Node(_, _, _, l, r) => {
                 let wl = l.weight();
                 if i < wl {
                     let (old, l_new) = l.replace_index(w_outer, a_outer, i);
-                    (
-                        old,
-                        Node(
-                            w.wrapping_sub(old.0).wrapping_add(w_outer),
-                            Box::new(l_new),
-                            r.clone(),
-                        ),
-                    )
+                    (old, Tree::node(Box::new(l_new), r.clone()))
                 } else {
                     let (old, r_new) =
                         r.replace_index(w_outer, a_outer, i - wl);
-                    (
-                        old,
-                        Node(
-                            w.wrapping_sub(old.0).wrapping_add(w_outer),
-                            l.clone(),
-                            Box::new(r_new),
-                        ),
-                    )
+                    (old, Tree::node(l.clone(), Box::new(r_new)))
+                }
+            }
+        }
+    }
+
+    /// Owned counterpart of `replace_index`: consumes the tree, moving the
+    /// element at index `i` out and moving `(w_outer, a_outer)` into its
+    /// bucket. Since subtrees are moved rather than borrowed,
+    /// neither the subtrees nor the elements are ever cloned.
+    pub fn replace_index_owned(
+        self,
+        w_outer: Weight,
+        a_outer: T,
+        i: Index,
+    ) -> ((Weight, T), Self) {
+        match self {
+            Leaf(w, a) => ((w, a), Leaf(w_outer, a_outer)),
+            Node(_, _, _, l, r) => {
+                let wl = l.weight();
+                if i < wl {
+                    let (old, l_new) =
+                        l.replace_index_owned(w_outer, a_outer, i);
+                    (old, Tree::node(Box::new(l_new), r))
+                } else {
+                    let (old, r_new) =
+                        r.replace_index_owned(w_outer, a_outer, i - wl);
+                    (old, Tree::node(l, Box::new(r_new)))
+                }
+            }
+        }
+    }
+
+    /// Like `replace_index_owned`, but locates the element to replace by
+    /// following the insertion `path` (see `Urn::insert`) instead of an index
+    pub fn replace_path_owned(
+        self,
+        w_outer: Weight,
+        a_outer: T,
+        path: u32,
+    ) -> ((Weight, T), Self) {
+        match self {
+            Leaf(w, a) => ((w, a), Leaf(w_outer, a_outer)),
+            Node(_, _, _, l, r) => {
+                let new_path = path >> 1;
+                if path & 1 == 1 {
+                    let (old, r_new) =
+                        r.replace_path_owned(w_outer, a_outer, new_path);
+                    (old, Tree::node(l, Box::new(r_new)))
+                } else {
+                    let (old, l_new) =
+                        l.replace_path_owned(w_outer, a_outer, new_path);
+                    (old, Tree::node(Box::new(l_new), r))
+                }
+            }
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                  Iterators                                 */
+/* -------------------------------------------------------------------------- */
+
+/// Iterator over the leaves of a `Tree` (from left to right), created by
+/// `Tree::leaves` and `Tree::leaves_at_least`. The traversal uses an
+/// explicit stack (rather than recursion), so it can't overflow the call stack.
+#[derive(Debug, Clone)]
+pub struct Leaves<'a, T: Clone> {
+    stack: Vec<&'a Tree<T>>,
+    /// Leaves with weight less than `min_w` are skipped
+    min_w: Weight,
+}
+
+impl<'a, T: Clone> Iterator for Leaves<'a, T> {
+    type Item = (Weight, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let tree = self.stack.pop()?;
+            if tree.max_weight() < self.min_w {
+                continue;
+            }
+            match tree {
+                Leaf(w, a) => return Some((*w, a)),
+                Node(_, _, _, l, r) => {
+                    self.stack.push(r);
+                    self.stack.push(l);
                 }
             }
         }
@@ -0,0 +1,279 @@
+use crate::{
+    types::{Index, IndexDraw, Urn, Weight},
+    urn::{path_of_position, singleton},
+};
+
+/* -------------------------------------------------------------------------- */
+/*                         Checkpoints and rollbacks                          */
+/* -------------------------------------------------------------------------- */
+
+/// A checkpoint of an `UndoUrn`, created by `UndoUrn::checkpoint`,
+/// which the urn can be rolled back to
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Checkpoint {
+    id: u64,
+}
+
+/// A logged mutation of an `UndoUrn`, with enough information to undo it
+#[derive(Debug, PartialEq, Clone)]
+enum UndoOp<T> {
+    /// An element was inserted (at the end of the insertion path `size`)
+    Insert,
+    /// The element `elem` with insertion path `k` was removed
+    /// (see `Urn::remove_nth`), and the urn used the strategy `draw`
+    Remove {
+        k: u32,
+        elem: (Weight, T),
+        draw: IndexDraw,
+    },
+    /// The weight of the element at the end of the insertion path `k`
+    /// was changed from `w_old`
+    Reweight { k: u32, w_old: Weight },
+}
+
+/// An urn which logs its mutations, so that it can be rolled back to any
+/// earlier checkpoint (e.g. to try a few removals speculatively during a
+/// search, and then undo them).
+///
+/// Every mutation is undone by its exact inverse: an insertion by
+/// `uninsert`, a removal (which moves the most recently inserted element into
+/// the vacated leaf) by moving that element back and re-inserting the
+/// removed one, and a reweighting by restoring the old weight. Hence a
+/// rolled back urn is structurally equal (`==`) to the urn at the checkpoint,
+/// and rolling back `m` mutations takes `O(m log n)` time,
+/// independently of the urn's size.
+///
+/// The log grows with every mutation until `forget` is called.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndoUrn<T: Clone> {
+    urn: Option<Urn<T>>,
+    log: Vec<UndoOp<T>>,
+    /// The live checkpoints (oldest first), with the length of the log
+    /// when each checkpoint was taken
+    checkpoints: Vec<(u64, usize)>,
+    next_id: u64,
+}
+
+impl<T: Clone> Default for UndoUrn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> From<Urn<T>> for UndoUrn<T> {
+    fn from(urn: Urn<T>) -> Self {
+        Self {
+            urn: Some(urn),
+            ..Self::new()
+        }
+    }
+}
+
+impl<T: Clone> UndoUrn<T> {
+    /// Creates an empty `UndoUrn`
+    pub fn new() -> Self {
+        Self {
+            urn: None,
+            log: Vec::new(),
+            checkpoints: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// The underlying urn (`None` if there are no elements)
+    pub fn urn(&self) -> Option<&Urn<T>> {
+        self.urn.as_ref()
+    }
+
+    /// Consumes the `UndoUrn`, returning the underlying urn
+    /// (`None` if there are no elements)
+    pub fn into_urn(self) -> Option<Urn<T>> {
+        self.urn
+    }
+
+    /// The no. of logged mutations
+    pub fn log_len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Inserts element `a` with weight `w` (see `Urn::insert`).
+    /// Time complexity: `O(log n)`.
+    pub fn insert(&mut self, w: Weight, a: T) {
+        self.urn = Some(match self.urn.take() {
+            None => singleton(w, a),
+            Some(urn) => urn.insert(w, a),
+        });
+        self.log.push(UndoOp::Insert);
+    }
+
+    /// Removes the element at index `i`, returning it along with its weight
+    /// (or `None` if there are no elements).
+    /// Time complexity: `O(log n)`.
+    pub fn remove_index(&mut self, i: Index) -> Option<(Weight, T)> {
+        let k = self.urn.as_ref()?.tree.locate_index(i).0;
+        Some(self.remove_nth(k))
+    }
+
+    /// Removes the element at (leaf) position `pos`, returning it along
+    /// with its weight (see `Urn::remove_position`).
+    /// Panics if there are no more than `pos` elements.
+    /// Time complexity: `O(log n)`.
+    pub fn remove_position(&mut self, pos: u32) -> (Weight, T) {
+        let size = self.urn.as_ref().map_or(0, |urn| urn.size());
+        assert!(
+            pos < size,
+            "position {} out of bounds for urn of size {}",
+            pos,
+            size
+        );
+        self.remove_nth(path_of_position(size, pos))
+    }
+
+    /// Removes the element with insertion path `k`, logging it
+    fn remove_nth(&mut self, k: u32) -> (Weight, T) {
+        let urn = self.urn.take().expect("urns are non-empty");
+        let draw = urn.draw;
+        let ((w, a), urn_opt) = urn.remove_nth(k);
+        self.urn = urn_opt;
+        self.log.push(UndoOp::Remove {
+            k,
+            elem: (w, a.clone()),
+            draw,
+        });
+        (w, a)
+    }
+
+    /// Sets the weight of the element at (leaf) position `pos` to `w`,
+    /// returning its old weight.
+    /// Panics if there are no more than `pos` elements.
+    /// Time complexity: `O(log n)`.
+    pub fn set_weight(&mut self, pos: u32, w: Weight) -> Weight {
+        let size = self.urn.as_ref().map_or(0, |urn| urn.size());
+        assert!(
+            pos < size,
+            "position {} out of bounds for urn of size {}",
+            pos,
+            size
+        );
+        let k = path_of_position(size, pos);
+        let urn = self.urn.as_mut().expect("urns are non-empty");
+        let w_old = urn.tree.reweight_path(k, w);
+        self.log.push(UndoOp::Reweight { k, w_old });
+        w_old
+    }
+
+    /// Takes a checkpoint of the current state of the urn.
+    /// Time complexity: `O(1)`.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.checkpoints.push((id, self.log.len()));
+        Checkpoint { id }
+    }
+
+    /// Restores the state of the urn when `checkpoint` was taken, by undoing
+    /// the mutations logged since then (most recent first).
+    /// The checkpoint stays valid (so the urn can be rolled back to it
+    /// again), but all later checkpoints are invalidated.
+    /// Panics if `checkpoint` has been invalidated (by rolling back to an
+    /// earlier checkpoint or by `forget`).
+    /// Time complexity: `O(m log n)`, where `m` is the no. of mutations undone.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        let j = self
+            .checkpoints
+            .iter()
+            .rposition(|&(id, _)| id == checkpoint.id)
+            .expect("checkpoint has been invalidated");
+        let len = self.checkpoints[j].1;
+        self.checkpoints.truncate(j + 1);
+        while self.log.len() > len {
+            let op = self.log.pop().expect("the log is longer than `len`");
+            self.undo(op);
+        }
+    }
+
+    /// Clears the log, invalidating all checkpoints
+    pub fn forget(&mut self) {
+        self.log.clear();
+        self.checkpoints.clear();
+    }
+
+    /// Undoes the mutation `op`, which must be the last logged mutation
+    fn undo(&mut self, op: UndoOp<T>) {
+        let urn = self.urn.take();
+        self.urn = match op {
+            UndoOp::Insert => urn.and_then(|urn| urn.uninsert().2),
+            UndoOp::Remove {
+                k,
+                elem: (w, a),
+                draw,
+            } => Some(match urn {
+                None => singleton(w, a).with_index_draw(draw),
+                // The removed element was the most recently inserted one
+                Some(urn) if k == urn.size => urn.insert(w, a),
+                // Move the most recently inserted element back to the end
+                Some(Urn { size, tree, draw }) => {
+                    let ((w_moved, moved), tree) =
+                        tree.replace_path_owned(w, a, k);
+                    Urn { size, tree, draw }.insert(w_moved, moved)
+                }
+            }),
+            UndoOp::Reweight { k, w_old } => urn.map(|mut urn| {
+                urn.tree.reweight_path(k, w_old);
+                urn
+            }),
+        };
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+
+    #[test]
+    fn rollback_restores_the_urn() {
+        let elems: Vec<(Weight, u32)> =
+            (0..10).map(|k| (k as Weight, k)).collect();
+        let urn = from_list(elems).unwrap().with_index_draw(IndexDraw::Lemire);
+        let mut undo = UndoUrn::from(urn.clone());
+
+        let start = undo.checkpoint();
+        assert_eq!(undo.remove_position(3), (3, 3));
+        undo.insert(7, 70);
+        let middle = undo.checkpoint();
+        let snapshot = undo.urn().cloned();
+        assert_eq!(undo.set_weight(0, 9), 0);
+        assert_eq!(undo.remove_index(0), Some((9, 0)));
+        for _ in 0..9 {
+            undo.remove_position(0);
+        }
+        assert_eq!(undo.urn(), None);
+
+        undo.rollback(middle);
+        assert_eq!(undo.urn().cloned(), snapshot);
+        // The same checkpoint can be rolled back to several times
+        undo.remove_position(2);
+        undo.rollback(middle);
+        assert_eq!(undo.urn().cloned(), snapshot);
+
+        undo.rollback(start);
+        assert_eq!(undo.urn(), Some(&urn));
+        assert_eq!(undo.log_len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalidated")]
+    fn rolling_back_invalidates_later_checkpoints() {
+        let mut undo = UndoUrn::new();
+        let start = undo.checkpoint();
+        undo.insert(1, 'a');
+        let later = undo.checkpoint();
+        undo.rollback(start);
+        undo.rollback(later);
+    }
+}
@@ -1,14 +1,16 @@
-#![allow(dead_code)]
-
 use crate::{
-    almost_perfect::almost_perfect,
+    almost_perfect::{almost_perfect, almost_perfect_iter},
+    metrics::record_sample_drawn,
+    random::{below_u128, below_u64},
     types::{
-        Index,
+        widen, Index, IndexDraw, Leaves,
         Tree::{self, *},
-        Urn, Weight,
+        Urn, Weight, WeightError, ZeroWeightPolicy,
     },
 };
-use rand::prelude::*;
+#[cfg(feature = "std-rng")]
+use rand::thread_rng;
+use rand_core::RngCore;
 
 /* -------------------------------------------------------------------------- */
 /*                                   Helpers                                  */
@@ -16,8 +18,8 @@ use rand::prelude::*;
 
 /// Smart constructor for `Node`s
 /// (automatically wraps the two subtrees in `Box`es)
-fn node<T: Clone>(w: Weight, l: Tree<T>, r: Tree<T>) -> Tree<T> {
-    Node(w, Box::new(l), Box::new(r))
+fn node<T: Clone>(l: Tree<T>, r: Tree<T>) -> Tree<T> {
+    Tree::node(Box::new(l), Box::new(r))
 }
 
 /// Alias for the `Leaf` constructor
@@ -31,12 +33,101 @@ fn test_bit(input: u32, n: u32) -> bool {
     (input & (1 << n)) != 0
 }
 
-/// Produces a value uniformly at random from the range `[0, w]`
-fn sample_weight(w: Weight) -> Weight {
-    let mut rng = thread_rng();
-    rng.gen_range(0..=w)
+/// Computes the insertion path (see `Urn::insert`) of the `pos`-th leaf
+/// (from the left) of a tree with `n` leaves.      
+/// Since the left subtree of a node with `n` leaves has `(n + 1) / 2`
+/// leaves, this only depends on `n` and `pos`, not on the tree itself.
+pub(crate) fn path_of_position(n: u32, pos: u32) -> u32 {
+    let (mut n, mut pos, mut path, mut depth) = (n, pos, 0, 0);
+    while n > 1 {
+        let nl = n.div_ceil(2);
+        if pos < nl {
+            n = nl;
+        } else {
+            path |= 1 << depth;
+            (n, pos) = (n - nl, pos - nl);
+        }
+        depth += 1;
+    }
+    path
+}
+
+/// Rebuilds a path through a tree bottom-up, starting from the subtree
+/// `tree` at the bottom of the path. `siblings` contains the siblings of
+/// the nodes along the path (from the root downwards), each paired with
+/// `true` if the path went right at that node (so the sibling is
+/// the left child), and `false` otherwise.
+fn rebuild_path<T: Clone>(
+    tree: Tree<T>,
+    siblings: Vec<(Box<Tree<T>>, bool)>,
+) -> Tree<T> {
+    siblings
+        .into_iter()
+        .rev()
+        .fold(tree, |tree, (sibling, went_right)| {
+            if went_right {
+                Tree::node(sibling, Box::new(tree))
+            } else {
+                Tree::node(Box::new(tree), sibling)
+            }
+        })
+}
+
+/// Produces a value uniformly at random from the range `[0, w)`,
+/// using the supplied random number generator `rng`
+/// (or 0 if `w` is 0, in which case no index is valid)
+pub(crate) fn sample_weight_with<R: RngCore + ?Sized>(
+    rng: &mut R,
+    w: Index,
+) -> Index {
+    draw_index_with(rng, w, IndexDraw::Uniform)
+}
+
+/// Produces a value uniformly at random from the range `[0, w)`
+/// using the strategy `draw` and the random number generator `rng`
+/// (or 0 if `w` is 0, in which case no index is valid)
+pub(crate) fn draw_index_with<R: RngCore + ?Sized>(
+    rng: &mut R,
+    w: Index,
+    draw: IndexDraw,
+) -> Index {
+    if w == 0 {
+        return 0;
+    }
+    match draw {
+        IndexDraw::Uniform => below_u64(rng, w),
+        // Both of the following strategies work on `u128`s, so that they
+        // don't depend on the width of `Weight`
+        IndexDraw::Lemire => {
+            let w = u128::from(w);
+            // `2^64 mod w`: products whose low half is below this threshold
+            // would make the smallest high halves slightly more likely
+            let threshold = ((1 << 64) - w) % w;
+            loop {
+                let m = u128::from(rng.next_u64()) * w;
+                if m & u128::from(u64::MAX) >= threshold {
+                    return (m >> 64) as Index;
+                }
+            }
+        }
+        IndexDraw::Bitmask => {
+            let w = u128::from(w);
+            // The no. of bits needed to represent `w - 1` (at least 1)
+            let bits = (128 - (w - 1).leading_zeros()).max(1);
+            let mask = (1 << bits) - 1;
+            loop {
+                let i = u128::from(rng.next_u64()) & mask;
+                if i < w {
+                    return i as Index;
+                }
+            }
+        }
+    }
 }
 
+/// Default value of the `rebuild_factor` used by `Urn::insert_many`
+pub const DEFAULT_REBUILD_FACTOR: u32 = 2;
+
 /* -------------------------------------------------------------------------- */
 /*                             Methods for Urn<T>                             */
 /* -------------------------------------------------------------------------- */
@@ -47,6 +138,7 @@ pub fn singleton<T: Clone>(w: Weight, a: T) -> Urn<T> {
     Urn {
         size: 1,
         tree: Leaf(w, a),
+        draw: IndexDraw::default(),
     }
 }
 
@@ -67,7 +159,13 @@ pub fn from_list_naive<T: Clone>(elems: Vec<(Weight, T)>) -> Option<Urn<T>> {
 
 /// An optimized version of `from_list`, which builds an almost perfect tree
 /// in linear time (see `almost_perfect.rs`)    
+/// (`Urn::try_from` does the same, but also checks that the total weight
+/// doesn't overflow, and reports errors with `ConversionError`.)
 /// Time complexity: `O(n)`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "trace", skip_all, fields(n = elems.len()))
+)]
 pub fn from_list<T: Clone>(elems: Vec<(Weight, T)>) -> Option<Urn<T>> {
     if elems.is_empty() {
         None
@@ -75,10 +173,54 @@ pub fn from_list<T: Clone>(elems: Vec<(Weight, T)>) -> Option<Urn<T>> {
         Some(Urn {
             size: elems.len() as u32,
             tree: almost_perfect(elems),
+            draw: IndexDraw::default(),
+        })
+    }
+}
+
+/// Builds an almost perfect urn directly from an iterator of
+/// (weight, element) pairs whose length is known upfront,
+/// without collecting the iterator into an intermediate `Vec`.     
+/// Returns `None` if the iterator is empty.      
+/// Panics if the iterator yields fewer elements than it reported.
+/// Time complexity: `O(n)`.
+pub fn from_iter_exact<T, I>(elems: I) -> Option<Urn<T>>
+where
+    T: Clone,
+    I: ExactSizeIterator<Item = (Weight, T)>,
+{
+    let size = elems.len();
+    if size == 0 {
+        None
+    } else {
+        Some(Urn {
+            size: size as u32,
+            tree: almost_perfect_iter(elems),
+            draw: IndexDraw::default(),
         })
     }
 }
 
+/// Builds an urn from a sequence of chunks of (weight, element) pairs,
+/// e.g. when bulk-loading elements that arrive in batches.
+/// Each chunk is built into an almost perfect block (see `from_list`),
+/// and the blocks are merged one at a time (see `Urn::merge`).
+/// Returns `None` if every chunk is empty.     
+/// Time complexity: `O(n log n)` in the worst case, and `O(n)` when the
+/// chunks are large enough that every merge rebuilds the urn.
+pub fn from_chunks<T, I>(chunks: I) -> Option<Urn<T>>
+where
+    T: Clone,
+    I: IntoIterator<Item = Vec<(Weight, T)>>,
+{
+    chunks
+        .into_iter()
+        .fold(None, |acc, chunk| match (acc, from_list(chunk)) {
+            (Some(urn), Some(block)) => Some(urn.merge(block)),
+            (acc, block) => acc.or(block),
+        })
+}
+
 /* -------------------------------------------------------------------------- */
 /*                Deterministic (index-based) methods for Urns                */
 /* -------------------------------------------------------------------------- */
@@ -90,152 +232,540 @@ impl<T: Clone> Urn<T> {
     }
 
     /// Retrieves the `weight` of the tree underlying the urn
-    pub fn weight(&self) -> Weight {
+    pub fn weight(&self) -> Index {
         self.tree.weight()
     }
 
+    /// The no. of elements in the urn, as a `usize`
+    /// (like `size`, for consistency with the standard collections)
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Whether the urn has no elements (always `false`, since urns contain
+    /// at least one element; for consistency with `len`)
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The total weight of all the elements in the urn
+    /// (an alias for `weight`)
+    pub fn total_weight(&self) -> Index {
+        self.weight()
+    }
+
+    /// Iterates over the (weight, element) pairs in the urn, in leaf order
+    pub fn iter(&self) -> Leaves<'_, T> {
+        self.tree.leaves()
+    }
+
+    /// Collects the (weight, element) pairs in the urn into a vector,
+    /// in leaf order. This is the inverse of `from_list`, i.e.
+    /// `from_list(u.to_vec())` represents the same distribution as `u`.     
+    /// Time complexity: `O(n)`.
+    pub fn to_vec(&self) -> Vec<(Weight, T)> {
+        self.iter().map(|(w, a)| (w, a.clone())).collect()
+    }
+
+    /// Consumes the urn, moving its (weight, element) pairs into a vector
+    /// (in leaf order, like `to_vec`).     
+    /// Time complexity: `O(n)`.
+    pub fn into_vec(self) -> Vec<(Weight, T)> {
+        let mut elems = Vec::with_capacity(self.size as usize);
+        self.tree.into_leaves(&mut elems);
+        elems
+    }
+
     /// Samples the value at index `i` from an urn
+    #[cfg_attr(not(feature = "std-rng"), allow(dead_code))]
     fn sample_index(&self, i: Index) -> T {
         self.tree.sample_index(i)
     }
 
-    /// `u.update(f, i)` samples an element from the urn `u`, then replaces the
-    /// chosen element `a` and its weight `w` by a new element `a_new`
+    /// Samples (a reference to) the value at index `i` from an urn,
+    /// without cloning it
+    pub fn sample_index_ref(&self, i: Index) -> &T {
+        self.tree.sample_index_ref(i)
+    }
+
+    /// The bucket containing index `i`, i.e. the range `[lb, ub)` of
+    /// cumulative weights occupied by the element at index `i` (in the
+    /// order of `iter`), as `(lb, ub, &a)`. Every index in `[lb, ub)` refers
+    /// to `a`, and `ub - lb` is its weight.
+    /// (`uninsert` returns the lower bound of the removed element's bucket.)
+    /// Time complexity: `O(log n)`.
+    pub fn bucket_of(&self, i: Index) -> (Index, Index, &T) {
+        let (mut tree, mut lb) = (&self.tree, 0);
+        loop {
+            match tree {
+                Leaf(w, a) => return (lb, lb.wrapping_add(widen(*w)), a),
+                Node(_, _, _, l, r) => {
+                    let wl = l.weight();
+                    if i.wrapping_sub(lb) < wl {
+                        tree = l;
+                    } else {
+                        (tree, lb) = (r, lb.wrapping_add(wl));
+                    }
+                }
+            }
+        }
+    }
+
+    /// `u.update_index(f, i)` replaces the element `a` at index `i` of the
+    /// urn `u` and its weight `w` by a new element `a_new`
     /// with weight `w_new`, where `(w_new, a_new) = f(w, a)`.    
     /// This function returns a triple `((w, a), (w_new, a_new), u_new)`,
     /// where `u_new` is the same urn as `u`,
     /// but with `(w, a)` replaced by `(w_new, a_new)`.
-    fn update_index<F>(
-        &self,
+    pub fn update_index<F>(
+        self,
         f: F,
         i: Index,
-    ) -> ((Weight, &T), (Weight, &T), Self)
+    ) -> ((Weight, T), (Weight, T), Self)
     where
-        F: FnOnce(Weight, &T) -> (Weight, &T),
+        F: FnOnce(Weight, T) -> (Weight, T),
     {
         let (old, new, new_tree) = self.tree.update_index(f, i);
-        (
-            old,
-            new,
-            Urn {
-                tree: new_tree,
-                ..*self
-            },
-        )
+        let urn = Urn {
+            tree: new_tree,
+            ..self
+        };
+        urn.debug_check_invariants();
+        (old, new, urn)
     }
 
     /// Samples from the urn, and returns the sampled element and its weight,
     /// along with a new urn with the sampled elements removed and a new element
     /// `a` with weight `w` added.
-    fn replace_index(
+    pub(crate) fn replace_index(
         &self,
         w: Weight,
         a: &T,
         i: Index,
     ) -> ((Weight, &T), Self) {
         let (old, new_tree) = self.tree.replace_index(w, a, i);
-        (
-            old,
-            Urn {
-                tree: new_tree,
-                ..*self
-            },
-        )
+        let urn = Urn {
+            tree: new_tree,
+            ..*self
+        };
+        urn.debug_check_invariants();
+        (old, urn)
     }
 
-    /// Inserts a new element `a` with weight `w` into the `Urn`.
+    /// Inserts a new element `a` with weight `w` into the `Urn`.     
+    /// The new leaf is placed at the end of the *insertion path* `size`,
+    /// read from its least significant bit: 0 means left, 1 means right.
+    /// (Since the path increments with every insertion, consecutive
+    /// insertions alternate between subtrees, which keeps the tree
+    /// almost balanced. See section 3.4-3.5 of the paper for details.)     
+    /// The path is walked iteratively: the siblings of the nodes along
+    /// the path are collected on the way down, and the path is then
+    /// rebuilt bottom-up with updated weights.     
     /// Time complexity: `O(log n)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(size = self.size, weight = self.weight(), w = w_outer)
+        )
+    )]
     pub fn insert(self, w_outer: Weight, a_outer: T) -> Self {
-        /// Helper function which updates the weights on all the
-        /// nodes encountered on a `path` through the `tree`.              
-        /// (The `path` is the binary representation of an integer,
-        /// where 0 is Left and 1 is right. We toggle the direction every time
-        /// we insert a new node to ensure that the tree is almost balanced.
-        /// See section 3.4-3.5 of the paper for details.)              
-        /// Note: since recursive closures aren't really possible
-        /// in Rust, and since nested functions can't access outer variables,
-        /// we need to supply the `w_outer` and `a_outer` arguments explicitly.
-        fn go<T: Clone>(
-            w_outer: Weight,
-            a_outer: T,
-            path: u32,
-            tree: Tree<T>,
-        ) -> Tree<T> {
+        let (mut tree, mut path) = (self.tree, self.size);
+        let mut siblings = Vec::new();
+        let old_leaf = loop {
             match tree {
-                Leaf(w, a) => node(
-                    w.wrapping_add(w_outer),
-                    leaf(w, a),
-                    leaf(w_outer, a_outer),
-                ),
-                Node(w, l, r) => {
-                    let new_path = path >> 1;
+                Node(_, _, _, l, r) => {
                     if test_bit(path, 0) {
-                        node(
-                            w.wrapping_add(w_outer),
-                            *l,
-                            go(w_outer, a_outer, new_path, *r),
-                        )
+                        siblings.push((l, true));
+                        tree = *r;
                     } else {
-                        node(
-                            w.wrapping_add(w_outer),
-                            go(w_outer, a_outer, new_path, *l),
-                            *r,
-                        )
+                        siblings.push((r, false));
+                        tree = *l;
                     }
+                    path >>= 1;
                 }
+                leaf => break leaf,
             }
-        }
-
-        Urn {
+        };
+        let tree =
+            rebuild_path(node(old_leaf, leaf(w_outer, a_outer)), siblings);
+        let urn = Urn {
             size: self.size + 1,
-            tree: go(w_outer, a_outer, self.size, self.tree),
+            tree,
+            draw: self.draw,
+        };
+        urn.debug_check_invariants();
+        urn
+    }
+
+    /// Inserts all the (weight, element) pairs in `elems` into the urn,
+    /// using the default `rebuild_factor` (`DEFAULT_REBUILD_FACTOR`),
+    /// see `insert_many_with`.
+    pub fn insert_many<I>(self, elems: I) -> Self
+    where
+        I: IntoIterator<Item = (Weight, T)>,
+    {
+        self.insert_many_with(elems, DEFAULT_REBUILD_FACTOR)
+    }
+
+    /// Inserts all the (weight, element) pairs in `elems` into the urn.    
+    /// Inserting `k` elements one at a time into an urn of size `n` takes
+    /// `O(k log(n + k))` time, whereas rebuilding the whole urn with
+    /// `from_list` takes `O(n + k)` time. We rebuild the urn whenever
+    /// `k * ceil(log2(n + k)) >= rebuild_factor * (n + k)`
+    /// (so larger values of `rebuild_factor` make rebuilds rarer),
+    /// and `insert` the elements one at a time otherwise.    
+    /// Note: the resultant urn represents the same distribution either way,
+    /// but the elements may end up in different leaves.
+    pub fn insert_many_with<I>(self, elems: I, rebuild_factor: u32) -> Self
+    where
+        I: IntoIterator<Item = (Weight, T)>,
+    {
+        let batch: Vec<(Weight, T)> = elems.into_iter().collect();
+        let k = batch.len() as u64;
+        let total = u64::from(self.size) + k;
+        let log = u64::from(total.next_power_of_two().ilog2());
+        if k * log >= u64::from(rebuild_factor) * total {
+            let mut all = self.into_vec();
+            all.extend(batch);
+            from_list(all).expect("the urn is non-empty")
+        } else {
+            batch.into_iter().fold(self, |urn, (w, a)| urn.insert(w, a))
         }
     }
 
+    /// Merges two urns into one containing the elements of both.
+    /// The elements of the smaller urn are added to the larger one using
+    /// `insert_many` (which rebuilds the urn when that is cheaper than
+    /// inserting them one at a time), so the larger urn's index-drawing
+    /// strategy is kept.     
+    /// Time complexity: `O(min(m, n) log(m + n))`, or `O(m + n)` if the
+    /// urn is rebuilt.
+    pub fn merge(self, other: Self) -> Self {
+        let (larger, smaller) = if self.size >= other.size {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        larger.insert_many(smaller.into_vec())
+    }
+
     /// `uninsert`s (deletes) the most-recently-inserted weighted value `(w, a)`
     /// from the urn, returning `(w, a)`, the lower bound `lb` for the bucket
     /// that previously contained `a`, and an optional new urn
     /// (since `uninsert`-ing from an `Urn` of size 1 produces `None`).    
-    pub fn uninsert(self) -> ((Weight, T), Weight, Option<Self>) {
-        fn go<T: Clone>(
-            path: u32,
-            tree: Tree<T>,
-        ) -> ((Weight, T), Weight, Option<Tree<T>>) {
+    pub fn uninsert(self) -> ((Weight, T), Index, Option<Self>) {
+        // As in `insert`, we walk down the path iteratively, collecting
+        // the siblings of the nodes along the path
+        let (mut tree, mut path) = (self.tree, self.size - 1);
+        let mut siblings = Vec::new();
+        let mut lb: Index = 0;
+        let (w, a) = loop {
             match tree {
-                Leaf(w, a) => ((w, a), 0, None),
-                Node(w, l, r) => {
-                    let new_path = path >> 1;
+                Node(_, _, _, l, r) => {
                     if test_bit(path, 0) {
-                        let ((w_new, a_new), lb, r_opt) = go(new_path, *r);
-                        let new_tree = r_opt.map_or(*l.clone(), |r_new| {
-                            Node(w.wrapping_sub(w_new), l, Box::new(r_new))
-                        });
-                        ((w_new, a_new), lb, Some(new_tree))
+                        lb = lb.wrapping_add(l.weight());
+                        siblings.push((l, true));
+                        tree = *r;
                     } else {
-                        let ((w_new, a_new), lb, l_opt) = go(new_path, *l);
-                        let new_tree = l_opt.map_or(*r.clone(), |l_new| {
-                            Node(w.wrapping_sub(w_new), Box::new(l_new), r)
-                        });
-                        ((w_new, a_new), lb, Some(new_tree))
+                        siblings.push((r, false));
+                        tree = *l;
                     }
+                    path >>= 1;
                 }
+                Leaf(w, a) => break (w, a),
+            }
+        };
+        // The sibling of the removed leaf takes the place of their parent
+        let urn_opt = siblings.pop().map(|(sibling, _)| Urn {
+            size: self.size - 1,
+            tree: rebuild_path(*sibling, siblings),
+            draw: self.draw,
+        });
+        if let Some(urn) = &urn_opt {
+            urn.debug_check_invariants();
+        }
+        ((w, a), lb, urn_opt)
+    }
+
+    /// Removes the element at index `i` in the urn, returning the element,
+    /// its weight, and an optional new urn.       
+    /// The most recently inserted element is spliced into the bucket vacated
+    /// by the removed element. Both `uninsert` and `replace_index_owned`
+    /// move (rather than borrow) the tree, so no element or subtree is cloned.
+    /// Urns of size 1 and 2 (which are common, e.g. when choosing between a
+    /// couple of alternatives) are handled directly, without any descent.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(size = self.size, weight = self.weight(), i)
+        )
+    )]
+    pub fn remove_index(self, i: Index) -> ((Weight, T), Option<Self>) {
+        match self {
+            Urn {
+                tree: Leaf(w, a), ..
+            } => return ((w, a), None),
+            Urn {
+                size: 2,
+                tree: Node(_, _, _, l, r),
+                draw,
+            } => {
+                let (Leaf(wl, al), Leaf(wr, ar)) = (*l, *r) else {
+                    unreachable!("an urn of size 2 has two leaves")
+                };
+                // Same outcome as the general case below: `(wr, ar)` is the
+                // most recently inserted element, with bucket `[wl, wl + wr)`
+                let (lb, ub) = (widen(wl), widen(wl).wrapping_add(widen(wr)));
+                let (removed, kept) = if i >= lb && i < ub {
+                    ((wr, ar), Leaf(wl, al))
+                } else {
+                    ((wl, al), Leaf(wr, ar))
+                };
+                return (
+                    removed,
+                    Some(Urn {
+                        size: 1,
+                        tree: kept,
+                        draw,
+                    }),
+                );
             }
+            _ => (),
+        }
+
+        let ((w, a), lb, urn_opt) = self.uninsert();
+        match urn_opt {
+            None => ((w, a), None),
+            Some(urn) if i >= lb && i < lb.wrapping_add(widen(w)) => {
+                ((w, a), Some(urn))
+            }
+            Some(Urn { size, tree, draw }) => {
+                let j = if i < lb { i } else { i.wrapping_sub(widen(w)) };
+                let (old, tree) = tree.replace_index_owned(w, a, j);
+                let urn = Urn { size, tree, draw };
+                urn.debug_check_invariants();
+                (old, Some(urn))
+            }
+        }
+    }
+
+    /// Adds `delta` (which may be negative) to the weight of the element
+    /// at index `i`, returning the new urn.        
+    /// If the new weight is 0 and `on_zero` is `ZeroWeightPolicy::Remove`,
+    /// the element is removed from the urn instead (so the result is `None`
+    /// if the urn only contained that element).       
+    /// Fails with `WeightError::Underflow` if the new weight would be
+    /// negative, and with `WeightError::Overflow` if either the new weight
+    /// would exceed `Weight::MAX` or the urn's total weight would exceed
+    /// `Index::MAX`, giving back the urn unchanged along with the error.
+    /// Time complexity: `O(log n)`.
+    pub fn adjust_index(
+        self,
+        delta: i64,
+        i: Index,
+        on_zero: ZeroWeightPolicy,
+    ) -> Result<Option<Self>, (WeightError, Self)> {
+        let w_new = match self.adjusted_weight(delta, i) {
+            Ok(w_new) => w_new,
+            Err(err) => return Err((err, self)),
+        };
+        if w_new == 0 && on_zero == ZeroWeightPolicy::Remove {
+            let (_, urn_opt) = self.remove_index(i);
+            Ok(urn_opt)
+        } else {
+            let (_, tree) = self.tree.reweight_index(w_new, i);
+            let urn = Urn { tree, ..self };
+            urn.debug_check_invariants();
+            Ok(Some(urn))
+        }
+    }
+
+    /// The weight of the element at index `i` once `delta` is added to it
+    /// (see `adjust_index`)
+    fn adjusted_weight(
+        &self,
+        delta: i64,
+        i: Index,
+    ) -> Result<Weight, WeightError> {
+        let (w_old, _) = self.tree.lookup_index(i);
+        // `i128` can represent every `Weight` plus every `i64`
+        let w_new = i128::from(w_old) + i128::from(delta);
+        if w_new < 0 {
+            return Err(WeightError::Underflow);
+        }
+        let w_new =
+            Weight::try_from(w_new).map_err(|_| WeightError::Overflow)?;
+        if w_new > w_old {
+            self.weight()
+                .checked_add(widen(w_new - w_old))
+                .ok_or(WeightError::Overflow)?;
         }
+        Ok(w_new)
+    }
 
-        let ((w, a), lb, tree_opt) = go(self.size - 1, self.tree);
-        (
-            (w, a),
-            lb,
-            tree_opt.map(|tree| Self {
-                size: self.size - 1,
-                tree,
-            }),
+    /// Removes the element at (leaf) position `pos` in the urn, returning
+    /// the element, its weight, and an optional new urn.       
+    /// Unlike `remove_index`, this can remove elements with weight 0.
+    /// As with `remove_index`, the most recently inserted element is spliced
+    /// into the vacated leaf, and no element or subtree is cloned.     
+    /// Panics if `pos >= self.size()`.     
+    /// Time complexity: `O(log n)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(size = self.size, weight = self.weight(), pos)
         )
+    )]
+    pub fn remove_position(self, pos: u32) -> ((Weight, T), Option<Self>) {
+        assert!(
+            pos < self.size(),
+            "position {} out of bounds for urn of size {}",
+            pos,
+            self.size()
+        );
+        let path = path_of_position(self.size, pos);
+        self.remove_nth(path)
     }
 
-    /// Removes the element at index `i` in the urn, returning the element,
-    /// its weight, and an optional new urn
-    fn remove_index(self, i: Index) -> ((Weight, T), Option<Self>) {
+    /// Retrieves the weight and (a reference to) an element with the minimum
+    /// weight (the leftmost one if there are several), using the minimum
+    /// weights cached at the nodes.
+    /// Time complexity: `O(log n)`.
+    pub fn min_weight_element(&self) -> (Weight, &T) {
+        let (_, w, a) = self.tree.locate_min();
+        (w, a)
+    }
+
+    /// Retrieves the weight and (a reference to) an element with the maximum
+    /// weight (the leftmost one if there are several), using the maximum
+    /// weights cached at the nodes.
+    /// Time complexity: `O(log n)`.
+    pub fn max_weight_element(&self) -> (Weight, &T) {
+        let (_, w, a) = self.tree.locate_max();
+        (w, a)
+    }
+
+    /// Removes the element returned by `min_weight_element` (e.g. to evict
+    /// the lightest element when the urn grows beyond some capacity),
+    /// returning it along with its weight, and the resultant urn in an
+    /// `Option` (which is `None` if the urn is empty).
+    /// As with `remove_position`, elements with weight 0 can be removed.
+    /// Time complexity: `O(log n)`.
+    pub fn remove_lightest(self) -> ((Weight, T), Option<Self>) {
+        let (path, _, _) = self.tree.locate_min();
+        self.remove_nth(path)
+    }
+
+    /// Retrieves the weight and (a reference to) the `i`-th inserted element
+    /// (i.e. the element whose insertion path is `i`, see `insert`),
+    /// or `None` if `i >= self.size()`.
+    /// Ordinals behave like the indexes of a vector with `swap_remove`:
+    /// removing the `k`-th element (by any of the removal methods) moves the
+    /// most recently inserted element into the vacated slot, so that element
+    /// becomes the `k`-th one, while all other elements keep their ordinals.
+    /// (`from_list` doesn't insert the elements one at a time, so the
+    /// ordinals of its elements don't follow the order of the list.)
+    /// Time complexity: `O(log n)`.
+    pub fn get_nth(&self, i: u32) -> Option<(Weight, &T)> {
+        (i < self.size).then(|| self.tree.lookup_path(i))
+    }
+
+    /// Retrieves the weight of the `i`-th inserted element (see `get_nth`),
+    /// or `None` if `i >= self.size()`.
+    /// Time complexity: `O(log n)`.
+    pub fn nth_weight(&self, i: u32) -> Option<Weight> {
+        self.get_nth(i).map(|(w, _)| w)
+    }
+
+    /// Sets the weight of the `i`-th inserted element (see `get_nth`) to `w`
+    /// in place, returning its old weight (or `None`, leaving the urn
+    /// untouched, if `i >= self.size()`).
+    /// Time complexity: `O(log n)`.
+    pub fn set_nth_weight(&mut self, i: u32, w: Weight) -> Option<Weight> {
+        if i >= self.size {
+            return None;
+        }
+        let w_old = self.tree.reweight_path(i, w);
+        self.debug_check_invariants();
+        Some(w_old)
+    }
+
+    /// Replaces the weight `w` of every element `a` with `f(w, a)` in place
+    /// (visiting the elements in leaf order), e.g. to recompute the weights
+    /// of all elements after some external parameter has changed.      
+    /// Time complexity: `O(n)`.
+    pub fn reweight_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Weight, &T) -> Weight,
+    {
+        self.tree.map_weights_with(&mut f);
+        self.debug_check_invariants();
+    }
+
+    /// Replaces every element `a` and its weight `w` with `f(w, a)` (visiting
+    /// the elements in leaf order), e.g. to reweight and transform all
+    /// elements at once. The internal weights are rebuilt in the same pass,
+    /// and the tree keeps its shape, so no element is moved or cloned.
+    /// The new elements may have a different type.      
+    /// Time complexity: `O(n)`.
+    pub fn update_all<U, F>(self, mut f: F) -> Urn<U>
+    where
+        U: Clone,
+        F: FnMut(Weight, T) -> (Weight, U),
+    {
+        let urn = Urn {
+            size: self.size,
+            tree: self.tree.update_all_with(&mut f),
+            draw: self.draw,
+        };
+        urn.debug_check_invariants();
+        urn
+    }
+
+    /// Removes the `k`-th inserted element of the urn (i.e. the element whose
+    /// insertion path is `k`, see `insert`), returning the element,
+    /// its weight, and an optional new urn.       
+    /// The most recently inserted element (the `size - 1`-th one) is moved
+    /// into the vacated slot, i.e. it becomes the `k`-th element,
+    /// while all other elements keep their insertion paths.      
+    /// Panics if `k >= self.size()`.     
+    /// Time complexity: `O(log n)`.
+    pub(crate) fn remove_nth(self, k: u32) -> ((Weight, T), Option<Self>) {
+        assert!(
+            k < self.size(),
+            "ordinal {} out of bounds for urn of size {}",
+            k,
+            self.size()
+        );
+        let ((w, a), _, urn_opt) = self.uninsert();
+        match urn_opt {
+            None => ((w, a), None),
+            // The removed element was the most recently inserted one
+            Some(urn) if k == urn.size => ((w, a), Some(urn)),
+            Some(Urn { size, tree, draw }) => {
+                let (old, tree) = tree.replace_path_owned(w, a, k);
+                let urn = Urn { size, tree, draw };
+                urn.debug_check_invariants();
+                (old, Some(urn))
+            }
+        }
+    }
+
+    /// Naive implementation of `remove_index`, which `uninsert`s the last
+    /// element and then uses `replace_index` to put it in the bucket of the
+    /// removed element (cloning the removed element along the way).
+    /// Only used to cross-check `remove_index` in the QuickCheck properties.
+    #[cfg(test)]
+    pub(crate) fn remove_index_naive(
+        self,
+        i: Index,
+    ) -> ((Weight, T), Option<Self>) {
         let ((w, a), lb, urn_opt) = self.uninsert();
         match urn_opt {
             None => ((w, a), None),
@@ -244,11 +774,11 @@ impl<T: Clone> Urn<T> {
                     let ((w_new, a_new), final_urn) =
                         new_urn.replace_index(w, &a, i);
                     ((w_new, a_new.clone()), Some(final_urn))
-                } else if i < lb + w {
+                } else if i < lb.wrapping_add(widen(w)) {
                     ((w, a), Some(new_urn))
                 } else {
                     let ((w_new, a_new), final_urn) =
-                        new_urn.replace_index(w, &a, i - w);
+                        new_urn.replace_index(w, &a, i.wrapping_sub(widen(w)));
                     ((w_new, a_new.clone()), Some(final_urn))
                 }
             }
@@ -261,24 +791,181 @@ impl<T: Clone> Urn<T> {
 /* -------------------------------------------------------------------------- */
 
 impl<T: Clone> Urn<T> {
+    /// Returns the urn with its index-drawing strategy set to `draw`
+    /// (see `IndexDraw`). Urns derived from this urn (e.g. by `insert` or
+    /// `remove`) use the same strategy.
+    pub fn with_index_draw(self, draw: IndexDraw) -> Self {
+        Urn { draw, ..self }
+    }
+
+    /// Draws an index uniformly at random from the range `[0, self.weight())`
+    /// using the urn's index-drawing strategy and `rng`
+    /// (every element is sampled with probability proportional to its weight
+    /// by looking up the bucket containing the index).
+    pub fn draw_index<R: RngCore + ?Sized>(&self, rng: &mut R) -> Index {
+        record_sample_drawn();
+        draw_index_with(rng, self.weight(), self.draw)
+    }
+
     /// Randomly sample an element from the distribution represented by the urn.
     /// Time complexity: `O(log n)`.
+    /// (Urns with one or two elements are sampled without walking the tree,
+    /// see `sample_small`.)
+    #[cfg(feature = "std-rng")]
     pub fn sample(&self) -> T {
-        let i = sample_weight(self.weight());
+        let mut rng = thread_rng();
+        if let Some(a) = self.sample_small(&mut rng) {
+            return a.clone();
+        }
+        let i = self.draw_index(&mut rng);
         self.sample_index(i)
     }
 
+    /// Samples (a reference to) an element of an urn with one or two
+    /// elements directly: a singleton's element is returned without drawing
+    /// from `rng`, and for two leaves, the drawn index is compared against
+    /// the left leaf's weight once. Returns `None` for larger urns.
+    /// (The outcome is the same as looking up the drawn index in the tree.)
+    fn sample_small<R: RngCore + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        match &self.tree {
+            Leaf(_, a) => {
+                record_sample_drawn();
+                Some(a)
+            }
+            Node(_, _, _, l, r) => match (&**l, &**r) {
+                (Leaf(wl, al), Leaf(_, ar)) => {
+                    let i = self.draw_index(rng);
+                    Some(if i < widen(*wl) { al } else { ar })
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Randomly samples (a reference to) an element from the distribution
+    /// represented by the urn using `rng`, without cloning the element.
+    /// Time complexity: `O(log n)`.
+    /// (Urns with one or two elements are sampled without walking the tree,
+    /// see `sample_small`.)
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(size = self.size, weight = self.weight())
+        )
+    )]
+    pub fn sample_ref<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        if let Some(a) = self.sample_small(rng) {
+            return a;
+        }
+        let i = self.draw_index(rng);
+        self.sample_index_ref(i)
+    }
+
+    /// Randomly samples an element using `rng` (like `sample_ref`), also
+    /// returning the drawn index `i` and the element's weight, as
+    /// `(i, w, &a)`. Since `i` lies in the sampled element's bucket, passing
+    /// it to `remove_index`, `update_index` or `adjust_index` acts on exactly
+    /// the sampled element, without sampling again.
+    /// Time complexity: `O(log n)`.
+    pub fn sample_with_position<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> (Index, Weight, &T) {
+        let i = self.draw_index(rng);
+        let (w, a) = self.tree.lookup_index(i);
+        (i, w, a)
+    }
+
+    /// Samples (a reference to) an element uniformly at random using `rng`,
+    /// ignoring the weights (so elements with weight 0 may be sampled too).
+    /// The no. of leaves of every subtree is determined by the urn's size
+    /// (see `Tree::lookup_position`), so no counts need to be stored.
+    /// Time complexity: `O(log n)`.
+    pub fn sample_uniform<R: RngCore + ?Sized>(&self, rng: &mut R) -> &T {
+        let pos = below_u64(rng, u64::from(self.size())) as u32;
+        self.tree.lookup_position(self.size(), pos).1
+    }
+
+    /// Removes an element chosen uniformly at random using `rng`, ignoring
+    /// the weights (see `sample_uniform` and `remove_position`), returning
+    /// it along with its weight, and the resultant urn in an `Option`
+    /// (which is `None` if the urn is empty).
+    /// Time complexity: `O(log n)`.
+    pub fn remove_uniform<R: RngCore + ?Sized>(
+        self,
+        rng: &mut R,
+    ) -> ((Weight, T), Option<Self>) {
+        let pos = below_u64(rng, u64::from(self.size())) as u32;
+        self.remove_position(pos)
+    }
+
+    /// Samples (a reference to) an element using the 64-bit value `r` as the
+    /// only source of randomness, for callers with their own entropy stream
+    /// (e.g. a replay log or a deterministic simulation framework) rather
+    /// than an `RngCore`. If `r` is uniformly random, every element is
+    /// sampled with probability proportional to its weight, up to an error
+    /// of less than `2^-64` per index.
+    /// `r` is mapped onto the range `[0, self.weight())` by multiplying it by
+    /// the total weight and keeping the high 64 bits (Lemire's method without
+    /// the rejection step), so the values of `r` left over by the division
+    /// are spread evenly over the range, whereas `r % self.weight()` would
+    /// favor the first elements. (Removing the error entirely requires
+    /// rejecting some values of `r`, which `sample_ref` with
+    /// `IndexDraw::Lemire` does.)
+    /// Time complexity: `O(log n)`.
+    pub fn sample_from_entropy(&self, r: u64) -> &T {
+        let w = u128::from(self.weight());
+        self.sample_index_ref(((u128::from(r) * w) >> 64) as Index)
+    }
+
+    /// Randomly samples an element among those whose weight is at least
+    /// `min_w` (with probability proportional to its weight), using `rng`.
+    /// Returns `None` if no such element has a positive weight.     
+    /// Subtrees whose maximum leaf weight is less than `min_w` are pruned,
+    /// so the time complexity is `O(k log n)`, where `k` is the no. of
+    /// elements with weight at least `min_w`.
+    pub fn sample_at_least<R: RngCore + ?Sized>(
+        &self,
+        min_w: Weight,
+        rng: &mut R,
+    ) -> Option<T> {
+        // The total eligible weight may not fit in a `Weight`
+        let total: u128 = self
+            .tree
+            .leaves_at_least(min_w)
+            .map(|(w, _)| u128::from(w))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut i = below_u128(rng, total);
+        for (w, a) in self.tree.leaves_at_least(min_w) {
+            let w = u128::from(w);
+            if i < w {
+                return Some(a.clone());
+            }
+            i -= w;
+        }
+        unreachable!("sampled index {} is out of range", i)
+    }
+
     /// Randomly samples an element from the urn, then replaces the
     /// chosen element `a` and its weight `w` by a new element `a_new`
     /// with weight `w_new`, where `(w_new, a_new) = f(w, a)`.    
     /// This function returns a triple `((w, a), (w_new, a_new), new_urn)`,
     /// where `new_urn` has `(w, a)` replaced by `(w_new, a_new)`.    
+    /// `f` takes the sampled element by value, so it can compute a genuinely
+    /// new element (or modify the old one). The urn is consumed, and `a` and
+    /// `a_new` are cloned once each, so that they can be returned.
     /// Time complexity: `O(log n)`.
-    pub fn update<F>(&self, f: F) -> ((Weight, &T), (Weight, &T), Self)
+    #[cfg(feature = "std-rng")]
+    pub fn update<F>(self, f: F) -> ((Weight, T), (Weight, T), Self)
     where
-        F: FnOnce(Weight, &T) -> (Weight, &T),
+        F: FnOnce(Weight, T) -> (Weight, T),
     {
-        let i = sample_weight(self.weight());
+        let i = self.draw_index(&mut thread_rng());
         self.update_index(f, i)
     }
 
@@ -286,20 +973,168 @@ impl<T: Clone> Urn<T> {
     /// along with an urn where the sampled element has been replaced with
     /// the element `a` with weight `w`.    
     /// Time complexity: `O(log n)`.
+    #[cfg(feature = "std-rng")]
     pub fn replace(&self, w: Weight, a: &T) -> ((Weight, &T), Self) {
-        let i = sample_weight(self.weight());
+        let i = self.draw_index(&mut thread_rng());
         self.replace_index(w, a, i)
     }
 
+    /// Randomly samples an element from the urn and adds `delta` to its weight
+    /// (see `adjust_index`), returning (a copy of) the sampled element
+    /// along with the new urn.        
+    /// Time complexity: `O(log n)`.
+    #[cfg(feature = "std-rng")]
+    pub fn adjust(
+        self,
+        delta: i64,
+        on_zero: ZeroWeightPolicy,
+    ) -> Result<(T, Option<Self>), (WeightError, Self)> {
+        let i = self.draw_index(&mut thread_rng());
+        let a = self.sample_index(i);
+        let urn_opt = self.adjust_index(delta, i, on_zero)?;
+        Ok((a, urn_opt))
+    }
+
     /// Removes a random element from the urn, returning it along with its weight,
     /// and the resultant urn in an `Option` (which is `None` if the urn is empty).
     /// Time complexity: `O(log n)`.
+    #[cfg(feature = "std-rng")]
     pub fn remove(self) -> ((Weight, T), Option<Self>) {
-        let i = sample_weight(self.weight());
+        let i = self.draw_index(&mut thread_rng());
         self.remove_index(i)
     }
 }
 
+/* -------------------------------------------------------------------------- */
+/*                                  Iterators                                 */
+/* -------------------------------------------------------------------------- */
+
+/// Iterator which consumes an urn, yielding its elements (and their weights)
+/// in weighted-random order: each element is drawn with probability
+/// proportional to its weight among the elements that remain in the urn.    
+/// Created by `Urn::into_random_order`.
+pub struct RandomOrder<T: Clone, R: RngCore> {
+    urn: Option<Urn<T>>,
+    rng: R,
+}
+
+impl<T: Clone, R: RngCore> Iterator for RandomOrder<T, R> {
+    type Item = (Weight, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let urn = self.urn.take()?;
+        let i = urn.draw_index(&mut self.rng);
+        let (elem, urn_opt) = urn.remove_index(i);
+        self.urn = urn_opt;
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.urn.as_ref().map_or(0, |urn| urn.size() as usize);
+        (n, Some(n))
+    }
+}
+
+impl<T: Clone, R: RngCore> ExactSizeIterator for RandomOrder<T, R> {}
+
+impl<T: Clone> Urn<T> {
+    /// Returns an iterator which lazily yields each element `w` times
+    /// (where `w` is its weight), i.e. the exact discrete distribution
+    /// represented by the urn as a plain sequence of outcomes.
+    pub fn iter_buckets_expanded(&self) -> BucketsExpanded<'_, T> {
+        BucketsExpanded {
+            leaves: self.iter(),
+            current: None,
+        }
+    }
+
+    /// Consumes the urn, returning an iterator which repeatedly `remove`s
+    /// elements from it using `rng` (i.e. a weighted shuffle of the urn).    
+    /// Each call to `next` takes time `O(log n)`.
+    pub fn into_random_order<R: RngCore>(self, rng: R) -> RandomOrder<T, R> {
+        RandomOrder {
+            urn: Some(self),
+            rng,
+        }
+    }
+
+    /// Consumes the urn, returning an infinite "shuffle bag" iterator
+    /// which draws elements without replacement using `rng`, and refills the
+    /// urn (from a snapshot of this urn) whenever it runs out.    
+    /// Each call to `next` takes time `O(log n)`, except for the first call
+    /// of every cycle, which copies the snapshot in time `O(n)`.
+    pub fn into_shuffle_bag<R: RngCore>(self, rng: R) -> ShuffleBag<T, R> {
+        ShuffleBag {
+            snapshot: self,
+            urn: None,
+            rng,
+        }
+    }
+}
+
+/// Iterator which yields each element of an urn `w` times in a row (where `w`
+/// is the element's weight), in leaf order, without materializing the
+/// repeated elements. Element `a` is yielded at the `i`-th step iff
+/// `a` occupies the bucket containing index `i`.  
+/// Created by `Urn::iter_buckets_expanded`.
+#[derive(Debug, Clone)]
+pub struct BucketsExpanded<'a, T: Clone> {
+    leaves: Leaves<'a, T>,
+    /// The current element, along with how many more times to yield it
+    current: Option<(Weight, &'a T)>,
+}
+
+impl<'a, T: Clone> Iterator for BucketsExpanded<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current {
+                Some((remaining, a)) if remaining > 0 => {
+                    self.current = Some((remaining - 1, a));
+                    return Some(a);
+                }
+                _ => self.current = Some(self.leaves.next()?),
+            }
+        }
+    }
+}
+
+/// Infinite iterator implementing "shuffle bag" semantics: elements are
+/// drawn without replacement (in weighted-random order, as in `RandomOrder`)
+/// until the urn is exhausted, at which point the urn is refreshed from a
+/// snapshot and the next cycle begins. Hence every element is yielded exactly
+/// once per cycle (of `size` draws).    
+/// Created by `Urn::into_shuffle_bag`.
+pub struct ShuffleBag<T: Clone, R: RngCore> {
+    snapshot: Urn<T>,
+    urn: Option<Urn<T>>,
+    rng: R,
+}
+
+impl<T: Clone, R: RngCore> ShuffleBag<T, R> {
+    /// The no. of draws left before the urn is refreshed
+    pub fn remaining(&self) -> u32 {
+        self.urn.as_ref().map_or(0, |urn| urn.size())
+    }
+}
+
+impl<T: Clone, R: RngCore> Iterator for ShuffleBag<T, R> {
+    type Item = (Weight, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let urn = self.urn.take().unwrap_or_else(|| self.snapshot.clone());
+        let i = urn.draw_index(&mut self.rng);
+        let (elem, urn_opt) = urn.remove_index(i);
+        self.urn = urn_opt;
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
 /* -------------------------------------------------------------------------- */
 /*                               Unit Tests                                   */
 /* -------------------------------------------------------------------------- */
@@ -307,23 +1142,23 @@ impl<T: Clone> Urn<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::prelude::*;
+    use std::collections::HashMap;
 
     /// Example from figure 5 in the paper
     #[test]
     fn sample_example() {
         let tree = node(
-            21,
             node(
-                9,
-                node(5, leaf(4, 'a'), leaf(1, 'b')),
-                node(4, leaf(2, 'c'), leaf(2, 'd')),
+                node(leaf(4, 'a'), leaf(1, 'b')),
+                node(leaf(2, 'c'), leaf(2, 'd')),
             ),
             node(
-                12,
-                node(7, leaf(2, 'e'), leaf(5, 'f')),
-                node(5, leaf(3, 'g'), leaf(2, 'h')),
+                node(leaf(2, 'e'), leaf(5, 'f')),
+                node(leaf(3, 'g'), leaf(2, 'h')),
             ),
         );
+        assert_eq!(tree.weight(), 21);
         let expected = 'f';
         let actual = tree.sample_index(12);
         assert_eq!(expected, actual);
@@ -355,4 +1190,306 @@ mod tests {
         assert_eq!(naive_urn.size(), urn.size());
         assert_eq!(naive_urn.weight(), urn.weight());
     }
+
+    #[test]
+    fn adjust_index_example() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        let new_urn = urn
+            .clone()
+            .adjust_index(3, 2, ZeroWeightPolicy::Keep)
+            .unwrap()
+            .unwrap();
+        assert_eq!(new_urn.weight(), 12);
+        assert_eq!(new_urn.tree.lookup_index(2), (7, &'G'));
+
+        // Failed adjustments give back the urn unchanged
+        assert_eq!(
+            urn.clone().adjust_index(-3, 0, ZeroWeightPolicy::Keep),
+            Err((WeightError::Underflow, urn.clone()))
+        );
+        let full = from_list(vec![(Weight::MAX - 1, 'a'), (1, 'b')]).unwrap();
+        assert_eq!(
+            full.clone().adjust_index(2, 0, ZeroWeightPolicy::Keep),
+            Err((WeightError::Overflow, full.clone()))
+        );
+        // The total weight only overflows once it exceeds `Index::MAX`
+        let idx = widen(Weight::MAX) - 1;
+        let bigger = full.adjust_index(1, idx, ZeroWeightPolicy::Keep);
+        if cfg!(feature = "weight-u64") {
+            assert_eq!(bigger.unwrap_err().0, WeightError::Overflow);
+        } else {
+            let total = bigger.unwrap().unwrap().weight();
+            assert_eq!(u128::from(total), u128::from(Weight::MAX) + 1);
+        }
+
+        let removed = urn
+            .adjust_index(-2, 0, ZeroWeightPolicy::Remove)
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed.size(), 2);
+        assert_eq!(removed.weight(), 7);
+    }
+
+    #[test]
+    fn into_random_order_yields_every_element() {
+        let elems = vec![(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e')];
+        let urn = from_list(elems.clone()).unwrap();
+        let mut drawn: Vec<(Weight, char)> =
+            urn.into_random_order(thread_rng()).collect();
+        drawn.sort();
+        assert_eq!(drawn, elems);
+    }
+
+    #[test]
+    fn from_iter_exact_matches_from_list() {
+        let elems = vec![(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e')];
+        let urn = from_iter_exact(elems.clone().into_iter());
+        assert_eq!(urn, from_list(elems));
+        assert_eq!(from_iter_exact(std::iter::empty::<(Weight, char)>()), None);
+    }
+
+    #[test]
+    fn sample_at_least_only_samples_heavy_elements() {
+        let urn =
+            from_list(vec![(1, 'a'), (5, 'b'), (2, 'c'), (7, 'd')]).unwrap();
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let a = urn.sample_at_least(5, &mut rng).unwrap();
+            assert!(a == 'b' || a == 'd');
+        }
+        assert_eq!(urn.sample_at_least(8, &mut rng), None);
+    }
+
+    #[test]
+    fn sample_ref_does_not_clone() {
+        let urn =
+            from_list(vec![(1, String::from("a")), (2, "b".into())]).unwrap();
+        let a: &String = urn.sample_ref(&mut thread_rng());
+        assert!(urn.iter().any(|(_, b)| std::ptr::eq(a, b)));
+    }
+
+    #[test]
+    fn bucket_of_example() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (4, 'Y')]);
+        let urn = urn.unwrap();
+        let buckets: Vec<(Index, Index, char)> = (0..urn.weight())
+            .map(|i| urn.bucket_of(i))
+            .map(|(lb, ub, a)| (lb, ub, *a))
+            .collect();
+        let mut expected = vec![(0, 2, 'R'); 2];
+        expected.extend(vec![(2, 5, 'B'); 3]);
+        expected.extend(vec![(5, 9, 'Y'); 4]);
+        assert_eq!(buckets, expected);
+
+        // `uninsert` computes the same lower bound
+        let (lb, ub, _) = urn.bucket_of(8);
+        let ((w, a), lb_uninsert, _) = urn.uninsert();
+        assert_eq!((lb, ub, a), (lb_uninsert, lb_uninsert + w as Index, 'Y'));
+    }
+
+    #[test]
+    fn sample_with_position_finds_sampled_element() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B'), (1, 'Y')]);
+        let mut urn = urn.unwrap();
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut removed = vec![];
+        while urn.weight() > 0 {
+            let (i, w, a) = urn.sample_with_position(&mut rng);
+            assert_eq!(urn.sample_index_ref(i), a);
+            let (a, w) = (*a, w);
+            let ((w_removed, a_removed), rest) = urn.remove_index(i);
+            assert_eq!((w_removed, a_removed), (w, a));
+            removed.push(a);
+            urn = rest.unwrap();
+        }
+        removed.sort();
+        assert_eq!(removed, vec!['B', 'R', 'Y']);
+    }
+
+    #[test]
+    fn lightest_and_heaviest_elements() {
+        let elems = vec![(4, 'R'), (2, 'G'), (7, 'B'), (2, 'Y'), (5, 'P')];
+        let urn = from_list(elems).unwrap();
+        assert_eq!(urn.min_weight_element(), (2, &'G'));
+        assert_eq!(urn.max_weight_element(), (7, &'B'));
+        assert_eq!(urn.tree.min_weight(), 2);
+
+        // Evicting the lightest elements one by one yields them in order
+        // of increasing weight
+        let (mut urn_opt, mut evicted) = (Some(urn), vec![]);
+        while let Some(urn) = urn_opt {
+            let ((w, _), rest) = urn.remove_lightest();
+            if let Some(rest) = &rest {
+                assert_eq!(rest.validate(), Ok(()));
+            }
+            evicted.push(w);
+            urn_opt = rest;
+        }
+        assert_eq!(evicted, vec![2, 2, 4, 5, 7]);
+    }
+
+    #[test]
+    fn nth_elements_follow_insertion_order() {
+        let elems: Vec<(Weight, u32)> =
+            (0..7).map(|k| (k as Weight, k)).collect();
+        let mut urn = from_list_naive(elems).unwrap();
+        for k in 0..7 {
+            assert_eq!(urn.get_nth(k), Some((k as Weight, &k)));
+        }
+        assert_eq!(urn.get_nth(7), None);
+
+        assert_eq!(urn.set_nth_weight(3, 9), Some(3));
+        assert_eq!(urn.set_nth_weight(7, 9), None);
+        assert_eq!(urn.nth_weight(3), Some(9));
+        assert_eq!(urn.weight(), 21 - 3 + 9);
+
+        // Removing the 2nd element moves the 6th one into its slot
+        let (_, urn) = urn.remove_value(&2).unwrap();
+        let urn = urn.unwrap();
+        assert_eq!(urn.get_nth(2), Some((6, &6)));
+        assert_eq!(urn.get_nth(6), None);
+    }
+
+    #[test]
+    fn uniform_sampling_ignores_weights() {
+        let urn = from_list(vec![(9, 'R'), (0, 'G'), (1, 'B')]).unwrap();
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut counts: HashMap<char, u32> = HashMap::new();
+        for _ in 0..3000 {
+            *counts.entry(*urn.sample_uniform(&mut rng)).or_default() += 1;
+        }
+        assert!(counts.values().all(|&c| (850..1150).contains(&c)));
+
+        let (mut urn_opt, mut removed) = (Some(urn), vec![]);
+        while let Some(urn) = urn_opt {
+            let ((_, a), rest) = urn.remove_uniform(&mut rng);
+            removed.push(a);
+            urn_opt = rest;
+        }
+        removed.sort();
+        assert_eq!(removed, vec!['B', 'G', 'R']);
+    }
+
+    #[test]
+    fn sample_from_entropy_spreads_values_evenly() {
+        let urn = from_list(vec![(2, 'R'), (4, 'G'), (3, 'B')]).unwrap();
+        assert_eq!(urn.sample_from_entropy(0), &'R');
+        assert_eq!(urn.sample_from_entropy(u64::MAX), &'B');
+        // Evenly spaced values of `r` hit each bucket equally often
+        let step = u64::MAX / 900 + 1;
+        let mut counts: HashMap<char, u32> = HashMap::new();
+        for k in 0..900 {
+            *counts
+                .entry(*urn.sample_from_entropy(k * step))
+                .or_default() += 1;
+        }
+        assert_eq!((counts[&'R'], counts[&'G'], counts[&'B']), (200, 400, 300));
+    }
+
+    #[test]
+    fn shuffle_bag_yields_each_element_once_per_cycle() {
+        let elems = vec![(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd')];
+        let urn = from_list(elems.clone()).unwrap();
+        let mut bag = urn.into_shuffle_bag(thread_rng());
+        for _ in 0..3 {
+            let mut cycle: Vec<(Weight, char)> = bag.by_ref().take(4).collect();
+            cycle.sort();
+            assert_eq!(cycle, elems);
+            assert_eq!(bag.remaining(), 0);
+        }
+    }
+
+    #[test]
+    fn iter_buckets_expanded_example() {
+        let urn = from_list(vec![(2, 'R'), (0, 'G'), (3, 'B')]).unwrap();
+        let outcomes: String = urn.iter_buckets_expanded().collect();
+        assert_eq!(outcomes, "RRBBB");
+    }
+
+    #[test]
+    fn index_draw_strategies_cover_range() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for draw in [IndexDraw::Uniform, IndexDraw::Lemire, IndexDraw::Bitmask]
+        {
+            let mut counts = [0; 5];
+            for _ in 0..500 {
+                counts[draw_index_with(&mut rng, 5, draw) as usize] += 1;
+            }
+            assert!(counts.iter().all(|&c| c > 0), "{:?}", draw);
+            assert_eq!(draw_index_with(&mut rng, 1, draw), 0);
+            assert_eq!(draw_index_with(&mut rng, 0, draw), 0);
+        }
+
+        #[cfg(feature = "std-rng")]
+        {
+            let urn = from_list(vec![(1, 'a'), (2, 'b'), (3, 'c')])
+                .unwrap()
+                .with_index_draw(IndexDraw::Lemire);
+            let urn = urn.insert(4, 'd').remove().1.unwrap();
+            assert_eq!(urn.draw, IndexDraw::Lemire);
+        }
+    }
+
+    #[test]
+    fn from_chunks_example() {
+        let chunks = vec![
+            vec![(1, 'a'), (2, 'b')],
+            vec![],
+            vec![(3, 'c'), (4, 'd'), (5, 'e')],
+            vec![(6, 'f')],
+        ];
+        let urn = from_chunks(chunks).unwrap();
+        assert_eq!(urn.size(), 6);
+        assert_eq!(urn.weight(), 21);
+        let mut elems = urn.to_vec();
+        elems.sort();
+        assert_eq!(elems.iter().map(|(_, a)| *a).collect::<String>(), "abcdef");
+        assert!(from_chunks(vec![Vec::<(Weight, char)>::new()]).is_none());
+    }
+
+    // (Checking the invariants after every insertion would take `O(n^2)`)
+    #[test]
+    #[cfg_attr(feature = "debug-invariants", ignore)]
+    fn million_element_urns() {
+        const N: u32 = 1 << 20;
+        let elems = (0..N).map(|k| (Weight::from(k == 0), k));
+        let urn = from_iter_exact(elems).unwrap();
+        assert_eq!(urn.size(), N);
+        assert_eq!(urn.sample_index_ref(0), &0);
+
+        let urn = urn.insert(2, N);
+        assert_eq!(urn.weight(), 3);
+        let ((w, a), lb, urn) = urn.uninsert();
+        assert_eq!((w, a, lb), (2, N, 1));
+        assert_eq!(urn.map(|u| u.size()), Some(N));
+
+        let urn = (1..N).fold(singleton(1, 0), |urn, k| urn.insert(0, k));
+        assert_eq!(urn.size(), N);
+        assert_eq!(urn.sample_index_ref(0), &0);
+    }
+
+    #[test]
+    #[cfg(feature = "std-rng")]
+    fn update_returns_owned_values() {
+        let urn = from_list(vec![(1, String::from("a")), (0, "b".into())]);
+        // Only "a" has a positive weight, so it is always sampled
+        let (old, new, urn) = urn.unwrap().update(|w, mut a| {
+            a.push('!');
+            (w + 2, a)
+        });
+        assert_eq!(old, (1, "a".into()));
+        assert_eq!(new, (3, "a!".into()));
+        assert_eq!(urn.to_vec(), vec![(3, "a!".into()), (0, "b".into())]);
+    }
+
+    #[test]
+    fn update_all_rebuilds_weights() {
+        let urn = from_list(vec![(1, 'a'), (2, 'b'), (3, 'c')]).unwrap();
+        let urn = urn.update_all(|w, a| (w * 2, a.to_ascii_uppercase()));
+        assert_eq!(urn.to_vec(), vec![(2, 'A'), (4, 'B'), (6, 'C')]);
+        assert_eq!((urn.size(), urn.weight()), (3, 12));
+        assert_eq!(urn.tree.max_weight(), 6);
+        let urn = urn.update_all(|w, a| (w - 1, (a as u8) - b'A'));
+        assert_eq!(urn.to_vec(), vec![(1, 0), (3, 1), (5, 2)]);
+    }
 }
@@ -0,0 +1,266 @@
+use std::fmt;
+
+use crate::types::{widen, Index, Tree, Tree::*, Urn, Weight};
+
+/* -------------------------------------------------------------------------- */
+/*                            Invariant validation                            */
+/* -------------------------------------------------------------------------- */
+
+/// A violated invariant of an urn, as reported by `Urn::validate`.
+/// Nodes are identified by their path from the root, written as a string of
+/// `L`s and `R`s (the root's path is empty).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum InvariantViolation {
+    /// The total weight cached at a node differs from the (wrapping) sum of
+    /// the weights of the leaves below it
+    WeightMismatch {
+        path: String,
+        cached: Index,
+        actual: Index,
+    },
+    /// The maximum leaf weight cached at a node differs from the maximum
+    /// weight of the leaves below it
+    MaxWeightMismatch {
+        path: String,
+        cached: Weight,
+        actual: Weight,
+    },
+    /// The minimum leaf weight cached at a node differs from the minimum
+    /// weight of the leaves below it
+    MinWeightMismatch {
+        path: String,
+        cached: Weight,
+        actual: Weight,
+    },
+    /// A node with `n` leaves below it doesn't have `ceil(n / 2)` leaves in
+    /// its left subtree, so insertion paths no longer lead to the right leaves
+    ShapeMismatch { path: String, left: u64, right: u64 },
+    /// The urn's `size` differs from the no. of leaves in its tree
+    SizeMismatch { size: u32, leaves: u64 },
+}
+
+/// Formats a path for error messages
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "the root"
+    } else {
+        path
+    }
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::WeightMismatch {
+                path,
+                cached,
+                actual,
+            } => write!(
+                f,
+                "node at {} caches weight {} but its leaves weigh {}",
+                display_path(path),
+                cached,
+                actual
+            ),
+            InvariantViolation::MaxWeightMismatch {
+                path,
+                cached,
+                actual,
+            } => write!(
+                f,
+                "node at {} caches maximum weight {} but its heaviest leaf \
+                 weighs {}",
+                display_path(path),
+                cached,
+                actual
+            ),
+            InvariantViolation::MinWeightMismatch {
+                path,
+                cached,
+                actual,
+            } => write!(
+                f,
+                "node at {} caches minimum weight {} but its lightest leaf \
+                 weighs {}",
+                display_path(path),
+                cached,
+                actual
+            ),
+            InvariantViolation::ShapeMismatch { path, left, right } => write!(
+                f,
+                "node at {} has {} leaves on the left and {} on the right",
+                display_path(path),
+                left,
+                right
+            ),
+            InvariantViolation::SizeMismatch { size, leaves } => {
+                write!(f, "urn has size {} but {} leaves", size, leaves)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+/// The no. of leaves, total weight and maximum and minimum leaf weights
+/// of a subtree
+struct Summary {
+    leaves: u64,
+    weight: Index,
+    max: Weight,
+    min: Weight,
+}
+
+impl<T: Clone> Tree<T> {
+    /// Checks the invariants of the subtree at `path`, returning its summary
+    /// (`path` is restored before returning)
+    fn validate_at(
+        &self,
+        path: &mut String,
+    ) -> Result<Summary, InvariantViolation> {
+        let (w, m, k, l, r) = match self {
+            Leaf(w, _) => {
+                return Ok(Summary {
+                    leaves: 1,
+                    weight: widen(*w),
+                    max: *w,
+                    min: *w,
+                })
+            }
+            Node(w, m, k, l, r) => (*w, *m, *k, l, r),
+        };
+        path.push('L');
+        let l = l.validate_at(path)?;
+        path.pop();
+        path.push('R');
+        let r = r.validate_at(path)?;
+        path.pop();
+
+        let (weight, max) = (l.weight.wrapping_add(r.weight), l.max.max(r.max));
+        if w != weight {
+            return Err(InvariantViolation::WeightMismatch {
+                path: path.clone(),
+                cached: w,
+                actual: weight,
+            });
+        }
+        if m != max {
+            return Err(InvariantViolation::MaxWeightMismatch {
+                path: path.clone(),
+                cached: m,
+                actual: max,
+            });
+        }
+        let min = l.min.min(r.min);
+        if k != min {
+            return Err(InvariantViolation::MinWeightMismatch {
+                path: path.clone(),
+                cached: k,
+                actual: min,
+            });
+        }
+        let leaves = l.leaves + r.leaves;
+        if l.leaves != leaves.div_ceil(2) {
+            return Err(InvariantViolation::ShapeMismatch {
+                path: path.clone(),
+                left: l.leaves,
+                right: r.leaves,
+            });
+        }
+        Ok(Summary {
+            leaves,
+            weight,
+            max,
+            min,
+        })
+    }
+}
+
+impl<T: Clone> Urn<T> {
+    /// Checks the urn's invariants: every node caches the total, maximum and
+    /// minimum weight of the leaves below it, every node's left subtree has half of
+    /// its leaves (rounded up), as required for insertion paths (see
+    /// `insert`), and the urn's `size` is its no. of leaves. Returns the first
+    /// violation found (in post-order), e.g. after modifying the public
+    /// fields of an urn directly.
+    /// Time complexity: `O(n)`.
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        let summary = self.tree.validate_at(&mut String::new())?;
+        if u64::from(self.size) != summary.leaves {
+            return Err(InvariantViolation::SizeMismatch {
+                size: self.size,
+                leaves: summary.leaves,
+            });
+        }
+        Ok(())
+    }
+
+    /// With the `debug-invariants` feature enabled in debug builds, panics
+    /// if the urn violates its invariants (see `validate`).
+    /// Otherwise, does nothing.
+    #[inline]
+    pub(crate) fn debug_check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        if let Err(err) = self.validate() {
+            panic!("urn invariant violated: {}", err);
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urn::from_list;
+
+    #[test]
+    fn validate_reports_violations() {
+        let urn = from_list(vec![(1, 'a'), (2, 'b'), (3, 'c')]).unwrap();
+        assert_eq!(urn.validate(), Ok(()));
+
+        let mut bad = urn.clone();
+        bad.size = 4;
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantViolation::SizeMismatch { size: 4, leaves: 3 })
+        );
+
+        // The left subtree of the root is a node with two leaves
+        let mut bad = urn.clone();
+        if let Node(_, _, _, l, _) = &mut bad.tree {
+            if let Node(w, _, _, _, _) = l.as_mut() {
+                *w = 7;
+            }
+        }
+        let err = bad.validate().unwrap_err();
+        assert_eq!(
+            err,
+            InvariantViolation::WeightMismatch {
+                path: "L".into(),
+                cached: 7,
+                actual: 3
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "node at L caches weight 7 but its leaves weigh 3"
+        );
+
+        // Swapping the subtrees of the root breaks its shape
+        let mut bad = urn;
+        if let Node(_, _, _, l, r) = &mut bad.tree {
+            std::mem::swap(l, r);
+        }
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantViolation::ShapeMismatch {
+                path: String::new(),
+                left: 1,
+                right: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use rand::{rngs::StdRng, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    types::{Urn, Weight},
+    urn::singleton,
+};
+
+/* -------------------------------------------------------------------------- */
+/*                          `wasm-bindgen` bindings                           */
+/* -------------------------------------------------------------------------- */
+
+/// An urn of JavaScript values, exported to JavaScript as the class `Urn`.
+/// Each `JsUrn` owns its RNG, which can be seeded (via `Urn.withSeed`)
+/// to make procedural generation reproducible.
+///
+/// In JavaScript, weights are `number`s (which must fit in a `Weight`),
+/// and `sample` / `remove` return `undefined` if the urn is empty.
+#[wasm_bindgen(js_name = Urn)]
+pub struct JsUrn {
+    urn: Option<Urn<JsValue>>,
+    rng: StdRng,
+}
+
+impl Default for JsUrn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Urn)]
+impl JsUrn {
+    /// Creates an empty urn, whose RNG is seeded from the browser's
+    /// `crypto.getRandomValues`
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            urn: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Creates an empty urn whose RNG is seeded with `seed`
+    #[wasm_bindgen(js_name = withSeed)]
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            urn: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The no. of elements
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> u32 {
+        self.urn.as_ref().map_or(0, |urn| urn.size())
+    }
+
+    /// The total weight of all the elements (as a `number`, so totals above
+    /// `2^53` are rounded)
+    #[wasm_bindgen(getter)]
+    pub fn weight(&self) -> f64 {
+        self.urn.as_ref().map_or(0.0, |urn| urn.weight() as f64)
+    }
+
+    /// Inserts `value` with weight `weight`.
+    /// Time complexity: `O(log n)`.
+    pub fn insert(&mut self, weight: Weight, value: JsValue) {
+        self.urn = Some(match self.urn.take() {
+            None => singleton(weight, value),
+            Some(urn) => urn.insert(weight, value),
+        });
+    }
+
+    /// Randomly samples a value (without removing it).
+    /// Time complexity: `O(log n)`.
+    pub fn sample(&mut self) -> JsValue {
+        match &self.urn {
+            None => JsValue::UNDEFINED,
+            Some(urn) => urn.sample_ref(&mut self.rng).clone(),
+        }
+    }
+
+    /// Randomly removes a value, and returns it.
+    /// Time complexity: `O(log n)`.
+    pub fn remove(&mut self) -> JsValue {
+        let Some(urn) = self.urn.take() else {
+            return JsValue::UNDEFINED;
+        };
+        let i = urn.draw_index(&mut self.rng);
+        let ((_, value), rest) = urn.remove_index(i);
+        self.urn = rest;
+        value
+    }
+}